@@ -0,0 +1,14 @@
+// Arbitrary text parsed as a movie, anything that parses has to write out and parse again.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use chip8_core::movie::Movie;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(movie) = Movie::parse(text) {
+        Movie::parse(&movie.to_string()).unwrap();
+    }
+});
@@ -0,0 +1,34 @@
+// Arbitrary bytes loaded as a ROM, run for a few hundred frames with random key states.
+// First 8 bytes seed both the emulator and the keys, next one picks quirks, rest is the ROM.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use chip8_core::bus::Memory;
+use chip8_core::guard::Guard;
+use chip8_core::quirks::Quirks;
+use chip8_core::rng::Rng;
+use chip8_core::{Emulator, TICKS_PER_FRAME};
+
+const FRAMES: usize = 300;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 9 {
+        return;
+    }
+    let (seed, rest) = data.split_at(8);
+    let seed = u64::from_le_bytes(seed.try_into().unwrap());
+
+    // Guard is what desktop runs with, it reads every access too
+    let mut emu = Emulator::with_bus(Guard::new(Memory::new()), seed);
+    emu.set_quirks(Quirks::from_bits(rest[0]));
+    emu.load_data(&rest[1..]);
+
+    let mut keys = Rng::new(seed);
+    for _ in 0..FRAMES {
+        let held = (keys.next_byte() as u16) << 8 | keys.next_byte() as u16;
+        for key in 0..16 {
+            emu.keypress(key, held & (1 << key) != 0);
+        }
+        emu.run_frame(TICKS_PER_FRAME);
+    }
+});
@@ -0,0 +1,24 @@
+// Arbitrary bytes given to load_state. Buffers with the right size are the interesting ones,
+// so the input is also laid over a valid state, then whatever loads has to keep running.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use chip8_core::state::STATE_SIZE;
+use chip8_core::{Emulator, TICKS_PER_FRAME};
+
+fuzz_target!(|data: &[u8]| {
+    let mut emu = Emulator::with_seed(0);
+    let _ = emu.load_state(data);
+
+    let mut state = vec![0; STATE_SIZE];
+    emu.save_state(&mut state).unwrap();
+    // Keep header, so the rest gets past the magic and version checks
+    for (byte, value) in state.iter_mut().skip(5).zip(data) {
+        *byte = *value;
+    }
+    if emu.load_state(&state).is_ok() {
+        for _ in 0..100 {
+            emu.run_frame(TICKS_PER_FRAME);
+        }
+    }
+});
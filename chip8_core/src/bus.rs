@@ -0,0 +1,438 @@
+// Memory bus: every read and write of the interpreter goes through it, so memory can be
+// extended with peripherals, logged or protected without touching the CPU.
+// Buses wrap each other, e.g. Logged<Protected<Memory>, _> logs and then protects.
+use core::ops::Range;
+use crate::{ADDR_MASK, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Fonts live here, programs have no reason to write into it
+pub const FONT_AREA: Range<u16> = 0x000..0x050;
+// Where the COSMAC VIP kept its display, one bit per pixel
+pub const VIP_FRAMEBUFFER: u16 = 0xF00;
+
+
+// Emulator only gives addresses below RAM_SIZE
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, value: u8);
+
+    // Read of an instruction, buses telling code apart from data override it
+    fn fetch(&mut self, addr: u16) -> u16 {
+        (self.read(addr) as u16) << 8 | self.read((addr + 1) & ADDR_MASK) as u16
+    }
+
+    // Emulator wrote these bytes straight into RAM (fonts, ROM, save state)
+    fn loaded(&mut self, _range: Range<u16>) {}
+
+    // Emulator was reset and RAM cleared
+    fn reset(&mut self) {}
+
+    // Display was cleared or drawn to
+    fn display_changed(&mut self, _screen: &[bool]) {}
+
+    // Timers were updated at the end of a 60Hz frame
+    fn frame_ended(&mut self) {}
+
+    // Whole backing memory (RAM_SIZE bytes), bypasses peripherals. Used for loading ROMs,
+    // save states and debuggers
+    fn ram(&self) -> &[u8];
+    fn ram_mut(&mut self) -> &mut [u8];
+}
+
+// Plain RAM of the console
+#[derive(Clone)]
+pub struct Memory {
+    data: [u8; RAM_SIZE],
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self { data: [0; RAM_SIZE] }
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus for Memory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.data[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.data[addr as usize] = value;
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+// Device mapped into the address space, gets addresses relative to the start of its range
+pub trait Peripheral {
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, value: u8);
+
+    // Called once every 60Hz frame
+    fn tick(&mut self) {}
+
+    // Emulator was reset
+    fn reset(&mut self) {}
+}
+
+// Debug port: every byte written into it goes to the closure, e.g. to print it. Reads give 0
+pub struct ConsolePort<F: FnMut(u8)> {
+    output: F,
+}
+
+impl<F: FnMut(u8)> ConsolePort<F> {
+    pub fn new(output: F) -> Self {
+        Self { output }
+    }
+}
+
+impl<F: FnMut(u8)> Peripheral for ConsolePort<F> {
+    fn read(&mut self, _offset: u16) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _offset: u16, value: u8) {
+        (self.output)(value);
+    }
+}
+
+// Frames since reset as big endian u16 at offsets 0 and 1, writing anything restarts it
+#[derive(Clone, Debug, Default)]
+pub struct Timer {
+    frames: u16,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn frames(&self) -> u16 {
+        self.frames
+    }
+}
+
+impl Peripheral for Timer {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            0 => (self.frames >> 8) as u8,
+            1 => self.frames as u8,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {
+        self.frames = 0;
+    }
+
+    fn tick(&mut self) {
+        self.frames = self.frames.wrapping_add(1);
+    }
+
+    fn reset(&mut self) {
+        self.frames = 0;
+    }
+}
+
+// Bus with a peripheral covering part of memory, e.g. a console port or a timer
+pub struct Mapped<B: Bus, P: Peripheral> {
+    pub inner: B,
+    pub peripheral: P,
+    range: Range<u16>,
+}
+
+impl<B: Bus, P: Peripheral> Mapped<B, P> {
+    pub fn new(inner: B, range: Range<u16>, peripheral: P) -> Self {
+        Self { inner, peripheral, range }
+    }
+}
+
+impl<B: Bus, P: Peripheral> Bus for Mapped<B, P> {
+    fn read(&mut self, addr: u16) -> u8 {
+        if self.range.contains(&addr) {
+            self.peripheral.read(addr - self.range.start)
+        } else {
+            self.inner.read(addr)
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if self.range.contains(&addr) {
+            self.peripheral.write(addr - self.range.start, value);
+        } else {
+            self.inner.write(addr, value);
+        }
+    }
+
+    fn fetch(&mut self, addr: u16) -> u16 {
+        let next = (addr + 1) & ADDR_MASK;
+        if self.range.contains(&addr) || self.range.contains(&next) {
+            (self.read(addr) as u16) << 8 | self.read(next) as u16
+        } else {
+            self.inner.fetch(addr)
+        }
+    }
+
+    fn loaded(&mut self, range: Range<u16>) {
+        self.inner.loaded(range);
+    }
+
+    fn reset(&mut self) {
+        self.peripheral.reset();
+        self.inner.reset();
+    }
+
+    fn display_changed(&mut self, screen: &[bool]) {
+        self.inner.display_changed(screen);
+    }
+
+    fn frame_ended(&mut self) {
+        self.peripheral.tick();
+        self.inner.frame_ended();
+    }
+
+    fn ram(&self) -> &[u8] {
+        self.inner.ram()
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        self.inner.ram_mut()
+    }
+}
+
+// Bus copying the display into RAM at VIP_FRAMEBUFFER like the original VIP did, so programs
+// can read pixels back. Copy goes one way, writes into the area don't change the display
+pub struct VipFramebuffer<B: Bus> {
+    pub inner: B,
+}
+
+impl<B: Bus> VipFramebuffer<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner }
+    }
+}
+
+impl<B: Bus> Bus for VipFramebuffer<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.inner.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.inner.write(addr, value);
+    }
+
+    fn fetch(&mut self, addr: u16) -> u16 {
+        self.inner.fetch(addr)
+    }
+
+    fn loaded(&mut self, range: Range<u16>) {
+        self.inner.loaded(range);
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn display_changed(&mut self, screen: &[bool]) {
+        // Rows of 8 bytes, leftmost pixel in the highest bit
+        let start = VIP_FRAMEBUFFER as usize;
+        let end = start + SCREEN_WIDTH * SCREEN_HEIGHT / 8;
+        for (byte, pixels) in self.inner.ram_mut()[start..end].iter_mut().zip(screen.chunks_exact(8)) {
+            *byte = pixels.iter().fold(0, |byte, pixel| byte << 1 | *pixel as u8);
+        }
+        // Pixels count as written, e.g. for the Guard
+        self.inner.loaded(VIP_FRAMEBUFFER..end as u16);
+        self.inner.display_changed(screen);
+    }
+
+    fn frame_ended(&mut self) {
+        self.inner.frame_ended();
+    }
+
+    fn ram(&self) -> &[u8] {
+        self.inner.ram()
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        self.inner.ram_mut()
+    }
+}
+
+// What happens when program writes into a read-only region
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    // Write goes through, it's only counted
+    Allow,
+    // Write is dropped, like in ROM
+    Ignore,
+    // Emulation stops with a panic, for catching bugs in tests
+    Panic,
+}
+
+// Bus with read-only regions
+pub struct Protected<B: Bus> {
+    pub inner: B,
+    regions: [Option<Range<u16>>; 4],
+    policy: Violation,
+    violations: u32,
+    last_violation: Option<u16>,
+}
+
+impl<B: Bus> Protected<B> {
+    // Protects the font area, more regions can be added with protect
+    pub fn new(inner: B, policy: Violation) -> Self {
+        Self { inner, regions: [Some(FONT_AREA), None, None, None], policy, violations: 0, last_violation: None }
+    }
+
+    // Add read-only region, returns false if all slots are taken
+    pub fn protect(&mut self, range: Range<u16>) -> bool {
+        match self.regions.iter_mut().find(|region| region.is_none()) {
+            Some(slot) => {
+                *slot = Some(range);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: Violation) {
+        self.policy = policy;
+    }
+
+    // Number of writes into protected regions so far
+    pub fn violations(&self) -> u32 {
+        self.violations
+    }
+
+    // Address of the most recent forbidden write
+    pub fn last_violation(&self) -> Option<u16> {
+        self.last_violation
+    }
+
+    fn is_protected(&self, addr: u16) -> bool {
+        self.regions.iter().flatten().any(|region| region.contains(&addr))
+    }
+}
+
+impl<B: Bus> Bus for Protected<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.inner.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if self.is_protected(addr) {
+            self.violations += 1;
+            self.last_violation = Some(addr);
+            match self.policy {
+                Violation::Allow => (),
+                Violation::Ignore => return,
+                Violation::Panic => panic!("write of {:02X} into read-only {:03X}", value, addr),
+            }
+        }
+        self.inner.write(addr, value);
+    }
+
+    fn fetch(&mut self, addr: u16) -> u16 {
+        self.inner.fetch(addr)
+    }
+
+    fn loaded(&mut self, range: Range<u16>) {
+        self.inner.loaded(range);
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.violations = 0;
+        self.last_violation = None;
+    }
+
+    fn display_changed(&mut self, screen: &[bool]) {
+        self.inner.display_changed(screen);
+    }
+
+    fn frame_ended(&mut self) {
+        self.inner.frame_ended();
+    }
+
+    fn ram(&self) -> &[u8] {
+        self.inner.ram()
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        self.inner.ram_mut()
+    }
+}
+
+// Single memory access, given to the logger
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read { addr: u16, value: u8 },
+    Fetch { addr: u16, opcode: u16 },
+    Write { addr: u16, value: u8 },
+}
+
+// Bus reporting every access to a closure
+pub struct Logged<B: Bus, F: FnMut(Access)> {
+    pub inner: B,
+    log: F,
+}
+
+impl<B: Bus, F: FnMut(Access)> Logged<B, F> {
+    pub fn new(inner: B, log: F) -> Self {
+        Self { inner, log }
+    }
+}
+
+impl<B: Bus, F: FnMut(Access)> Bus for Logged<B, F> {
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.inner.read(addr);
+        (self.log)(Access::Read { addr, value });
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        (self.log)(Access::Write { addr, value });
+        self.inner.write(addr, value);
+    }
+
+    fn fetch(&mut self, addr: u16) -> u16 {
+        let opcode = self.inner.fetch(addr);
+        (self.log)(Access::Fetch { addr, opcode });
+        opcode
+    }
+
+    fn loaded(&mut self, range: Range<u16>) {
+        self.inner.loaded(range);
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn display_changed(&mut self, screen: &[bool]) {
+        self.inner.display_changed(screen);
+    }
+
+    fn frame_ended(&mut self) {
+        self.inner.frame_ended();
+    }
+
+    fn ram(&self) -> &[u8] {
+        self.inner.ram()
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        self.inner.ram_mut()
+    }
+}
@@ -0,0 +1,442 @@
+// Challenges: achievements unlocked by conditions on memory and registers, and a score read
+// from memory for leaderboards. They're text, one per line, "#" starts a comment:
+// achievement First line: ram[0x3F0] >= 1
+// achievement Marathon: ram[0x3F0] >= 10 && vA == 0 for 60 frames
+// score ram[0x2F0] * 100 + ram[0x2F1]
+//
+// Values are numbers (decimal or 0x hex), ram[address], registers v0-vF, i, pc, dt and st,
+// joined with + - * and parentheses. Conditions compare two values with == != < <= > >=
+// and join comparisons with && and ||. "for N frames" has to hold that many frames in a row.
+use std::cmp::Reverse;
+use std::fmt;
+use crate::bus::Bus;
+use crate::{Emulator, ADDR_MASK};
+
+// Best scores kept in a leaderboard
+pub const LEADERBOARD_SIZE: usize = 10;
+// Parentheses and ram[] nested deeper than this are refused, parsing and evaluation recurse
+const MAX_DEPTH: usize = 32;
+// Longer conditions are refused, chains like 1 + 1 + ... make deep trees as well
+const MAX_TOKENS: usize = 256;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+}
+
+// Number computed from the state of the emulator
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Ram(Box<Expr>),
+    Register(usize),
+    I,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+    Binary(Box<Expr>, Operator, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let mut parser = Parser::new(text)?;
+        let expr = parser.sum()?;
+        parser.end()?;
+        Ok(expr)
+    }
+
+    pub fn eval(&self, emu: &Emulator<impl Bus>) -> i64 {
+        match self {
+            Expr::Number(number) => *number,
+            Expr::Ram(address) => emu.ram()[(address.eval(emu) as u16 & ADDR_MASK) as usize] as i64,
+            Expr::Register(register) => emu.v_regs()[*register] as i64,
+            Expr::I => emu.i_reg() as i64,
+            Expr::Pc => emu.pc() as i64,
+            Expr::DelayTimer => emu.delay_timer() as i64,
+            Expr::SoundTimer => emu.sound_timer() as i64,
+            Expr::Binary(left, operator, right) => {
+                let (left, right) = (left.eval(emu), right.eval(emu));
+                match operator {
+                    Operator::Add => left.wrapping_add(right),
+                    Operator::Subtract => left.wrapping_sub(right),
+                    Operator::Multiply => left.wrapping_mul(right),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Test {
+    Compare(Expr, Comparison, Expr),
+    All(Vec<Test>),
+    Any(Vec<Test>),
+}
+
+impl Test {
+    fn eval(&self, emu: &Emulator<impl Bus>) -> bool {
+        match self {
+            Test::Compare(left, comparison, right) => {
+                let (left, right) = (left.eval(emu), right.eval(emu));
+                match comparison {
+                    Comparison::Equal => left == right,
+                    Comparison::NotEqual => left != right,
+                    Comparison::Less => left < right,
+                    Comparison::LessOrEqual => left <= right,
+                    Comparison::Greater => left > right,
+                    Comparison::GreaterOrEqual => left >= right,
+                }
+            }
+            Test::All(tests) => tests.iter().all(|test| test.eval(emu)),
+            Test::Any(tests) => tests.iter().any(|test| test.eval(emu)),
+        }
+    }
+}
+
+// Test which has to pass for a number of frames in a row
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    test: Test,
+    frames: u32,
+    // Frames it has passed so far
+    held: u32,
+}
+
+impl Condition {
+    // Read a condition like "ram[0x3F0] >= 10 for 60 frames"
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let (text, frames) = match text.rsplit_once(" for ") {
+            Some((test, duration)) => {
+                let duration = duration.trim();
+                let count = duration.strip_suffix("frames").or_else(|| duration.strip_suffix("frame"))
+                    .unwrap_or(duration).trim();
+                let frames = count.parse::<u32>().ok().filter(|frames| *frames > 0)
+                    .ok_or_else(|| format!("invalid duration {}", duration))?;
+                (test, frames)
+            }
+            None => (text, 1),
+        };
+
+        let mut parser = Parser::new(text)?;
+        let test = parser.any()?;
+        parser.end()?;
+        Ok(Condition { test, frames, held: 0 })
+    }
+
+    // Check it after a frame, true once it passed long enough
+    pub fn update(&mut self, emu: &Emulator<impl Bus>) -> bool {
+        self.held = if self.test.eval(emu) { self.held.saturating_add(1) } else { 0 };
+        self.held >= self.frames
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Achievement {
+    pub name: String,
+    pub condition: Condition,
+    pub unlocked: bool,
+}
+
+// Everything defined for one game
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Challenge {
+    pub achievements: Vec<Achievement>,
+    pub score: Option<Expr>,
+    // Highest score seen since the game started
+    best_score: Option<i64>,
+}
+
+impl Challenge {
+    pub fn parse(text: &str) -> Result<Challenge, String> {
+        let mut challenge = Challenge::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |err: String| format!("line {}: {}", number + 1, err);
+            let (kind, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match kind {
+                "achievement" => {
+                    let (name, condition) = rest.split_once(':')
+                        .ok_or_else(|| error("expected achievement name: condition".to_string()))?;
+                    let condition = Condition::parse(condition).map_err(error)?;
+                    challenge.achievements.push(Achievement { name: name.trim().to_string(), condition, unlocked: false });
+                }
+                "score" => challenge.score = Some(Expr::parse(rest).map_err(error)?),
+                _ => return Err(error(format!("unknown entry {}", kind))),
+            }
+        }
+        Ok(challenge)
+    }
+
+    // Check every condition after a frame, returns achievements unlocked by it
+    pub fn update(&mut self, emu: &Emulator<impl Bus>) -> Vec<&Achievement> {
+        if let Some(score) = &self.score {
+            let score = score.eval(emu);
+            self.best_score = Some(self.best_score.map_or(score, |best| best.max(score)));
+        }
+
+        let mut unlocked = Vec::new();
+        for achievement in self.achievements.iter_mut().filter(|achievement| !achievement.unlocked) {
+            if achievement.condition.update(emu) {
+                achievement.unlocked = true;
+                unlocked.push(&*achievement);
+            }
+        }
+        unlocked
+    }
+
+    // None when the game has no score
+    pub fn best_score(&self) -> Option<i64> {
+        self.best_score
+    }
+}
+
+// Best scores of one game, highest first
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Leaderboard {
+    pub entries: Vec<(i64, String)>,
+}
+
+impl Leaderboard {
+    // Read a leaderboard saved with to_string, one "score player" per line
+    pub fn parse(text: &str) -> Result<Leaderboard, String> {
+        let mut entries = text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                let (score, player) = line.trim().split_once(char::is_whitespace).unwrap_or((line.trim(), ""));
+                let score = score.parse::<i64>().map_err(|_| format!("line {}: invalid score {}", number + 1, score))?;
+                Ok((score, player.trim().to_string()))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        entries.sort_by_key(|(score, _)| Reverse(*score));
+        entries.truncate(LEADERBOARD_SIZE);
+        Ok(Leaderboard { entries })
+    }
+
+    // Add a score, returns its place counted from 0, None when it's not good enough
+    pub fn insert(&mut self, score: i64, player: &str) -> Option<usize> {
+        // Older scores stay above equal new ones
+        let place = self.entries.iter().position(|(best, _)| score > *best).unwrap_or(self.entries.len());
+        if place >= LEADERBOARD_SIZE {
+            return None;
+        }
+        self.entries.insert(place, (score, player.to_string()));
+        self.entries.truncate(LEADERBOARD_SIZE);
+        Some(place)
+    }
+
+    // True when the score would take the first place
+    pub fn is_record(&self, score: i64) -> bool {
+        self.entries.first().is_none_or(|(best, _)| score > *best)
+    }
+}
+
+impl fmt::Display for Leaderboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (score, player) in &self.entries {
+            writeln!(f, "{} {}", score, player)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+// Recursive descent over tokens of one condition or value
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    // Parentheses and ram[] the parser is in
+    depth: usize,
+}
+
+// Longer symbols first, so "<=" isn't read as "<"
+const SYMBOLS: [&str; 15] = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "(", ")", "[", "]"];
+
+impl Parser {
+    fn new(text: &str) -> Result<Parser, String> {
+        let mut tokens = Vec::new();
+        let mut rest = text.trim_start();
+        while !rest.is_empty() {
+            let length;
+            if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+                tokens.push(Token::Symbol(symbol));
+                length = symbol.len();
+            }
+            else {
+                length = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+                let word = &rest[..length];
+                if length == 0 {
+                    return Err(format!("unexpected {}", rest));
+                }
+                let number = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                    Some(digits) => i64::from_str_radix(digits, 16).ok(),
+                    None => word.parse().ok(),
+                };
+                match number {
+                    Some(number) => tokens.push(Token::Number(number)),
+                    None if word.starts_with(|c: char| c.is_ascii_digit()) => return Err(format!("invalid number {}", word)),
+                    None => tokens.push(Token::Name(word.to_ascii_lowercase())),
+                }
+            }
+            rest = rest[length..].trim_start();
+            if tokens.len() > MAX_TOKENS {
+                return Err(format!("longer than {} tokens", MAX_TOKENS));
+            }
+        }
+        Ok(Parser { tokens, position: 0, depth: 0 })
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    // Take the symbol if it comes next
+    fn eat(&mut self, symbol: &'static str) -> bool {
+        let found = self.tokens.get(self.position) == Some(&Token::Symbol(symbol));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        if self.eat(symbol) { Ok(()) } else { Err(format!("expected {}", symbol)) }
+    }
+
+    fn end(&self) -> Result<(), String> {
+        match self.tokens.get(self.position) {
+            None => Ok(()),
+            Some(token) => Err(format!("unexpected {}", describe(token))),
+        }
+    }
+
+    fn any(&mut self) -> Result<Test, String> {
+        let mut tests = vec![self.all()?];
+        while self.eat("||") {
+            tests.push(self.all()?);
+        }
+        Ok(if tests.len() == 1 { tests.remove(0) } else { Test::Any(tests) })
+    }
+
+    fn all(&mut self) -> Result<Test, String> {
+        let mut tests = vec![self.compare()?];
+        while self.eat("&&") {
+            tests.push(self.compare()?);
+        }
+        Ok(if tests.len() == 1 { tests.remove(0) } else { Test::All(tests) })
+    }
+
+    fn compare(&mut self) -> Result<Test, String> {
+        let left = self.sum()?;
+        let comparison = match self.next() {
+            Some(Token::Symbol("==")) => Comparison::Equal,
+            Some(Token::Symbol("!=")) => Comparison::NotEqual,
+            Some(Token::Symbol("<")) => Comparison::Less,
+            Some(Token::Symbol("<=")) => Comparison::LessOrEqual,
+            Some(Token::Symbol(">")) => Comparison::Greater,
+            Some(Token::Symbol(">=")) => Comparison::GreaterOrEqual,
+            Some(token) => return Err(format!("expected comparison, got {}", describe(&token))),
+            None => return Err("expected comparison".to_string()),
+        };
+        Ok(Test::Compare(left, comparison, self.sum()?))
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        loop {
+            let operator = if self.eat("+") {
+                Operator::Add
+            }
+            else if self.eat("-") {
+                Operator::Subtract
+            }
+            else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.value()?;
+        while self.eat("*") {
+            expr = Expr::Binary(Box::new(expr), Operator::Multiply, Box::new(self.value()?));
+        }
+        Ok(expr)
+    }
+
+    // Value inside of parentheses or brackets
+    fn nested(&mut self) -> Result<Expr, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("nested deeper than {}", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let expr = self.sum();
+        self.depth -= 1;
+        expr
+    }
+
+    fn value(&mut self) -> Result<Expr, String> {
+        let name = match self.next() {
+            Some(Token::Number(number)) => return Ok(Expr::Number(number)),
+            Some(Token::Symbol("(")) => {
+                let expr = self.nested()?;
+                self.expect(")")?;
+                return Ok(expr);
+            }
+            Some(Token::Name(name)) => name,
+            Some(token) => return Err(format!("expected value, got {}", describe(&token))),
+            None => return Err("expected value".to_string()),
+        };
+        match name.as_str() {
+            "ram" => {
+                self.expect("[")?;
+                let address = self.nested()?;
+                self.expect("]")?;
+                Ok(Expr::Ram(Box::new(address)))
+            }
+            "i" => Ok(Expr::I),
+            "pc" => Ok(Expr::Pc),
+            "dt" => Ok(Expr::DelayTimer),
+            "st" => Ok(Expr::SoundTimer),
+            _ => {
+                let register = name.strip_prefix('v').filter(|digit| digit.len() == 1)
+                    .and_then(|digit| usize::from_str_radix(digit, 16).ok())
+                    .ok_or_else(|| format!("unknown value {}", name))?;
+                Ok(Expr::Register(register))
+            }
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(number) => number.to_string(),
+        Token::Name(name) => name.clone(),
+        Token::Symbol(symbol) => symbol.to_string(),
+    }
+}
@@ -0,0 +1,124 @@
+// Cheats: codes writing a value into memory every frame, which freezes counters like lives
+// or patches code, and a search narrowing down where the game keeps such a counter.
+//
+// Codes are text, one per line, address and value in hex with an optional name,
+// disabled ones start with "-":
+// 2F0=03 lives
+// -3A1=FF skip intro
+use std::fmt;
+use crate::ADDR_MASK;
+
+
+// One memory write repeated every frame
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+    pub enabled: bool,
+    pub name: String,
+}
+
+impl Cheat {
+    pub fn new(address: u16, value: u8) -> Self {
+        Self { address: address & ADDR_MASK, value, enabled: true, name: String::new() }
+    }
+
+    // Read a code like "2F0=03 lives"
+    pub fn parse(code: &str) -> Result<Cheat, String> {
+        let code = code.trim();
+        let (enabled, code) = match code.strip_prefix('-') {
+            Some(rest) => (false, rest),
+            None => (true, code),
+        };
+        let (code, name) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+        let (address, value) = code.split_once('=').ok_or_else(|| format!("expected address=value, got {}", code))?;
+
+        let address = parse_hex(address).filter(|address| *address <= ADDR_MASK as u32)
+            .ok_or_else(|| format!("invalid address {}", address))?;
+        let value = parse_hex(value).filter(|value| *value <= u8::MAX as u32)
+            .ok_or_else(|| format!("invalid value {}", value))?;
+        Ok(Cheat { address: address as u16, value: value as u8, enabled, name: name.trim().to_string() })
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.enabled {
+            write!(f, "-")?;
+        }
+        write!(f, "{:03X}={:02X}", self.address, self.value)?;
+        if !self.name.is_empty() {
+            write!(f, " {}", self.name)?;
+        }
+        Ok(())
+    }
+}
+
+// Every code of the text, blank lines and "#" comments are skipped
+pub fn parse_cheats(text: &str) -> Result<Vec<Cheat>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(number, line)| Cheat::parse(line).map_err(|err| format!("line {}: {}", number + 1, err)))
+        .collect()
+}
+
+// Codes as text for parse_cheats, one per line
+pub fn format_cheats(cheats: &[Cheat]) -> String {
+    cheats.iter().map(|cheat| format!("{}\n", cheat)).collect()
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    let digits = text.trim().trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).ok()
+}
+
+// How memory has to compare with the last snapshot to stay in the search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compare {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Compare {
+    fn matches(self, before: u8, now: u8) -> bool {
+        match self {
+            Compare::Equal(value) => now == value,
+            Compare::Changed => now != before,
+            Compare::Unchanged => now == before,
+            Compare::Increased => now > before,
+            Compare::Decreased => now < before,
+        }
+    }
+}
+
+// Addresses which could hold the value looked for, fewer after every filter
+pub struct CheatSearch {
+    snapshot: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl CheatSearch {
+    // Every address is a candidate at first
+    pub fn new(ram: &[u8]) -> Self {
+        Self { snapshot: ram.to_vec(), candidates: (0..ram.len() as u16).collect() }
+    }
+
+    // Keep addresses which compare with the snapshot, then take a new one. Returns candidates left
+    pub fn filter(&mut self, ram: &[u8], compare: Compare) -> usize {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|address| {
+            let address = *address as usize;
+            compare.matches(snapshot[address], ram[address])
+        });
+        self.snapshot.copy_from_slice(ram);
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+}
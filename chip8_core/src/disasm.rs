@@ -0,0 +1,106 @@
+// Disassembler using the usual Cowgod mnemonics, e.g. "LD V1, 0x05" or "DRW V0, V1, 5".
+// Formatting is done lazily through Display, so nothing is allocated.
+use core::fmt;
+
+
+// Single decoded opcode, print it to get the mnemonic
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction(pub u16);
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let opcode = self.0;
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let n = opcode & 0x000F;
+        let nn = opcode & 0x00FF;
+        let nnn = opcode & 0x0FFF;
+
+        match (opcode >> 12, x, y, n) {
+            (0, 0, 0, 0) => write!(f, "NOP"),
+            (0, 0, 0xE, 0) => write!(f, "CLS"),
+            (0, 0, 0xE, 0xE) => write!(f, "RET"),
+            (0, _, _, _) => write!(f, "SYS 0x{:03X}", nnn),
+            (1, _, _, _) => write!(f, "JP 0x{:03X}", nnn),
+            (2, _, _, _) => write!(f, "CALL 0x{:03X}", nnn),
+            (3, _, _, _) => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            (4, _, _, _) => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            (5, _, _, 0) => write!(f, "SE V{:X}, V{:X}", x, y),
+            (6, _, _, _) => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            (7, _, _, _) => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            (8, _, _, 0) => write!(f, "LD V{:X}, V{:X}", x, y),
+            (8, _, _, 1) => write!(f, "OR V{:X}, V{:X}", x, y),
+            (8, _, _, 2) => write!(f, "AND V{:X}, V{:X}", x, y),
+            (8, _, _, 3) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            (8, _, _, 4) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            (8, _, _, 5) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            (8, _, _, 6) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            (8, _, _, 7) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            (8, _, _, 0xE) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            (9, _, _, 0) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            (0xA, _, _, _) => write!(f, "LD I, 0x{:03X}", nnn),
+            (0xB, _, _, _) => write!(f, "JP V0, 0x{:03X}", nnn),
+            (0xC, _, _, _) => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            (0xD, _, _, _) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            (0xE, _, 9, 0xE) => write!(f, "SKP V{:X}", x),
+            (0xE, _, 0xA, 1) => write!(f, "SKNP V{:X}", x),
+            (0xF, _, 0, 7) => write!(f, "LD V{:X}, DT", x),
+            (0xF, _, 0, 0xA) => write!(f, "LD V{:X}, K", x),
+            (0xF, _, 1, 5) => write!(f, "LD DT, V{:X}", x),
+            (0xF, _, 1, 8) => write!(f, "LD ST, V{:X}", x),
+            (0xF, _, 1, 0xE) => write!(f, "ADD I, V{:X}", x),
+            (0xF, _, 2, 9) => write!(f, "LD F, V{:X}", x),
+            (0xF, _, 3, 3) => write!(f, "LD B, V{:X}", x),
+            (0xF, _, 5, 5) => write!(f, "LD [I], V{:X}", x),
+            (0xF, _, 6, 5) => write!(f, "LD V{:X}, [I]", x),
+            // Not an instruction, probably data
+            _ => write!(f, "DW 0x{:04X}", opcode),
+        }
+    }
+}
+
+impl Instruction {
+    // Opcode with its operands replaced by letters, e.g. "8XY4", "????" if it's not an instruction
+    pub fn pattern(self) -> &'static str {
+        let opcode = self.0;
+        match (opcode >> 12, (opcode >> 8) & 0xF, (opcode >> 4) & 0xF, opcode & 0xF) {
+            (0, 0, 0, 0) => "0000",
+            (0, 0, 0xE, 0) => "00E0",
+            (0, 0, 0xE, 0xE) => "00EE",
+            (0, _, _, _) => "0NNN",
+            (1, _, _, _) => "1NNN",
+            (2, _, _, _) => "2NNN",
+            (3, _, _, _) => "3XNN",
+            (4, _, _, _) => "4XNN",
+            (5, _, _, 0) => "5XY0",
+            (6, _, _, _) => "6XNN",
+            (7, _, _, _) => "7XNN",
+            (8, _, _, 0) => "8XY0",
+            (8, _, _, 1) => "8XY1",
+            (8, _, _, 2) => "8XY2",
+            (8, _, _, 3) => "8XY3",
+            (8, _, _, 4) => "8XY4",
+            (8, _, _, 5) => "8XY5",
+            (8, _, _, 6) => "8XY6",
+            (8, _, _, 7) => "8XY7",
+            (8, _, _, 0xE) => "8XYE",
+            (9, _, _, 0) => "9XY0",
+            (0xA, _, _, _) => "ANNN",
+            (0xB, _, _, _) => "BNNN",
+            (0xC, _, _, _) => "CXNN",
+            (0xD, _, _, _) => "DXYN",
+            (0xE, _, 9, 0xE) => "EX9E",
+            (0xE, _, 0xA, 1) => "EXA1",
+            (0xF, _, 0, 7) => "FX07",
+            (0xF, _, 0, 0xA) => "FX0A",
+            (0xF, _, 1, 5) => "FX15",
+            (0xF, _, 1, 8) => "FX18",
+            (0xF, _, 1, 0xE) => "FX1E",
+            (0xF, _, 2, 9) => "FX29",
+            (0xF, _, 3, 3) => "FX33",
+            (0xF, _, 5, 5) => "FX55",
+            (0xF, _, 6, 5) => "FX65",
+            _ => "????",
+        }
+    }
+}
@@ -0,0 +1,326 @@
+// Presentation helpers shared by frontends: pixel-art upscalers, CRT scanlines and
+// integer scaling with letterboxing. All of them work on RGBA buffers made by
+// Emulator::write_rgba and write into buffers given by the caller.
+
+// Biggest buffer any scaler can produce from the display
+pub const MAX_OUTPUT_SIZE: usize = crate::RGBA_SIZE * 9;
+
+// Upscaling algorithm applied to the display
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Scale2x,
+    Scale3x,
+    Epx,
+    XbrLite,
+}
+
+impl Filter {
+    // Every available filter, in order frontends cycle through them
+    pub const ALL: [Filter; 5] = [
+        Filter::Nearest, Filter::Scale2x, Filter::Scale3x, Filter::Epx, Filter::XbrLite,
+    ];
+
+    // How many times the filter enlarges the image
+    pub fn factor(self) -> usize {
+        match self {
+            Filter::Nearest => 1,
+            Filter::Scale2x | Filter::Epx | Filter::XbrLite => 2,
+            Filter::Scale3x => 3,
+        }
+    }
+
+    // Name shown to the user
+    pub fn name(self) -> &'static str {
+        match self {
+            Filter::Nearest => "nearest",
+            Filter::Scale2x => "scale2x",
+            Filter::Scale3x => "scale3x",
+            Filter::Epx => "epx",
+            Filter::XbrLite => "xbr-lite",
+        }
+    }
+
+    // Find filter by its name
+    pub fn from_name(name: &str) -> Option<Filter> {
+        Self::ALL.iter().copied().find(|filter| filter.name() == name)
+    }
+
+    // Filter that comes after this one
+    pub fn next(self) -> Filter {
+        let index = Self::ALL.iter().position(|filter| *filter == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+// Filter together with optional CRT effect
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scaler {
+    pub filter: Filter,
+    pub scanlines: bool,
+}
+
+impl Default for Scaler {
+    fn default() -> Self {
+        Self { filter: Filter::Nearest, scanlines: false }
+    }
+}
+
+impl Scaler {
+    // Scale of output, scanlines need at least two rows per pixel
+    pub fn factor(&self) -> usize {
+        let factor = self.filter.factor();
+        if self.scanlines && factor == 1 { 2 } else { factor }
+    }
+
+    // Size of output buffer in bytes for source of given size
+    pub fn output_size(&self, width: usize, height: usize) -> usize {
+        let factor = self.factor();
+        width * factor * height * factor * 4
+    }
+
+    // Upscale source RGBA image into destination, apply scanlines if enabled
+    pub fn apply(&self, src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+        let factor = self.factor();
+        if factor != self.filter.factor() {
+            nearest(src, width, height, factor, dst);
+        }
+        else {
+            match self.filter {
+                Filter::Nearest => nearest(src, width, height, 1, dst),
+                Filter::Scale2x => scale2x(src, width, height, dst),
+                Filter::Scale3x => scale3x(src, width, height, dst),
+                Filter::Epx => epx(src, width, height, dst),
+                Filter::XbrLite => xbr_lite(src, width, height, dst),
+            }
+        }
+
+        if self.scanlines {
+            scanlines(dst, width * factor, height * factor, factor, 0.5);
+        }
+    }
+}
+
+// Read pixel, coordinates outside of image are clamped to its edge
+fn pixel(src: &[u8], width: usize, height: usize, x: isize, y: isize) -> [u8; 4] {
+    let x = x.clamp(0, width as isize - 1) as usize;
+    let y = y.clamp(0, height as isize - 1) as usize;
+    let index = (x + y * width) * 4;
+
+    [src[index], src[index + 1], src[index + 2], src[index + 3]]
+}
+
+// Write pixel into destination image
+fn put(dst: &mut [u8], width: usize, x: usize, y: usize, color: [u8; 4]) {
+    let index = (x + y * width) * 4;
+    dst[index..index + 4].copy_from_slice(&color);
+}
+
+// Average of two colors
+fn blend(a: [u8; 4], b: [u8; 4]) -> [u8; 4] {
+    let mut out = [0; 4];
+    for i in 0..4 {
+        out[i] = ((a[i] as u16 + b[i] as u16) / 2) as u8;
+    }
+    out
+}
+
+// Repeat every pixel factor times in both directions
+pub fn nearest(src: &[u8], width: usize, height: usize, factor: usize, dst: &mut [u8]) {
+    let out_width = width * factor;
+    for y in 0..height * factor {
+        for x in 0..out_width {
+            let color = pixel(src, width, height, (x / factor) as isize, (y / factor) as isize);
+            put(dst, out_width, x, y, color);
+        }
+    }
+}
+
+// AdvMAME2x: round corners where two neighbours agree
+pub fn scale2x(src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+    let out_width = width * 2;
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let p = pixel(src, width, height, x, y);
+            let a = pixel(src, width, height, x, y - 1);
+            let b = pixel(src, width, height, x + 1, y);
+            let c = pixel(src, width, height, x - 1, y);
+            let d = pixel(src, width, height, x, y + 1);
+
+            let mut out = [p; 4];
+            if a != d && c != b {
+                if c == a { out[0] = a; }
+                if a == b { out[1] = b; }
+                if d == c { out[2] = c; }
+                if b == d { out[3] = d; }
+            }
+
+            let (ox, oy) = (x as usize * 2, y as usize * 2);
+            put(dst, out_width, ox, oy, out[0]);
+            put(dst, out_width, ox + 1, oy, out[1]);
+            put(dst, out_width, ox, oy + 1, out[2]);
+            put(dst, out_width, ox + 1, oy + 1, out[3]);
+        }
+    }
+}
+
+// AdvMAME3x: same idea as Scale2x, on a 3x3 block
+pub fn scale3x(src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+    let out_width = width * 3;
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            // Neighbourhood named like in the original description:
+            // A B C
+            // D E F
+            // G H I
+            let a = pixel(src, width, height, x - 1, y - 1);
+            let b = pixel(src, width, height, x, y - 1);
+            let c = pixel(src, width, height, x + 1, y - 1);
+            let d = pixel(src, width, height, x - 1, y);
+            let e = pixel(src, width, height, x, y);
+            let f = pixel(src, width, height, x + 1, y);
+            let g = pixel(src, width, height, x - 1, y + 1);
+            let h = pixel(src, width, height, x, y + 1);
+            let i = pixel(src, width, height, x + 1, y + 1);
+
+            let mut out = [e; 9];
+            if b != h && d != f {
+                if d == b { out[0] = d; }
+                if (d == b && e != c) || (b == f && e != a) { out[1] = b; }
+                if b == f { out[2] = f; }
+                if (d == b && e != g) || (d == h && e != a) { out[3] = d; }
+                if (b == f && e != i) || (h == f && e != c) { out[5] = f; }
+                if d == h { out[6] = d; }
+                if (d == h && e != i) || (h == f && e != g) { out[7] = h; }
+                if h == f { out[8] = f; }
+            }
+
+            for (number, color) in out.iter().enumerate() {
+                put(dst, out_width, x as usize * 3 + number % 3, y as usize * 3 + number / 3, *color);
+            }
+        }
+    }
+}
+
+// Original EPX by Eric Johnston, corners don't change when three neighbours agree
+pub fn epx(src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+    let out_width = width * 2;
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let p = pixel(src, width, height, x, y);
+            let a = pixel(src, width, height, x, y - 1);
+            let b = pixel(src, width, height, x + 1, y);
+            let c = pixel(src, width, height, x - 1, y);
+            let d = pixel(src, width, height, x, y + 1);
+
+            let mut out = [p; 4];
+            if c == a { out[0] = a; }
+            if a == b { out[1] = b; }
+            if d == c { out[2] = c; }
+            if b == d { out[3] = d; }
+
+            let agreeing = [a == b, a == c, a == d, b == c, b == d, c == d]
+                .iter().filter(|same| **same).count();
+            if agreeing >= 3 {
+                out = [p; 4];
+            }
+
+            let (ox, oy) = (x as usize * 2, y as usize * 2);
+            put(dst, out_width, ox, oy, out[0]);
+            put(dst, out_width, ox + 1, oy, out[1]);
+            put(dst, out_width, ox, oy + 1, out[2]);
+            put(dst, out_width, ox + 1, oy + 1, out[3]);
+        }
+    }
+}
+
+// Simplified xBR: smooth diagonal edges by blending corners along them
+pub fn xbr_lite(src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+    let out_width = width * 2;
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let e = pixel(src, width, height, x, y);
+            let b = pixel(src, width, height, x, y - 1);
+            let d = pixel(src, width, height, x - 1, y);
+            let f = pixel(src, width, height, x + 1, y);
+            let h = pixel(src, width, height, x, y + 1);
+
+            // Corner is on an edge when both of its neighbours match, but not the center
+            let corner = |first: [u8; 4], second: [u8; 4], opposite: [u8; 4]| {
+                if first == second && first != e && opposite != first {
+                    blend(e, first)
+                }
+                else {
+                    e
+                }
+            };
+
+            let (ox, oy) = (x as usize * 2, y as usize * 2);
+            put(dst, out_width, ox, oy, corner(b, d, h));
+            put(dst, out_width, ox + 1, oy, corner(b, f, h));
+            put(dst, out_width, ox, oy + 1, corner(h, d, b));
+            put(dst, out_width, ox + 1, oy + 1, corner(h, f, b));
+        }
+    }
+}
+
+// Darken last row of every scaled pixel, imitating gaps between CRT scanlines
+pub fn scanlines(dst: &mut [u8], width: usize, height: usize, factor: usize, strength: f32) {
+    if factor < 2 {
+        return;
+    }
+
+    let keep = 1.0 - strength.clamp(0.0, 1.0);
+    for y in (factor - 1..height).step_by(factor) {
+        let row = &mut dst[y * width * 4..(y + 1) * width * 4];
+        for rgba in row.chunks_exact_mut(4) {
+            for channel in &mut rgba[..3] {
+                *channel = (*channel as f32 * keep) as u8;
+            }
+        }
+    }
+}
+
+// How the image gets fitted into the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleMode {
+    // Biggest whole multiple of the image that fits, rest is letterboxed
+    Integer,
+    // Fill as much as possible, keeping the aspect ratio
+    Aspect,
+    // Fill the whole window
+    Stretch,
+}
+
+// Area of the window the image is drawn to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Place image of given size in the middle of window
+pub fn fit(image_width: u32, image_height: u32, window_width: u32, window_height: u32,
+           mode: ScaleMode) -> Viewport {
+    let (width, height) = match mode {
+        ScaleMode::Integer => {
+            let scale = (window_width / image_width).min(window_height / image_height).max(1);
+            (image_width * scale, image_height * scale)
+        }
+        ScaleMode::Aspect => {
+            let scale = (window_width as f32 / image_width as f32)
+                .min(window_height as f32 / image_height as f32);
+            ((image_width as f32 * scale) as u32, (image_height as f32 * scale) as u32)
+        }
+        ScaleMode::Stretch => (window_width, window_height),
+    };
+
+    Viewport {
+        x: window_width.saturating_sub(width) / 2,
+        y: window_height.saturating_sub(height) / 2,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,400 @@
+// GDB remote serial protocol over TCP, so existing debuggers can attach to a running game.
+// Registers are described by a custom target description, in order:
+// V0-VF (8 bits), I (16), PC (16), SP, DT, ST (8 bits each), multi-byte ones little endian.
+//
+// Server never blocks, frontend calls poll every loop and run_frame instead of
+// Emulator::run_frame. While GDB has the game stopped run_frame does nothing.
+use std::fmt;
+use std::io::{self, Read};
+use std::net::{TcpListener, TcpStream};
+use gdbstub::arch::Arch;
+use gdbstub::common::Signal;
+use gdbstub::stub::state_machine::GdbStubStateMachine;
+use gdbstub::stub::{DisconnectReason, GdbStub, SingleThreadStopReason};
+use gdbstub::target::ext::base::singlethread::{SingleThreadBase, SingleThreadResume,
+                                              SingleThreadResumeOps, SingleThreadSingleStep,
+                                              SingleThreadSingleStepOps};
+use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::breakpoints::{Breakpoints, BreakpointsOps, SwBreakpoint, SwBreakpointOps};
+use gdbstub::target::{Target, TargetError, TargetResult};
+use crate::bus::Bus;
+use crate::{Emulator, ADDR_MASK, RAM_SIZE, REGS_NUM, STACK_SIZE};
+
+// Bytes of all registers sent together
+const REGISTERS_SIZE: usize = REGS_NUM + 2 + 2 + 3;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+
+pub enum Chip8Arch {}
+
+impl Arch for Chip8Arch {
+    type Usize = u16;
+    type Registers = Registers;
+    // GDB sends size of the breakpoint instruction, which doesn't matter here
+    type BreakpointKind = usize;
+    type RegId = ();
+
+    fn target_description_xml() -> Option<&'static str> {
+        Some(TARGET_XML)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Registers {
+    pub v_reg: [u8; REGS_NUM],
+    pub i_reg: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl gdbstub::arch::Registers for Registers {
+    type ProgramCounter = u16;
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn gdb_serialize(&self, mut write_byte: impl FnMut(Option<u8>)) {
+        let [i_low, i_high] = self.i_reg.to_le_bytes();
+        let [pc_low, pc_high] = self.pc.to_le_bytes();
+        let rest = [i_low, i_high, pc_low, pc_high, self.sp, self.delay_timer, self.sound_timer];
+        for byte in self.v_reg.iter().chain(&rest) {
+            write_byte(Some(*byte));
+        }
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        if bytes.len() != REGISTERS_SIZE {
+            return Err(());
+        }
+        let (v_reg, rest) = bytes.split_at(REGS_NUM);
+        self.v_reg.copy_from_slice(v_reg);
+        self.i_reg = u16::from_le_bytes([rest[0], rest[1]]);
+        self.pc = u16::from_le_bytes([rest[2], rest[3]]);
+        [self.sp, self.delay_timer, self.sound_timer] = [rest[4], rest[5], rest[6]];
+        Ok(())
+    }
+}
+
+// What GDB asked the emulator to do, kept inside the emulator
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Resume {
+    #[default]
+    Continue,
+    Step,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DebugState {
+    breakpoints: Vec<u16>,
+    resume: Resume,
+}
+
+impl<B: Bus> Target for Emulator<B> {
+    type Arch = Chip8Arch;
+    type Error = &'static str;
+
+    fn base_ops(&mut self) -> BaseOps<'_, Chip8Arch, &'static str> {
+        BaseOps::SingleThread(self)
+    }
+
+    fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus> SingleThreadBase for Emulator<B> {
+    fn read_registers(&mut self, regs: &mut Registers) -> TargetResult<(), Self> {
+        *regs = Registers {
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            pc: self.pc,
+            sp: self.sp as u8,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        };
+        Ok(())
+    }
+
+    fn write_registers(&mut self, regs: &Registers) -> TargetResult<(), Self> {
+        if regs.sp as usize > STACK_SIZE {
+            return Err(TargetError::NonFatal);
+        }
+        self.v_reg = regs.v_reg;
+        self.i_reg = regs.i_reg;
+        self.pc = regs.pc & ADDR_MASK;
+        self.sp = regs.sp as u16;
+        self.delay_timer = regs.delay_timer;
+        self.sound_timer = regs.sound_timer;
+        Ok(())
+    }
+
+    // Memory is accessed directly, debugger shouldn't trigger what the bus does on access
+    fn read_addrs(&mut self, start_addr: u16, data: &mut [u8]) -> TargetResult<usize, Self> {
+        let ram = self.bus.ram();
+        let start = (start_addr as usize).min(RAM_SIZE);
+        let len = data.len().min(RAM_SIZE - start);
+        data[..len].copy_from_slice(&ram[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_addrs(&mut self, start_addr: u16, data: &[u8]) -> TargetResult<(), Self> {
+        let start = start_addr as usize;
+        if start + data.len() > RAM_SIZE {
+            return Err(TargetError::NonFatal);
+        }
+        self.bus.ram_mut()[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+// Signals mean nothing to CHIP-8, they're ignored
+impl<B: Bus> SingleThreadResume for Emulator<B> {
+    fn resume(&mut self, _signal: Option<Signal>) -> Result<(), &'static str> {
+        self.debug.resume = Resume::Continue;
+        Ok(())
+    }
+
+    fn support_single_step(&mut self) -> Option<SingleThreadSingleStepOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus> SingleThreadSingleStep for Emulator<B> {
+    fn step(&mut self, _signal: Option<Signal>) -> Result<(), &'static str> {
+        self.debug.resume = Resume::Step;
+        Ok(())
+    }
+}
+
+impl<B: Bus> Breakpoints for Emulator<B> {
+    fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus> SwBreakpoint for Emulator<B> {
+    fn add_sw_breakpoint(&mut self, addr: u16, _kind: usize) -> TargetResult<bool, Self> {
+        if !self.debug.breakpoints.contains(&addr) {
+            self.debug.breakpoints.push(addr);
+        }
+        Ok(true)
+    }
+
+    fn remove_sw_breakpoint(&mut self, addr: u16, _kind: usize) -> TargetResult<bool, Self> {
+        let count = self.debug.breakpoints.len();
+        self.debug.breakpoints.retain(|breakpoint| *breakpoint != addr);
+        Ok(self.debug.breakpoints.len() != count)
+    }
+}
+
+type StateMachine<B> = GdbStubStateMachine<'static, Emulator<B>, TcpStream>;
+
+// Something frontend may want to tell the user about
+#[derive(Debug, PartialEq, Eq)]
+pub enum GdbEvent {
+    Connected(String),
+    // Why the debugger left
+    Disconnected(String),
+}
+
+impl fmt::Display for GdbEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GdbEvent::Connected(address) => write!(f, "Debugger connected from {}", address),
+            GdbEvent::Disconnected(reason) => write!(f, "Debugger disconnected: {}", reason),
+        }
+    }
+}
+
+// Listens on localhost, one debugger at a time
+pub struct GdbServer<B: Bus> {
+    listener: TcpListener,
+    gdb: Option<StateMachine<B>>,
+    // Ticks run in the current frame, GDB can stop it in the middle
+    ticks: usize,
+}
+
+impl<B: Bus> GdbServer<B> {
+    // Port 0 picks any free one, see port()
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, gdb: None, ticks: 0 })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |address| address.port())
+    }
+
+    pub fn connected(&self) -> bool {
+        self.gdb.is_some()
+    }
+
+    // Check if GDB holds the game stopped
+    pub fn halted(&self) -> bool {
+        !matches!(self.gdb, None | Some(GdbStubStateMachine::Running(_)))
+    }
+
+    // Accept a debugger and handle everything it sent, call it every loop even when paused
+    pub fn poll(&mut self, emu: &mut Emulator<B>) -> Option<GdbEvent> {
+        let mut connected = None;
+        if self.gdb.is_none() {
+            let (stream, address) = self.listener.accept().ok()?;
+            // Connection is polled through peek, everything else blocks
+            let started = stream.set_nonblocking(false)
+                .map_err(|err| err.to_string())
+                .and_then(|_| GdbStub::new(stream).run_state_machine(emu).map_err(|err| err.to_string()));
+            match started {
+                Ok(gdb) => {
+                    emu.debug = DebugState::default();
+                    self.gdb = Some(gdb);
+                    connected = Some(GdbEvent::Connected(address.to_string()));
+                }
+                Err(err) => return Some(GdbEvent::Disconnected(err)),
+            }
+        }
+
+        let gdb = self.gdb.take()?;
+        match pump(gdb, emu) {
+            Ok(GdbStubStateMachine::Disconnected(gdb)) => {
+                let reason = match gdb.get_reason() {
+                    DisconnectReason::Kill => "killed",
+                    _ => "detached",
+                };
+                self.disconnect(emu, reason.to_string())
+            }
+            Ok(gdb) => {
+                self.gdb = Some(gdb);
+                connected
+            }
+            Err(err) => self.disconnect(emu, err),
+        }
+    }
+
+    // Run one frame unless GDB stopped the game, it stops on breakpoints and after steps
+    pub fn run_frame(&mut self, emu: &mut Emulator<B>, ticks: usize) {
+        let gdb = match self.gdb.take() {
+            None => return emu.run_frame(ticks),
+            Some(GdbStubStateMachine::Running(gdb)) => gdb,
+            Some(gdb) => {
+                self.gdb = Some(gdb);
+                return;
+            }
+        };
+
+        let mut stop = None;
+        while self.ticks < ticks && stop.is_none() {
+            emu.tick();
+            self.ticks += 1;
+            if emu.debug.resume == Resume::Step {
+                stop = Some(SingleThreadStopReason::DoneStep);
+            }
+            else if emu.debug.breakpoints.contains(&emu.pc) {
+                stop = Some(SingleThreadStopReason::SwBreak(()));
+            }
+        }
+        if self.ticks == ticks {
+            emu.time_tick();
+            self.ticks = 0;
+        }
+
+        let Some(reason) = stop else {
+            self.gdb = Some(gdb.into());
+            return;
+        };
+        match gdb.report_stop(emu, reason) {
+            Ok(gdb) => self.gdb = Some(gdb),
+            // Lost connection shows up in the next poll
+            Err(_) => self.drop_connection(emu),
+        }
+    }
+
+    // Close the connection, e.g. when the game GDB debugs is replaced by another one.
+    // Otherwise a debugger holding the old game stopped would keep the new one stopped too
+    pub fn detach(&mut self, emu: &mut Emulator<B>, reason: &str) -> Option<GdbEvent> {
+        self.gdb.as_ref()?;
+        self.disconnect(emu, reason.to_string())
+    }
+
+    fn disconnect(&mut self, emu: &mut Emulator<B>, reason: String) -> Option<GdbEvent> {
+        self.drop_connection(emu);
+        Some(GdbEvent::Disconnected(reason))
+    }
+
+    // Game runs on its own again
+    fn drop_connection(&mut self, emu: &mut Emulator<B>) {
+        self.gdb = None;
+        self.ticks = 0;
+        emu.debug = DebugState::default();
+    }
+}
+
+// Feed everything waiting on the connection into the state machine
+fn pump<B: Bus>(mut gdb: StateMachine<B>, emu: &mut Emulator<B>) -> Result<StateMachine<B>, String> {
+    loop {
+        gdb = match gdb {
+            GdbStubStateMachine::Idle(mut inner) => match read_byte(inner.borrow_conn())? {
+                Some(byte) => inner.incoming_data(emu, byte).map_err(|err| err.to_string())?,
+                None => return Ok(GdbStubStateMachine::Idle(inner)),
+            },
+            GdbStubStateMachine::Running(mut inner) => match read_byte(inner.borrow_conn())? {
+                Some(byte) => inner.incoming_data(emu, byte).map_err(|err| err.to_string())?,
+                None => return Ok(GdbStubStateMachine::Running(inner)),
+            },
+            GdbStubStateMachine::CtrlCInterrupt(inner) => {
+                let reason = SingleThreadStopReason::Signal(Signal::SIGINT);
+                inner.interrupt_handled(emu, Some(reason)).map_err(|err| err.to_string())?
+            }
+            gdb @ GdbStubStateMachine::Disconnected(_) => return Ok(gdb),
+        };
+    }
+}
+
+// Next byte if one already arrived
+fn read_byte(conn: &mut TcpStream) -> Result<Option<u8>, String> {
+    let mut byte = [0];
+    conn.set_nonblocking(true).map_err(|err| err.to_string())?;
+    let read = conn.read(&mut byte);
+    conn.set_nonblocking(false).map_err(|err| err.to_string())?;
+    match read {
+        Ok(0) => Err("connection closed".to_string()),
+        Ok(_) => Ok(Some(byte[0])),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(err) => Err(err.to_string()),
+    }
+}
@@ -0,0 +1,208 @@
+// Memory guard: catches the most common ROM bugs, code overwriting itself (often through
+// FX55 or FX33 with a wrong I), writes into the interpreter area below 0x200 and reads of
+// memory that was never written. Every issue is reported once with PC of the instruction.
+use core::fmt;
+use core::ops::Range;
+use crate::bus::{Bus, Memory};
+use crate::{ADDR_MASK, RAM_SIZE, START_MEMORY_ADDR};
+
+// Issues kept for the report, later ones are only counted
+pub const MAX_ISSUES: usize = 32;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    // Write into a byte which was executed before
+    CodeOverwrite,
+    // Write into fonts or interpreter area
+    LowWrite,
+    // Read or fetch of a byte no one has written
+    UninitializedRead,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Issue {
+    pub kind: IssueKind,
+    // Accessed address
+    pub addr: u16,
+    // Instruction doing the access
+    pub pc: u16,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let what = match self.kind {
+            IssueKind::CodeOverwrite => "overwrote code",
+            IssueKind::LowWrite => "wrote below 0x200",
+            IssueKind::UninitializedRead => "read uninitialized memory",
+        };
+        write!(f, "{:03X}: {} at {:03X}", self.pc, what, self.addr)
+    }
+}
+
+// One bit for every byte of memory
+#[derive(Clone)]
+struct BitSet([u8; RAM_SIZE / 8]);
+
+impl BitSet {
+    fn get(&self, addr: u16) -> bool {
+        let addr = addr as usize % RAM_SIZE;
+        self.0[addr / 8] & (1 << (addr % 8)) != 0
+    }
+
+    fn set(&mut self, addr: u16) {
+        let addr = addr as usize % RAM_SIZE;
+        self.0[addr / 8] |= 1 << (addr % 8);
+    }
+
+    fn clear(&mut self) {
+        self.0 = [0; RAM_SIZE / 8];
+    }
+}
+
+// Bus watching accesses of the inner one
+pub struct Guard<B: Bus = Memory> {
+    pub inner: B,
+    enabled: bool,
+    break_on_issue: bool,
+    executed: BitSet,
+    initialized: BitSet,
+    // Address of the instruction being executed
+    pc: u16,
+    issues: [Issue; MAX_ISSUES],
+    issues_len: usize,
+    total: u32,
+    pending_break: bool,
+}
+
+impl<B: Bus> Guard<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            enabled: true,
+            break_on_issue: false,
+            executed: BitSet([0; RAM_SIZE / 8]),
+            initialized: BitSet([0; RAM_SIZE / 8]),
+            pc: START_MEMORY_ADDR,
+            issues: [Issue { kind: IssueKind::LowWrite, addr: 0, pc: 0 }; MAX_ISSUES],
+            issues_len: 0,
+            total: 0,
+            pending_break: false,
+        }
+    }
+
+    // Disabled guard keeps tracking memory, but doesn't report anything
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Ask for a break when an issue is found, see take_break
+    pub fn set_break_on_issue(&mut self, break_on_issue: bool) {
+        self.break_on_issue = break_on_issue;
+    }
+
+    // Check if an issue asked for a break since the last call. Check it after every tick
+    // to stop right after the faulty instruction, or after every frame
+    pub fn take_break(&mut self) -> bool {
+        core::mem::take(&mut self.pending_break)
+    }
+
+    // Distinct issues found so far, at most MAX_ISSUES
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues[..self.issues_len]
+    }
+
+    // Number of bad accesses, repeated ones included
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    // Check if byte was executed as code
+    pub fn is_code(&self, addr: u16) -> bool {
+        self.executed.get(addr)
+    }
+
+    fn report(&mut self, kind: IssueKind, addr: u16) {
+        if !self.enabled {
+            return;
+        }
+        let issue = Issue { kind, addr, pc: self.pc };
+        self.total += 1;
+        if self.issues().contains(&issue) {
+            return;
+        }
+        if self.issues_len < MAX_ISSUES {
+            self.issues[self.issues_len] = issue;
+            self.issues_len += 1;
+        }
+        self.pending_break |= self.break_on_issue;
+    }
+
+    fn check_read(&mut self, addr: u16) {
+        if !self.initialized.get(addr) {
+            self.report(IssueKind::UninitializedRead, addr);
+        }
+    }
+}
+
+impl<B: Bus> Bus for Guard<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.check_read(addr);
+        self.inner.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr < START_MEMORY_ADDR {
+            self.report(IssueKind::LowWrite, addr);
+        } else if self.executed.get(addr) {
+            self.report(IssueKind::CodeOverwrite, addr);
+        }
+        self.initialized.set(addr);
+        self.inner.write(addr, value);
+    }
+
+    fn fetch(&mut self, addr: u16) -> u16 {
+        self.pc = addr;
+        for byte in [addr, (addr + 1) & ADDR_MASK] {
+            self.check_read(byte);
+            self.executed.set(byte);
+        }
+        self.inner.fetch(addr)
+    }
+
+    fn loaded(&mut self, range: Range<u16>) {
+        for addr in range.clone() {
+            self.initialized.set(addr);
+        }
+        self.inner.loaded(range);
+    }
+
+    fn reset(&mut self) {
+        self.executed.clear();
+        self.initialized.clear();
+        self.issues_len = 0;
+        self.total = 0;
+        self.pending_break = false;
+        self.inner.reset();
+    }
+
+    fn display_changed(&mut self, screen: &[bool]) {
+        self.inner.display_changed(screen);
+    }
+
+    fn frame_ended(&mut self) {
+        self.inner.frame_ended();
+    }
+
+    fn ram(&self) -> &[u8] {
+        self.inner.ram()
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        self.inner.ram_mut()
+    }
+}
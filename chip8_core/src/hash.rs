@@ -0,0 +1,38 @@
+// 64-bit FNV-1a, used to identify ROMs and compare emulator states.
+// Not cryptographic, but stable across platforms and versions.
+#[derive(Clone, Copy, Debug)]
+pub struct Fnv64 {
+    hash: u64,
+}
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self { hash: 0xCBF2_9CE4_8422_2325 }
+    }
+}
+
+impl Fnv64 {
+    pub fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+// Hash of the whole buffer
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hasher = Fnv64::default();
+    hasher.write(data);
+    hasher.finish()
+}
+
+// Lowercase hex SHA-1, what ROMs are known by in the game database, cheats, challenges and movies
+#[cfg(feature = "std")]
+pub fn sha1(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
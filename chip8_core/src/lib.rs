@@ -1,23 +1,78 @@
-use rand::Rng;
-
-const REGS_NUM: usize = 16;
-const RAM_SIZE: usize = 4096;
+// Without the default std feature the core needs neither std nor heap, e.g. for microcontrollers
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod bus;
+#[cfg(feature = "std")]
+pub mod challenge;
+#[cfg(feature = "std")]
+pub mod cheat;
+pub mod disasm;
+pub mod filter;
+#[cfg(feature = "gdb")]
+pub mod gdb;
+pub mod guard;
+pub mod hash;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "std")]
+pub mod netplay;
+#[cfg(feature = "std")]
+pub mod profile;
+pub mod quirks;
+pub mod rng;
+pub mod state;
+#[cfg(feature = "std")]
+pub mod trace;
+
+use bus::{Bus, Memory};
+use hash::Fnv64;
+use quirks::Quirks;
+use rng::Rng;
+
+pub const REGS_NUM: usize = 16;
+pub const RAM_SIZE: usize = 4096;
+// Addresses wrap around at the end of memory
+pub const ADDR_MASK: u16 = RAM_SIZE as u16 - 1;
 const STACK_SIZE: usize = 16;
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 const KEYS_NUM: usize = 16;
 
-const START_MEMORY_ADDR: u16 = 0x200;
+pub const START_MEMORY_ADDR: u16 = 0x200;
 const FONTS_SIZE: usize = 80;
+// Biggest ROM that fits into memory after the start address
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - START_MEMORY_ADDR as usize;
 
 pub const CLOCK_SPEED: u64 = 600;
+// Instructions executed during one 60Hz frame
+pub const TICKS_PER_FRAME: usize = (CLOCK_SPEED / 60) as usize;
+
+// Size of display converted into RGBA bytes
+pub const RGBA_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT * 4;
+
+
+// Colors of lit and unlit pixels, used when converting display to RGBA
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub on: [u8; 4],
+    pub off: [u8; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            on: [255, 255, 255, 255],
+            off: [0, 0, 0, 255],
+        }
+    }
+}
 
 
 // Core emulator for Chip 8 system
-pub struct Emulator {
+pub struct Emulator<B: Bus = Memory> {
     pc: u16,
-    ram: [u8; RAM_SIZE],
+    bus: B,
     v_reg: [u8; REGS_NUM],
     i_reg: u16,
 
@@ -29,14 +84,42 @@ pub struct Emulator {
 
     screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
     keys: [bool; KEYS_NUM],
+
+    quirks: Quirks,
+    seed: u64,
+    rng: Rng,
+    // Number of 60Hz frames since reset
+    frame: u64,
+
+    #[cfg(feature = "std")]
+    tracer: Option<Box<trace::Tracer>>,
+    #[cfg(feature = "std")]
+    profiler: Option<Box<profile::Profiler>>,
+    #[cfg(feature = "std")]
+    cheats: Vec<cheat::Cheat>,
+    #[cfg(feature = "gdb")]
+    debug: gdb::DebugState,
 }
 
 impl Emulator {
-    // Create a new emulator
+    // Create a new emulator with random seed, without std use with_seed and a seed from hardware
+    #[cfg(feature = "std")]
     pub fn new() -> Self {
+        Self::with_seed(rng::random_seed())
+    }
+
+    // Create a new emulator, which always generates the same random numbers for given seed
+    pub fn with_seed(seed: u64) -> Self {
+        Emulator::with_bus(Memory::new(), seed)
+    }
+}
+
+impl<B: Bus> Emulator<B> {
+    // Create a new emulator on top of custom memory bus
+    pub fn with_bus(bus: B, seed: u64) -> Self {
         let mut emu = Self {
             pc: START_MEMORY_ADDR,
-            ram: [0; RAM_SIZE],
+            bus,
             v_reg: [0; REGS_NUM],
             i_reg: 0,
             sp: 0,
@@ -45,16 +128,30 @@ impl Emulator {
             sound_timer: 0,
             screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
             keys: [false; KEYS_NUM],
+            quirks: Quirks::default(),
+            seed,
+            rng: Rng::new(seed),
+            frame: 0,
+            #[cfg(feature = "std")]
+            tracer: None,
+            #[cfg(feature = "std")]
+            profiler: None,
+            #[cfg(feature = "std")]
+            cheats: Vec::new(),
+            #[cfg(feature = "gdb")]
+            debug: gdb::DebugState::default(),
         };
 
-        emu.ram[..FONTS_SIZE].copy_from_slice(&FONT_SET);
+        emu.bus.ram_mut()[..FONTS_SIZE].copy_from_slice(&FONT_SET);
+        emu.bus.loaded(0..FONTS_SIZE as u16);
         emu
     }
 
     // Reset console's state
     pub fn reset(&mut self) {
         self.pc = START_MEMORY_ADDR;
-        self.ram = [0; RAM_SIZE];
+        self.bus.ram_mut().fill(0);
+        self.bus.reset();
         self.v_reg = [0; REGS_NUM];
         self.i_reg = 0;
         self.sp = 0;
@@ -63,16 +160,149 @@ impl Emulator {
         self.sound_timer = 0;
         self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
         self.keys = [false; KEYS_NUM];
+        self.rng = Rng::new(self.seed);
+        self.frame = 0;
 
-        self.ram[..FONTS_SIZE].copy_from_slice(&FONT_SET);
+        self.bus.ram_mut()[..FONTS_SIZE].copy_from_slice(&FONT_SET);
+        self.bus.loaded(0..FONTS_SIZE as u16);
+    }
+
+    // Change seed of random numbers, it takes effect immediately and after every reset
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Change behaviour to match other interpreters
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    // Number of frames run since reset
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Run one 60Hz frame: given number of cycles, then timers
+    pub fn run_frame(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+        self.time_tick();
     }
 
     // Run one cycle of emulator
     pub fn tick(&mut self) {
+        #[cfg(feature = "std")]
+        if self.tracer.is_some() || self.profiler.is_some() {
+            return self.tick_traced();
+        }
+
         let opcode = self.fetch_opcode();
         self.execute(opcode)
     }
 
+    // Tick, which remembers what the instruction changed
+    #[cfg(feature = "std")]
+    fn tick_traced(&mut self) {
+        let pc = self.pc & ADDR_MASK;
+        let (v_reg, i_reg, sp, delay_timer, sound_timer) =
+            (self.v_reg, self.i_reg, self.sp, self.delay_timer, self.sound_timer);
+
+        let opcode = self.fetch_opcode();
+        self.execute(opcode);
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc, opcode);
+        }
+
+        let Some(tracer) = self.tracer.as_mut() else {
+            return;
+        };
+        if !tracer.wants(pc) {
+            return;
+        }
+        let mut changed = 0;
+        for (reg, (before, after)) in v_reg.iter().zip(self.v_reg).enumerate() {
+            if *before != after {
+                changed |= 1 << reg;
+            }
+        }
+        for (flag, differs) in [(trace::CHANGED_I, i_reg != self.i_reg), (trace::CHANGED_SP, sp != self.sp),
+                                (trace::CHANGED_DT, delay_timer != self.delay_timer),
+                                (trace::CHANGED_ST, sound_timer != self.sound_timer)] {
+            if differs {
+                changed |= flag;
+            }
+        }
+        tracer.record(trace::TraceEntry {
+            pc,
+            opcode,
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            sp: self.sp,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            changed,
+        });
+    }
+
+    // Attach tracer recording every executed instruction, None turns tracing off
+    #[cfg(feature = "std")]
+    pub fn set_tracer(&mut self, tracer: Option<trace::Tracer>) {
+        self.tracer = tracer.map(Box::new);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn tracer_mut(&mut self) -> Option<&mut trace::Tracer> {
+        self.tracer.as_deref_mut()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn take_tracer(&mut self) -> Option<trace::Tracer> {
+        self.tracer.take().map(|tracer| *tracer)
+    }
+
+    // Attach profiler counting executions per address and opcode, None turns it off
+    #[cfg(feature = "std")]
+    pub fn set_profiler(&mut self, profiler: Option<profile::Profiler>) {
+        self.profiler = profiler.map(Box::new);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn profiler(&self) -> Option<&profile::Profiler> {
+        self.profiler.as_deref()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn profiler_mut(&mut self) -> Option<&mut profile::Profiler> {
+        self.profiler.as_deref_mut()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn take_profiler(&mut self) -> Option<profile::Profiler> {
+        self.profiler.take().map(|profiler| *profiler)
+    }
+
+    // Cheats written into memory at the end of every frame, they stay over resets
+    #[cfg(feature = "std")]
+    pub fn cheats(&self) -> &[cheat::Cheat] {
+        &self.cheats
+    }
+
+    #[cfg(feature = "std")]
+    pub fn cheats_mut(&mut self) -> &mut Vec<cheat::Cheat> {
+        &mut self.cheats
+    }
+
     // Execute current opcode
     fn execute(&mut self, opcode: u16) {
         let part1: u16 = (opcode & 0xF000) >> 12;
@@ -82,14 +312,17 @@ impl Emulator {
 
         match (part1, part2, part3, part4) {
             // NOP - literally nothing
-            (0, 0, 0, 0) => return,
+            (0, 0, 0, 0) => (),
             // CLS - clear screen
             (0, 0, 0xE, 0) => {
                 self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+                self.bus.display_changed(&self.screen);
             },
             // RET - return from subroutine
             (0, 0, 0xE, 0xE) => {
-                self.pc = self.pop();
+                if let Some(addr) = self.pop() {
+                    self.pc = addr;
+                }
             }
             // JP, addr - jump to the given address
             (0x1, _, _, _) => {
@@ -134,14 +367,23 @@ impl Emulator {
             // OR Vx, Vy - perform OR on Vx, Vy, save result in Vx
             (0x8, _, _, 1) => {
                 self.v_reg[part2 as usize] |= self.v_reg[part3 as usize];
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xFusize] = 0;
+                }
             }
             // AND Vx, Vy - save result of AND operation between Vx and Vy in Vx
             (0x8, _, _, 2) => {
                 self.v_reg[part2 as usize] &= self.v_reg[part3 as usize];
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xFusize] = 0;
+                }
             }
             // XOR Vx, Vy - save result of XOR operation of Vx and Vy in Vx
             (0x8, _, _, 3) => {
                 self.v_reg[part2 as usize] ^= self.v_reg[part3 as usize];
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xFusize] = 0;
+                }
             }
             // ADD Vx, Vy - add Vx to Vy, set VF to 1 if there overflowed, save reminder in Vx
             (0x8, _, _, 4) => {
@@ -166,10 +408,12 @@ impl Emulator {
             // SHR Vx - set VF to the least significant bit of Vx, divide Vx by 2
             (0x8, _, _, 6) => {
                 let x = part2 as usize;
-                self.v_reg[0xFusize] = self.v_reg[x] &  1;
-                self.v_reg[x] >>= 1;
+                let value = if self.quirks.shift_uses_vy { self.v_reg[part3 as usize] } else { self.v_reg[x] };
+
+                self.v_reg[x] = value >> 1;
+                self.v_reg[0xFusize] = value & 1;
             }
-            // SUBN Vx, Vy - Set Vx to Vy - Vx, if there was an borrow, set VF to 1
+            // SUBN Vx, Vy - Set Vx to Vy - Vx, set VF to 1 if there wasn't a borrow
             (0x8, _, _, 7) => {
                 let x = part2 as usize;
                 let (new_vx, overflow) = self.v_reg[part3 as usize].overflowing_sub(
@@ -177,14 +421,15 @@ impl Emulator {
                 );
 
                 self.v_reg[x] = new_vx;
-                self.v_reg[0xFusize] = if overflow { 1 } else { 0 };
+                self.v_reg[0xFusize] = if overflow { 0 } else { 1 };
             }
             // SHL Vx - Set VF to the most significant bit of Vx, multiply Vx by two
             (0x8, _, _, 0xE) => {
                 let x = part2 as usize;
+                let value = if self.quirks.shift_uses_vy { self.v_reg[part3 as usize] } else { self.v_reg[x] };
 
-                self.v_reg[0xFusize] = (self.v_reg[x] >> 7) & 1;
-                self.v_reg[x] <<= 1;
+                self.v_reg[x] = value << 1;
+                self.v_reg[0xFusize] = (value >> 7) & 1;
             }
             // SNE Vx, Vy - Skip next instruction if Vx != Vy
             (0x9, _, _, 0) => {
@@ -198,28 +443,35 @@ impl Emulator {
             }
             // JP V0, addr - jump to location addr + V0
             (0xB, _, _, _) => {
-                self.pc = (opcode & 0x0FFF) + self.v_reg[0] as u16;
+                let offset = if self.quirks.jump_uses_vx { self.v_reg[part2 as usize] } else { self.v_reg[0] };
+                self.pc = (opcode & 0x0FFF) + offset as u16;
             }
             // RND Vx, byte - store result of random number & kk in Vx
             (0xC, _, _, _) => {
-                let rnd: u8 = rand::thread_rng().gen();
+                let rnd: u8 = self.rng.next_byte();
                 self.v_reg[part2 as usize] = rnd & (opcode & 0x0FF) as u8;
             }
             // DRW Vx, Vy, nibble - display n-byte sprite at memory location I, position (Vx, Vy)
             // set VF to collision
             (0xD, _, _, _) => {
-                self.v_reg[0xFusize] = 0;
-
-                let x_coord: u16 = self.v_reg[part2 as usize] as u16;
-                let y_coord: u16 = self.v_reg[part3 as usize] as u16;
+                // Starting position always wraps around the screen
+                let x_coord: u16 = self.v_reg[part2 as usize] as u16 % SCREEN_WIDTH as u16;
+                let y_coord: u16 = self.v_reg[part3 as usize] as u16 % SCREEN_HEIGHT as u16;
 
                 let mut flipped: bool = false;
 
                 for y_line in 0..part4 {
-                    let pixels = self.ram[(self.i_reg + y_line) as usize];
+                    let pixels = self.bus.read(self.i_addr(y_line));
 
                     for x_line in 0..8 {
                         if (pixels & (0b10000000 >> x_line)) != 0 {
+                            // Pixels that go past the edge are either clipped or wrapped
+                            let past_edge = x_coord + x_line >= SCREEN_WIDTH as u16
+                                || y_coord + y_line >= SCREEN_HEIGHT as u16;
+                            if past_edge && self.quirks.clip_sprites {
+                                continue;
+                            }
+
                             let x = (x_coord + x_line) as usize % SCREEN_WIDTH;
                             let y = (y_coord + y_line) as usize % SCREEN_HEIGHT;
 
@@ -236,17 +488,18 @@ impl Emulator {
                 else {
                     self.v_reg[0xFusize] = 0;
                 }
+                self.bus.display_changed(&self.screen);
 
             }
             // SKP Vx - skip next instruction if key with an index of Vx is pressed
             (0xE, _, 9, 0xE) => {
-                if self.keys[self.v_reg[part2 as usize] as usize] {
+                if self.keys[(self.v_reg[part2 as usize] & 0xF) as usize] {
                     self.pc += 2;
                 }
             }
             // SKNP Vx - skip next instruction if key with an index of Vx isn't pressed
             (0xE, _, 0xA, 1) => {
-                if !self.keys[self.v_reg[part2 as usize] as usize] {
+                if !self.keys[(self.v_reg[part2 as usize] & 0xF) as usize] {
                     self.pc += 2;
                 }
             }
@@ -263,7 +516,7 @@ impl Emulator {
                     }
                 }
 
-                self.pc -= 2;
+                self.pc = self.pc.wrapping_sub(2) & ADDR_MASK;
             }
             // LD DT, Vx - set delay timer to Vx
             (0xF, _, 1, 5) => {
@@ -283,36 +536,48 @@ impl Emulator {
             }
             // LD B, Vx - store binary decimal of a number in memory, starting at location I
             (0xF, _, 3, 3) => {
-                let vx = self.v_reg[part2 as usize] as f32;
+                let vx = self.v_reg[part2 as usize];
 
-                self.ram[self.i_reg as usize] = (vx / 100.0).floor() as u8;
-                self.ram[(self.i_reg + 1) as usize] = ((vx / 10.0) % 10.0).floor() as u8;
-                self.ram[(self.i_reg + 2) as usize] = (vx % 10.0) as u8;
+                self.bus.write(self.i_addr(0), vx / 100);
+                self.bus.write(self.i_addr(1), (vx / 10) % 10);
+                self.bus.write(self.i_addr(2), vx % 10);
             }
             // LD [I], Vx - store registers V0 to Vx in memory starting at location I
             (0xF, _, 5, 5) => {
                 for num in 0..=part2 {
-                    self.ram[(self.i_reg + num) as usize] = self.v_reg[num as usize];
+                    self.bus.write(self.i_addr(num), self.v_reg[num as usize]);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i_reg = self.i_reg.wrapping_add(part2 + 1);
                 }
             }
             // LD Vx, [I] - read register from V0 to Vx staring at memory location I
             (0xF, _, 6, 5) => {
                 for num in 0..=part2 {
-                    self.v_reg[num as usize] = self.ram[(self.i_reg + num) as usize];
+                    self.v_reg[num as usize] = self.bus.read(self.i_addr(num));
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i_reg = self.i_reg.wrapping_add(part2 + 1);
                 }
             }
-            // Error safeguard
-            (_, _, _, _) => unimplemented!("Opcode {} is unimplemented", opcode),
+            // Unknown opcodes are skipped, ROMs can't be trusted to contain only valid ones
+            (_, _, _, _) => (),
         }
     }
 
+    // Address I + offset, wrapped into memory
+    fn i_addr(&self, offset: u16) -> u16 {
+        self.i_reg.wrapping_add(offset) & ADDR_MASK
+    }
+
     // Detect next opcode
     fn fetch_opcode(&mut self) -> u16 {
-        let higher_byte = self.ram[self.pc as usize] as u16;
-        let lower_byte = self.ram[(self.pc + 1) as usize] as u16;
+        // Skips and jumps with offset can point past the end of memory, then PC wraps around
+        let pc = self.pc & ADDR_MASK;
+        let opcode = self.bus.fetch(pc);
 
-        self.pc += 2;
-        (higher_byte << 8) | lower_byte
+        self.pc = (pc + 2) & ADDR_MASK;
+        opcode
     }
 
     // Decrease time, handle timing out
@@ -327,17 +592,114 @@ impl Emulator {
             }
             self.sound_timer -= 1;
         }
+
+        #[cfg(feature = "std")]
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            let address = cheat.address & ADDR_MASK;
+            self.bus.ram_mut()[address as usize] = cheat.value;
+            self.bus.loaded(address..address + 1);
+        }
+
+        self.bus.frame_ended();
+        self.frame += 1;
+    }
+
+    // Hash of everything that affects emulation, equal states give equal hashes
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv64::default();
+        hasher.write(&self.pc.to_le_bytes());
+        hasher.write(self.bus.ram());
+        hasher.write(&self.v_reg);
+        hasher.write(&self.i_reg.to_le_bytes());
+        hasher.write(&self.sp.to_le_bytes());
+        for value in self.stack {
+            hasher.write(&value.to_le_bytes());
+        }
+        hasher.write(&[self.delay_timer, self.sound_timer, self.quirks.to_bits()]);
+        for pixels in self.screen.chunks(8) {
+            hasher.write(&[pixels.iter().fold(0, |byte, pixel| (byte << 1) | *pixel as u8)]);
+        }
+        hasher.write(&self.rng.state().to_le_bytes());
+        hasher.write(&self.frame.to_le_bytes());
+        hasher.finish()
+    }
+
+    // Registers and timers, for debuggers and frontends showing them
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i_reg(&self) -> u16 {
+        self.i_reg
+    }
+
+    pub fn v_regs(&self) -> &[u8] {
+        &self.v_reg
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    // Changing registers from outside, for scripts and tools poking at a running game
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc & ADDR_MASK;
+    }
+
+    pub fn set_i_reg(&mut self, value: u16) {
+        self.i_reg = value;
+    }
+
+    pub fn v_regs_mut(&mut self) -> &mut [u8] {
+        &mut self.v_reg
+    }
+
+    // Whole memory, for debuggers and frontends exposing it
+    pub fn ram(&self) -> &[u8] {
+        self.bus.ram()
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [u8] {
+        self.bus.ram_mut()
     }
 
-    // Push value onto stack
+    // Memory bus, e.g. to talk to its peripherals
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    // Check if beeper should be sounding
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    // Push value onto stack, when it's full the value is lost
     fn push(&mut self, val: u16) {
-        self.stack[self.sp as usize] = val;
-        self.sp += 1;
+        if let Some(slot) = self.stack.get_mut(self.sp as usize) {
+            *slot = val;
+            self.sp += 1;
+        }
     }
-    // Pop value out of stack
-    fn pop(&mut self) -> u16 {
-        self.sp -= 1;
-        self.stack[self.sp as usize]
+    // Pop value out of stack, None if it's empty
+    fn pop(&mut self) -> Option<u16> {
+        self.sp = self.sp.checked_sub(1)?;
+        Some(self.stack[self.sp as usize])
     }
 
     // Get access to console's display
@@ -345,15 +707,50 @@ impl Emulator {
         &self.screen
     }
 
+    // Convert display into RGBA pixels, buffer should be at least RGBA_SIZE long
+    pub fn write_rgba(&self, buffer: &mut [u8], palette: &Palette) {
+        for (pixel, rgba) in self.screen.iter().zip(buffer.chunks_exact_mut(4)) {
+            rgba.copy_from_slice(if *pixel { &palette.on } else { &palette.off });
+        }
+    }
+
     // Set key states
     pub fn keypress(&mut self, index: usize, pressed: bool) {
         self.keys[index] = pressed;
     }
 
-    // Load data to RAM
+    // Load data to RAM, whatever doesn't fit after START_MEMORY_ADDR is cut off
     pub fn load_data(&mut self, data: &[u8]) {
-        let end: usize = (START_MEMORY_ADDR + data.len() as u16) as usize;
-        self.ram[START_MEMORY_ADDR as usize..end].copy_from_slice(data);
+        let data = &data[..data.len().min(MAX_ROM_SIZE)];
+        let end: usize = START_MEMORY_ADDR as usize + data.len();
+        self.bus.ram_mut()[START_MEMORY_ADDR as usize..end].copy_from_slice(data);
+        self.bus.loaded(START_MEMORY_ADDR..end as u16);
+    }
+
+    // Reset and load a new build of the ROM. Registers, stack and timers can be kept,
+    // so the game goes on from where it was instead of the beginning
+    pub fn reload(&mut self, data: &[u8], keep_registers: bool) {
+        let (pc, v_reg, i_reg, sp, stack) = (self.pc, self.v_reg, self.i_reg, self.sp, self.stack);
+        let (delay_timer, sound_timer) = (self.delay_timer, self.sound_timer);
+        self.reset();
+        self.load_data(data);
+
+        if keep_registers {
+            self.pc = pc;
+            self.v_reg = v_reg;
+            self.i_reg = i_reg;
+            self.sp = sp;
+            self.stack = stack;
+            self.delay_timer = delay_timer;
+            self.sound_timer = sound_timer;
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
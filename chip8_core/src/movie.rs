@@ -0,0 +1,311 @@
+// Input movies: every key press with the frame it happened on, enough to replay
+// a session exactly. Movies are plain text, so they can be attached to tickets and diffed.
+//
+// chip8-movie 2
+// emulator 0.1.0
+// rom 2a6b1ed6a8df4e2b3e9f9c5f0f2d1e7b8c4a3d21    SHA-1 of the ROM
+// seed 42
+// ticks 10
+// quirks 00
+// k 120 5 1       key 5 pressed before frame 120
+// h 180 00ff...   state hash after frame 180 finished
+use std::fmt;
+use std::ops::RangeInclusive;
+use crate::hash::sha1;
+use crate::quirks::Quirks;
+use crate::bus::Bus;
+use crate::Emulator;
+
+const MAGIC: &str = "chip8-movie";
+const FORMAT_VERSION: u32 = 2;
+// How often recorder stores state hash to detect desyncs
+pub const DEFAULT_HASH_INTERVAL: u64 = 60;
+// Instructions per frame a movie may ask for, more than this is a broken file
+const MAX_TICKS: u64 = 1000;
+
+
+// Everything needed to start emulator the same way as when movie was recorded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MovieHeader {
+    pub emulator_version: String,
+    // SHA-1 in lowercase hex
+    pub rom_hash: String,
+    pub seed: u64,
+    pub ticks_per_frame: usize,
+    pub quirks: Quirks,
+}
+
+// Single recorded event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieEvent {
+    // Key changed state before given frame was run
+    Key { frame: u64, key: u8, pressed: bool },
+    // State hash after given frame finished
+    Hash { frame: u64, hash: u64 },
+}
+
+impl MovieEvent {
+    pub fn frame(&self) -> u64 {
+        match self {
+            MovieEvent::Key { frame, .. } | MovieEvent::Hash { frame, .. } => *frame,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub header: MovieHeader,
+    pub events: Vec<MovieEvent>,
+}
+
+// Problems with reading or playing a movie
+#[derive(Debug, PartialEq, Eq)]
+pub enum MovieError {
+    // File isn't a movie or it's broken, along with line number
+    Parse { line: usize, message: String },
+    // Movie was recorded with a different ROM
+    RomMismatch { expected: String, actual: String },
+    // Emulation went different way than when recording
+    Desync { frame: u64, expected: u64, actual: u64 },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            MovieError::RomMismatch { expected, actual } => {
+                write!(f, "movie was recorded with ROM {}, got {}", expected, actual)
+            }
+            MovieError::Desync { frame, expected, actual } => {
+                write!(f, "desync at frame {}: expected state {:016x}, got {:016x}", frame, expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl Movie {
+    // Empty movie for emulator which was just reset and loaded with the ROM
+    pub fn new(emu: &Emulator<impl Bus>, rom: &[u8], ticks_per_frame: usize) -> Self {
+        Self {
+            header: MovieHeader {
+                emulator_version: env!("CARGO_PKG_VERSION").to_string(),
+                rom_hash: sha1(rom),
+                seed: emu.seed(),
+                ticks_per_frame,
+                quirks: emu.quirks(),
+            },
+            events: Vec::new(),
+        }
+    }
+
+    // Number of frames covered by the movie
+    pub fn length(&self) -> u64 {
+        self.events.last().map_or(0, |event| event.frame() + 1)
+    }
+
+    // Read movie from its text form
+    pub fn parse(text: &str) -> Result<Movie, MovieError> {
+        let mut lines = text.lines().enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        // Read next header line, which has to start with given key
+        let mut field = |key: &str| -> Result<(usize, String), MovieError> {
+            let (line, text) = lines.next().ok_or_else(|| MovieError::Parse {
+                line: 0, message: format!("missing {}", key),
+            })?;
+            match text.split_once(' ') {
+                Some((found, value)) if found == key => Ok((line, value.trim().to_string())),
+                _ => Err(MovieError::Parse { line, message: format!("expected {}", key) }),
+            }
+        };
+
+        let (line, version) = field(MAGIC)?;
+        if version != FORMAT_VERSION.to_string() {
+            return Err(MovieError::Parse { line, message: format!("unsupported version {}", version) });
+        }
+        let (_, emulator_version) = field("emulator")?;
+        let (line, rom_hash) = field("rom")?;
+        if rom_hash.len() != 40 || !rom_hash.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f')) {
+            return Err(MovieError::Parse { line, message: format!("invalid ROM SHA-1 {}", rom_hash) });
+        }
+        let (line, seed) = field("seed")?;
+        let seed = parse_number(&seed, 10, line)?;
+        let (line, ticks) = field("ticks")?;
+        let ticks_per_frame = parse_limited(&ticks, 10, 1..=MAX_TICKS, line)? as usize;
+        let (line, quirks) = field("quirks")?;
+        let quirks = Quirks::from_bits(parse_limited(&quirks, 16, 0..=0xFF, line)? as u8);
+
+        let mut events = Vec::new();
+        for (line, text) in lines {
+            let parts: Vec<&str> = text.split_whitespace().collect();
+            let event = match parts.as_slice() {
+                ["k", frame, key, pressed] => MovieEvent::Key {
+                    frame: parse_number(frame, 10, line)?,
+                    key: parse_limited(key, 16, 0..=0xF, line)? as u8,
+                    pressed: match *pressed {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(MovieError::Parse { line, message: format!("invalid key state {}", pressed) }),
+                    },
+                },
+                ["h", frame, hash] => MovieEvent::Hash {
+                    frame: parse_number(frame, 10, line)?,
+                    hash: parse_number(hash, 16, line)?,
+                },
+                _ => return Err(MovieError::Parse { line, message: format!("unknown event {}", text) }),
+            };
+
+            if events.last().is_some_and(|last: &MovieEvent| last.frame() > event.frame()) {
+                return Err(MovieError::Parse { line, message: "events are out of order".to_string() });
+            }
+            events.push(event);
+        }
+
+        Ok(Movie {
+            header: MovieHeader { emulator_version, rom_hash, seed, ticks_per_frame, quirks },
+            events,
+        })
+    }
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = &self.header;
+        writeln!(f, "{} {}", MAGIC, FORMAT_VERSION)?;
+        writeln!(f, "emulator {}", header.emulator_version)?;
+        writeln!(f, "rom {}", header.rom_hash)?;
+        writeln!(f, "seed {}", header.seed)?;
+        writeln!(f, "ticks {}", header.ticks_per_frame)?;
+        writeln!(f, "quirks {:02x}", header.quirks.to_bits())?;
+
+        for event in &self.events {
+            match event {
+                MovieEvent::Key { frame, key, pressed } => {
+                    writeln!(f, "k {} {:x} {}", frame, key, *pressed as u8)?
+                }
+                MovieEvent::Hash { frame, hash } => writeln!(f, "h {} {:016x}", frame, hash)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_number(text: &str, radix: u32, line: usize) -> Result<u64, MovieError> {
+    u64::from_str_radix(text, radix).map_err(|_| MovieError::Parse {
+        line, message: format!("invalid number {}", text),
+    })
+}
+
+// Number which has to fall into given range
+fn parse_limited(text: &str, radix: u32, range: RangeInclusive<u64>, line: usize) -> Result<u64, MovieError> {
+    let number = parse_number(text, radix, line)?;
+    if !range.contains(&number) {
+        return Err(MovieError::Parse { line, message: format!("{} is out of range", text) });
+    }
+    Ok(number)
+}
+
+// Records key presses of a running emulator
+pub struct MovieRecorder {
+    movie: Movie,
+    hash_interval: u64,
+}
+
+impl MovieRecorder {
+    // Start recording, emulator should be freshly reset and loaded with the ROM
+    pub fn new(emu: &Emulator<impl Bus>, rom: &[u8], ticks_per_frame: usize) -> Self {
+        Self { movie: Movie::new(emu, rom, ticks_per_frame), hash_interval: DEFAULT_HASH_INTERVAL }
+    }
+
+    // Store state hash every given number of frames, zero disables hashes
+    pub fn set_hash_interval(&mut self, frames: u64) {
+        self.hash_interval = frames;
+    }
+
+    // Press or release key in the emulator and remember it
+    pub fn keypress(&mut self, emu: &mut Emulator<impl Bus>, key: usize, pressed: bool) {
+        emu.keypress(key, pressed);
+        self.movie.events.push(MovieEvent::Key { frame: emu.frame(), key: key as u8, pressed });
+    }
+
+    // Call after every frame, stores state hashes
+    pub fn end_frame(&mut self, emu: &Emulator<impl Bus>) {
+        // Nothing to hash before the first frame was run
+        let Some(frame) = emu.frame().checked_sub(1) else {
+            return;
+        };
+        if self.hash_interval != 0 && frame.is_multiple_of(self.hash_interval) {
+            self.movie.events.push(MovieEvent::Hash { frame, hash: emu.state_hash() });
+        }
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+// Plays movie back through emulator
+pub struct MoviePlayer {
+    movie: Movie,
+    position: usize,
+}
+
+impl MoviePlayer {
+    // Reset emulator into the state movie starts from, then load the ROM
+    pub fn new(movie: Movie, emu: &mut Emulator<impl Bus>, rom: &[u8]) -> Result<Self, MovieError> {
+        let actual = sha1(rom);
+        if actual != movie.header.rom_hash {
+            return Err(MovieError::RomMismatch { expected: movie.header.rom_hash, actual });
+        }
+
+        emu.set_seed(movie.header.seed);
+        emu.set_quirks(movie.header.quirks);
+        emu.reset();
+        emu.load_data(rom);
+
+        Ok(Self { movie, position: 0 })
+    }
+
+    pub fn ticks_per_frame(&self) -> usize {
+        self.movie.header.ticks_per_frame
+    }
+
+    // Check if every event was played
+    pub fn finished(&self) -> bool {
+        self.position >= self.movie.events.len()
+    }
+
+    // Run one frame with recorded input, fails when state differs from the recorded one
+    pub fn run_frame(&mut self, emu: &mut Emulator<impl Bus>) -> Result<(), MovieError> {
+        let frame = emu.frame();
+
+        // Apply keys recorded before this frame
+        while let Some(MovieEvent::Key { frame: event_frame, key, pressed }) = self.movie.events.get(self.position) {
+            if *event_frame > frame {
+                break;
+            }
+            emu.keypress(*key as usize, *pressed);
+            self.position += 1;
+        }
+
+        emu.run_frame(self.movie.header.ticks_per_frame);
+
+        // Compare with hashes recorded after this frame
+        while let Some(MovieEvent::Hash { frame: event_frame, hash }) = self.movie.events.get(self.position) {
+            if *event_frame > frame {
+                break;
+            }
+            self.position += 1;
+
+            let actual = emu.state_hash();
+            if actual != *hash {
+                return Err(MovieError::Desync { frame: *event_frame, expected: *hash, actual });
+            }
+        }
+
+        Ok(())
+    }
+}
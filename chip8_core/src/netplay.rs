@@ -0,0 +1,489 @@
+// Two players on two emulators linked over TCP. Both run the same game from the same seed,
+// every frame each side sends its keys and the game sees keys of both players together.
+//
+// Local keys are sent a few frames ahead (input delay), so on a fast link the other side
+// has them before they're needed. When they're late the frame runs with a guess - the
+// other player's last keys - and once the real ones come the emulator restores the save
+// state from before the first wrong frame and runs again (rollback). Confirmed frames
+// exchange state hashes, so a desync is found on the frame it happened.
+//
+// Messages, little endian:
+// hello  01 "C8NP" version rom_sha1:40 seed:u64 quirks:u8 ticks:u16 delay:u8 rollback:u8
+// input  02 frame:u64 keys:u16
+// hash   03 frame:u64 hash:u64
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use crate::bus::Bus;
+use crate::hash::sha1;
+use crate::quirks::Quirks;
+use crate::state::STATE_SIZE;
+use crate::{Emulator, KEYS_NUM};
+
+const MAGIC: [u8; 4] = *b"C8NP";
+const VERSION: u8 = 2;
+const HELLO: u8 = 1;
+const INPUT: u8 = 2;
+const HASH: u8 = 3;
+const HELLO_SIZE: usize = 1 + 4 + 1 + SHA1_SIZE + 8 + 1 + 2 + 1 + 1;
+// ROM SHA-1 in hex, as cheats, challenges and movies know the ROM
+const SHA1_SIZE: usize = 40;
+const INPUT_SIZE: usize = 1 + 8 + 2;
+const HASH_SIZE: usize = 1 + 8 + 8;
+// Longest wait for the other side to introduce itself
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const DEFAULT_DELAY: u8 = 2;
+pub const DEFAULT_ROLLBACK: u8 = 8;
+
+
+// Reasons why netplay stopped
+#[derive(Debug)]
+pub enum NetplayError {
+    Io(io::Error),
+    // Other player quit
+    Closed,
+    // Other side isn't this emulator or speaks another version
+    Protocol(String),
+    // Players loaded different ROMs
+    RomMismatch { local: String, remote: String },
+    // Emulators went different ways, state hashes after the frame differ
+    Desync { frame: u64, local: u64, remote: u64 },
+}
+
+impl fmt::Display for NetplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetplayError::Io(err) => write!(f, "{}", err),
+            NetplayError::Closed => write!(f, "the other player disconnected"),
+            NetplayError::Protocol(message) => write!(f, "{}", message),
+            NetplayError::RomMismatch { local, remote } => {
+                write!(f, "the other player runs ROM {}, this one is {}", remote, local)
+            }
+            NetplayError::Desync { frame, local, remote } => {
+                write!(f, "desync at frame {}: state {:016x}, the other player has {:016x}", frame, local, remote)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetplayError {}
+
+impl From<io::Error> for NetplayError {
+    fn from(err: io::Error) -> Self {
+        NetplayError::Io(err)
+    }
+}
+
+// Waits for the other player, whose settings are then taken by both sides
+pub struct Host {
+    listener: TcpListener,
+    delay: u8,
+    rollback: u8,
+}
+
+impl Host {
+    // Listen on localhost only, the protocol has no authentication. Players on other machines
+    // come through a tunnel or a forwarded port
+    pub fn bind(port: u16, delay: u8, rollback: u8) -> io::Result<Self> {
+        Ok(Self { listener: TcpListener::bind(("127.0.0.1", port))?, delay, rollback })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |address| address.port())
+    }
+
+    // Block until the other player connects
+    pub fn accept(self) -> io::Result<Connection> {
+        let (stream, _) = self.listener.accept()?;
+        Ok(Connection { stream, host: Some((self.delay, self.rollback)) })
+    }
+}
+
+// Link to the other player, before both agree on the game
+pub struct Connection {
+    stream: TcpStream,
+    // Delay and rollback when hosting
+    host: Option<(u8, u8)>,
+}
+
+impl Connection {
+    // Connect to a host, e.g. "192.168.0.2:5000"
+    pub fn join(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self { stream: TcpStream::connect(address)?, host: None })
+    }
+
+    // Exchange settings, then drop cheats, reset the emulator and load the ROM so both sides start
+    // the same. Host decides on seed, quirks and speed, the ticks given to the guest are ignored
+    pub fn start(mut self, emu: &mut Emulator<impl Bus>, rom: &[u8], ticks_per_frame: usize)
+                 -> Result<Netplay, NetplayError> {
+        let (delay, rollback) = self.host.unwrap_or((0, 0));
+        let local = Hello {
+            rom_sha1: sha1(rom).as_bytes().try_into().unwrap(),
+            seed: emu.seed(),
+            quirks: emu.quirks(),
+            ticks_per_frame: ticks_per_frame as u16,
+            delay,
+            rollback,
+        };
+        self.stream.set_nodelay(true)?;
+        self.stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        self.stream.write_all(&local.encode())?;
+
+        let mut buffer = [0; HELLO_SIZE];
+        self.stream.read_exact(&mut buffer).map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => NetplayError::Closed,
+            _ => NetplayError::Io(err),
+        })?;
+        let remote = Hello::decode(&buffer)?;
+        if remote.rom_sha1 != local.rom_sha1 {
+            return Err(NetplayError::RomMismatch {
+                local: String::from_utf8_lossy(&local.rom_sha1).into_owned(),
+                remote: String::from_utf8_lossy(&remote.rom_sha1).into_owned(),
+            });
+        }
+
+        let settings = if self.host.is_some() { local } else { remote };
+        emu.set_seed(settings.seed);
+        emu.set_quirks(settings.quirks);
+        // Cheats would change memory on one side only
+        emu.cheats_mut().clear();
+        emu.reset();
+        emu.load_data(rom);
+
+        self.stream.set_read_timeout(None)?;
+        self.stream.set_nonblocking(true)?;
+        Ok(Netplay {
+            stream: self.stream,
+            host: self.host.is_some(),
+            ticks_per_frame: settings.ticks_per_frame as usize,
+            delay: settings.delay as u64,
+            rollback: settings.rollback as usize,
+            keys: 0,
+            frame: 0,
+            sent: 0,
+            local_inputs: BTreeMap::new(),
+            remote_inputs: BTreeMap::new(),
+            last_remote: 0,
+            history: VecDeque::new(),
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            received: Vec::new(),
+            outgoing: Vec::new(),
+            rollbacks: 0,
+        })
+    }
+}
+
+// Settings sent by both sides, guest only checks the ROM of the host
+#[derive(Clone, Copy)]
+struct Hello {
+    rom_sha1: [u8; SHA1_SIZE],
+    seed: u64,
+    quirks: Quirks,
+    ticks_per_frame: u16,
+    delay: u8,
+    rollback: u8,
+}
+
+impl Hello {
+    fn encode(&self) -> [u8; HELLO_SIZE] {
+        let mut buffer = [0; HELLO_SIZE];
+        buffer[0] = HELLO;
+        buffer[1..5].copy_from_slice(&MAGIC);
+        buffer[5] = VERSION;
+        buffer[6..46].copy_from_slice(&self.rom_sha1);
+        buffer[46..54].copy_from_slice(&self.seed.to_le_bytes());
+        buffer[54] = self.quirks.to_bits();
+        buffer[55..57].copy_from_slice(&self.ticks_per_frame.to_le_bytes());
+        buffer[57] = self.delay;
+        buffer[58] = self.rollback;
+        buffer
+    }
+
+    fn decode(buffer: &[u8; HELLO_SIZE]) -> Result<Self, NetplayError> {
+        if buffer[0] != HELLO || buffer[1..5] != MAGIC {
+            return Err(NetplayError::Protocol("the other side isn't a CHIP-8 netplay peer".to_string()));
+        }
+        if buffer[5] != VERSION {
+            return Err(NetplayError::Protocol(format!("unsupported netplay version {}", buffer[5])));
+        }
+        Ok(Self {
+            rom_sha1: buffer[6..46].try_into().unwrap(),
+            seed: u64::from_le_bytes(buffer[46..54].try_into().unwrap()),
+            quirks: Quirks::from_bits(buffer[54]),
+            ticks_per_frame: u16::from_le_bytes(buffer[55..57].try_into().unwrap()),
+            delay: buffer[57],
+            rollback: buffer[58],
+        })
+    }
+}
+
+// Frame which ran before the other player's keys for it were known
+struct Unconfirmed {
+    frame: u64,
+    // Save state from before the frame
+    state: Vec<u8>,
+    local: u16,
+    // Guessed keys of the other player
+    remote: u16,
+    // State hash after the frame
+    hash: u64,
+}
+
+pub struct Netplay {
+    stream: TcpStream,
+    host: bool,
+    ticks_per_frame: usize,
+    delay: u64,
+    // Frames allowed to run ahead of the other player's keys
+    rollback: usize,
+    // Keys this player holds right now, bit per key
+    keys: u16,
+    // Next frame to run
+    frame: u64,
+    // First frame without local keys sent yet
+    sent: u64,
+    local_inputs: BTreeMap<u64, u16>,
+    remote_inputs: BTreeMap<u64, u16>,
+    // Guess for frames whose keys didn't come yet
+    last_remote: u16,
+    history: VecDeque<Unconfirmed>,
+    // Hashes of confirmed frames, until the other side's hash of the same frame comes
+    local_hashes: BTreeMap<u64, u64>,
+    remote_hashes: BTreeMap<u64, u64>,
+    // Bytes of messages which didn't fully arrive yet, or couldn't be sent yet
+    received: Vec<u8>,
+    outgoing: Vec<u8>,
+    rollbacks: u64,
+}
+
+impl Netplay {
+    pub fn is_host(&self) -> bool {
+        self.host
+    }
+
+    // Instructions per frame agreed on, frontends have to use it for the game to stay in sync
+    pub fn ticks_per_frame(&self) -> usize {
+        self.ticks_per_frame
+    }
+
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+
+    // Frames run so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Times the emulator had to go back because of a wrong guess
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    // Local key press, used instead of Emulator::keypress
+    pub fn keypress(&mut self, index: usize, pressed: bool) {
+        let bit = 1 << (index % KEYS_NUM);
+        if pressed {
+            self.keys |= bit;
+        }
+        else {
+            self.keys &= !bit;
+        }
+    }
+
+    // Run the next frame if it's possible without getting too far ahead of the other player.
+    // Returns false when it has to wait, frontend should try again on the next loop
+    pub fn run_frame(&mut self, emu: &mut Emulator<impl Bus>) -> Result<bool, NetplayError> {
+        self.receive()?;
+
+        // Keys pressed now count a few frames later
+        while self.sent <= self.frame + self.delay {
+            self.local_inputs.insert(self.sent, self.keys);
+            self.outgoing.extend_from_slice(&encode_input(self.sent, self.keys));
+            self.sent += 1;
+        }
+
+        self.correct(emu)?;
+        self.confirm()?;
+
+        let ran = self.advance(emu)?;
+        self.flush()?;
+        Ok(ran)
+    }
+
+    fn advance(&mut self, emu: &mut Emulator<impl Bus>) -> Result<bool, NetplayError> {
+        let remote = self.remote_inputs.remove(&self.frame);
+        if remote.is_none() && self.history.len() >= self.rollback {
+            return Ok(false);
+        }
+
+        let local = self.local_inputs.remove(&self.frame).unwrap_or(0);
+        match remote {
+            // Nothing to guess, unless earlier frames still wait for their keys
+            Some(remote) if self.history.is_empty() => {
+                self.last_remote = remote;
+                run_with_keys(emu, local | remote, self.ticks_per_frame);
+                self.confirmed(self.frame, emu.state_hash())?;
+            }
+            _ => {
+                // Keys which came early stay until the frame gets confirmed
+                if let Some(remote) = remote {
+                    self.remote_inputs.insert(self.frame, remote);
+                }
+                let guess = remote.unwrap_or(self.last_remote);
+                let mut state = vec![0; STATE_SIZE];
+                emu.save_state(&mut state).unwrap();
+                run_with_keys(emu, local | guess, self.ticks_per_frame);
+                self.history.push_back(Unconfirmed {
+                    frame: self.frame, state, local, remote: guess, hash: emu.state_hash()
+                });
+            }
+        }
+        self.frame += 1;
+        Ok(true)
+    }
+
+    // Run again from the first frame which was guessed wrong
+    fn correct(&mut self, emu: &mut Emulator<impl Bus>) -> Result<(), NetplayError> {
+        let wrong = self.history.iter().position(|unconfirmed| {
+            self.remote_inputs.get(&unconfirmed.frame).is_some_and(|remote| *remote != unconfirmed.remote)
+        });
+        let Some(wrong) = wrong else {
+            return Ok(());
+        };
+
+        self.rollbacks += 1;
+        emu.rewind_state(&self.history[wrong].state).map_err(|err| NetplayError::Protocol(err.to_string()))?;
+        // Frames without keys yet get the newest known ones as the guess
+        let mut guess = self.last_remote;
+        for unconfirmed in self.history.iter_mut().skip(wrong) {
+            guess = self.remote_inputs.get(&unconfirmed.frame).copied().unwrap_or(guess);
+            emu.save_state(&mut unconfirmed.state).unwrap();
+            unconfirmed.remote = guess;
+            run_with_keys(emu, unconfirmed.local | guess, self.ticks_per_frame);
+            unconfirmed.hash = emu.state_hash();
+        }
+        Ok(())
+    }
+
+    // Drop frames which got their real keys, their hashes can be compared now
+    fn confirm(&mut self) -> Result<(), NetplayError> {
+        while let Some(first) = self.history.front() {
+            let Some(remote) = self.remote_inputs.remove(&first.frame) else {
+                break;
+            };
+            let first = self.history.pop_front().unwrap();
+            self.last_remote = remote;
+            self.confirmed(first.frame, first.hash)?;
+        }
+        Ok(())
+    }
+
+    // Frame ran with keys of both players, tell its hash and compare with the other side
+    fn confirmed(&mut self, frame: u64, hash: u64) -> Result<(), NetplayError> {
+        self.outgoing.extend_from_slice(&encode_hash(frame, hash));
+        self.local_hashes.insert(frame, hash);
+        self.compare_hashes()
+    }
+
+    fn compare_hashes(&mut self) -> Result<(), NetplayError> {
+        while let Some((&frame, &remote)) = self.remote_hashes.first_key_value() {
+            let Some(local) = self.local_hashes.remove(&frame) else {
+                break;
+            };
+            self.remote_hashes.remove(&frame);
+            if local != remote {
+                return Err(NetplayError::Desync { frame, local, remote });
+            }
+        }
+        Ok(())
+    }
+
+    // Read every message which came since the last time
+    fn receive(&mut self) -> Result<(), NetplayError> {
+        let mut chunk = [0; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(NetplayError::Closed),
+                Ok(read) => self.received.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(NetplayError::Io(err)),
+            }
+        }
+
+        let mut position = 0;
+        while let Some(&kind) = self.received.get(position) {
+            let size = match kind {
+                INPUT => INPUT_SIZE,
+                HASH => HASH_SIZE,
+                _ => return Err(NetplayError::Protocol(format!("unknown message {:02x}", kind))),
+            };
+            let Some(message) = self.received.get(position..position + size) else {
+                break;
+            };
+            let frame = u64::from_le_bytes(message[1..9].try_into().unwrap());
+            if kind == INPUT {
+                let keys = u16::from_le_bytes(message[9..11].try_into().unwrap());
+                // The other side runs at most rollback frames past our keys, and sends its own delay ahead
+                if frame > self.sent + self.delay + self.rollback as u64 {
+                    return Err(NetplayError::Protocol(format!("keys for frame {} came too early", frame)));
+                }
+                // Frames before this one are confirmed already
+                if frame >= self.frame || self.history.iter().any(|unconfirmed| unconfirmed.frame == frame) {
+                    self.remote_inputs.insert(frame, keys);
+                }
+            }
+            else {
+                self.remote_hashes.insert(frame, u64::from_le_bytes(message[9..17].try_into().unwrap()));
+            }
+            position += size;
+        }
+        self.received.drain(..position);
+        self.compare_hashes()
+    }
+
+    // Send as much as the socket takes without blocking
+    fn flush(&mut self) -> Result<(), NetplayError> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(NetplayError::Closed),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(NetplayError::Io(err)),
+            }
+        }
+        Ok(())
+    }
+}
+
+// Set every key from the bits and run the frame
+fn run_with_keys(emu: &mut Emulator<impl Bus>, keys: u16, ticks_per_frame: usize) {
+    for index in 0..KEYS_NUM {
+        emu.keypress(index, keys & (1 << index) != 0);
+    }
+    emu.run_frame(ticks_per_frame);
+}
+
+fn encode_input(frame: u64, keys: u16) -> [u8; INPUT_SIZE] {
+    let mut buffer = [0; INPUT_SIZE];
+    buffer[0] = INPUT;
+    buffer[1..9].copy_from_slice(&frame.to_le_bytes());
+    buffer[9..11].copy_from_slice(&keys.to_le_bytes());
+    buffer
+}
+
+fn encode_hash(frame: u64, hash: u64) -> [u8; HASH_SIZE] {
+    let mut buffer = [0; HASH_SIZE];
+    buffer[0] = HASH;
+    buffer[1..9].copy_from_slice(&frame.to_le_bytes());
+    buffer[9..17].copy_from_slice(&hash.to_le_bytes());
+    buffer
+}
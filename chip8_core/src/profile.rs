@@ -0,0 +1,146 @@
+// Coverage and hotspot profiler: counts how many times every address was executed and
+// how many times every opcode was, so dead code and inner loops of a ROM are easy to find.
+//
+// Heat map is the ROM disassembled, executed instructions with their hit counts and a bar,
+// bytes which never ran are grouped into marked regions:
+// 0x21A  F007  LD V0, DT                       320   5.3% ####################
+// ;;;; 0x234-0x235 never executed, 2 bytes
+// 0x234  7BFE  ADD VB, 0xFE                      -
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::ops::Range;
+use crate::disasm::Instruction;
+use crate::RAM_SIZE;
+
+// Longest bar in the heat map
+const BAR_WIDTH: u64 = 20;
+// Addresses listed as hotspots at the top of the heat map
+const HOTSPOTS: usize = 10;
+
+
+// Attached to the emulator with Emulator::set_profiler
+pub struct Profiler {
+    // Executions of instruction at every address
+    address_hits: Vec<u64>,
+    // Executions of every opcode, grouped into patterns only when asked
+    opcode_hits: Vec<u64>,
+    total: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self { address_hits: vec![0; RAM_SIZE], opcode_hits: vec![0; 1 << 16], total: 0 }
+    }
+
+    // Forget everything counted so far
+    pub fn clear(&mut self) {
+        self.address_hits.fill(0);
+        self.opcode_hits.fill(0);
+        self.total = 0;
+    }
+
+    // Instructions executed while profiling
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    // Executions of instruction at the address
+    pub fn hits(&self, addr: u16) -> u64 {
+        self.address_hits[addr as usize % RAM_SIZE]
+    }
+
+    // Executions at every address, indexed by address
+    pub fn address_hits(&self) -> &[u64] {
+        &self.address_hits
+    }
+
+    // Most executed addresses, hottest first
+    pub fn hotspots(&self, count: usize) -> Vec<(u16, u64)> {
+        let mut hot: Vec<(u16, u64)> = self.address_hits.iter().enumerate()
+            .filter(|(_, hits)| **hits > 0)
+            .map(|(addr, hits)| (addr as u16, *hits))
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot.truncate(count);
+        hot
+    }
+
+    // Executions per opcode type like "DXYN", most executed first
+    pub fn opcode_types(&self) -> Vec<(&'static str, u64)> {
+        let mut types = BTreeMap::new();
+        for (opcode, hits) in self.opcode_hits.iter().enumerate().filter(|(_, hits)| **hits > 0) {
+            *types.entry(Instruction(opcode as u16).pattern()).or_insert(0) += hits;
+        }
+        let mut types: Vec<(&'static str, u64)> = types.into_iter().collect();
+        types.sort_by_key(|(_, hits)| std::cmp::Reverse(*hits));
+        types
+    }
+
+    // Write heat map of the memory range, usually where the ROM was loaded
+    pub fn write_heat_map(&self, ram: &[u8], range: Range<u16>, out: &mut impl Write) -> io::Result<()> {
+        let percent = |hits: u64| hits as f64 * 100.0 / self.total.max(1) as f64;
+
+        writeln!(out, "; {} instructions executed", self.total)?;
+        writeln!(out, ";")?;
+        writeln!(out, "; Hotspots")?;
+        for (addr, hits) in self.hotspots(HOTSPOTS) {
+            writeln!(out, ";   0x{:03X} {:>12} {:>5.1}%  {}", addr, hits, percent(hits), Instruction(fetch(ram, addr)))?;
+        }
+        writeln!(out, ";")?;
+        writeln!(out, "; Opcode types")?;
+        for (pattern, hits) in self.opcode_types() {
+            writeln!(out, ";   {} {:>12} {:>5.1}%", pattern, hits, percent(hits))?;
+        }
+        writeln!(out, ";")?;
+
+        let max = self.address_hits.iter().max().copied().unwrap_or(0).max(1);
+        let mut addr = range.start;
+        while addr < range.end {
+            let hits = self.hits(addr);
+            if hits == 0 {
+                // Region lasts until the next executed instruction
+                let end = (addr..range.end).find(|addr| self.hits(*addr) > 0).unwrap_or(range.end);
+                writeln!(out, ";;;; 0x{:03X}-0x{:03X} never executed, {} bytes", addr, end - 1, end - addr)?;
+                while addr < end {
+                    // Last odd byte of the region gets shown alone
+                    if end - addr == 1 {
+                        writeln!(out, "0x{:03X}  {:02X}    DB 0x{:02X}", addr, ram[addr as usize], ram[addr as usize])?;
+                        addr += 1;
+                        continue;
+                    }
+                    let opcode = fetch(ram, addr);
+                    writeln!(out, "{:<36}{:>12}", line(addr, opcode), "-")?;
+                    addr += 2;
+                }
+                continue;
+            }
+
+            let bar = (hits * BAR_WIDTH).div_ceil(max) as usize;
+            writeln!(out, "{:<36}{:>12} {:>5.1}% {}", line(addr, fetch(ram, addr)), hits, percent(hits), "#".repeat(bar))?;
+            addr += 2;
+        }
+        Ok(())
+    }
+
+    // Count one executed instruction
+    pub(crate) fn record(&mut self, pc: u16, opcode: u16) {
+        self.address_hits[pc as usize % RAM_SIZE] += 1;
+        self.opcode_hits[opcode as usize] += 1;
+        self.total += 1;
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn fetch(ram: &[u8], addr: u16) -> u16 {
+    let addr = addr as usize % RAM_SIZE;
+    (ram[addr] as u16) << 8 | ram[(addr + 1) % RAM_SIZE] as u16
+}
+
+fn line(addr: u16, opcode: u16) -> String {
+    format!("0x{:03X}  {:04X}  {}", addr, opcode, Instruction(opcode))
+}
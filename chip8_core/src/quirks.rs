@@ -0,0 +1,102 @@
+// Behaviours that differ between CHIP-8 interpreters, games are written for one of them.
+// Default matches the way this emulator always worked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Quirks {
+    // 8XY6 / 8XYE shift Vy into Vx instead of shifting Vx in place
+    pub shift_uses_vy: bool,
+    // FX55 / FX65 leave I pointing after the last register
+    pub load_store_increments_i: bool,
+    // 8XY1 / 8XY2 / 8XY3 clear VF
+    pub logic_resets_vf: bool,
+    // BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+    // DXYN cuts sprites at the edge of screen instead of wrapping them
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+    // Interpreter used by this emulator before quirks were configurable
+    pub const MODERN: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        logic_resets_vf: false,
+        jump_uses_vx: false,
+        clip_sprites: false,
+    };
+
+    // Original interpreter of COSMAC VIP
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        logic_resets_vf: true,
+        jump_uses_vx: false,
+        clip_sprites: true,
+    };
+
+    // SUPER-CHIP 1.1 on HP48 calculators
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        logic_resets_vf: false,
+        jump_uses_vx: true,
+        clip_sprites: true,
+    };
+
+    // Every named preset along with its name
+    pub const PRESETS: [(&'static str, Quirks); 3] = [
+        ("modern", Quirks::MODERN),
+        ("vip", Quirks::COSMAC_VIP),
+        ("schip", Quirks::SUPER_CHIP),
+    ];
+
+    // Names of single quirks, in the same order as bits
+    pub const NAMES: [&'static str; 5] = [
+        "shift_uses_vy", "load_store_increments_i", "logic_resets_vf", "jump_uses_vx", "clip_sprites",
+    ];
+
+    // Find preset by its name
+    pub fn preset(name: &str) -> Option<Quirks> {
+        Self::PRESETS.iter().find(|(preset, _)| *preset == name).map(|(_, quirks)| *quirks)
+    }
+
+    // Pack quirks into bits, used by save files and movies
+    pub fn to_bits(self) -> u8 {
+        self.flags().iter().enumerate()
+            .fold(0, |bits, (bit, enabled)| bits | ((*enabled as u8) << bit))
+    }
+
+    // Unpack quirks from bits, unknown bits are ignored
+    pub fn from_bits(bits: u8) -> Quirks {
+        let flag = |bit: u8| bits & (1 << bit) != 0;
+        Quirks {
+            shift_uses_vy: flag(0),
+            load_store_increments_i: flag(1),
+            logic_resets_vf: flag(2),
+            jump_uses_vx: flag(3),
+            clip_sprites: flag(4),
+        }
+    }
+
+    // Enable or disable quirk by its name, returns false for unknown name
+    pub fn set(&mut self, name: &str, enabled: bool) -> bool {
+        match Self::NAMES.iter().position(|known| *known == name) {
+            Some(bit) => {
+                let bits = (self.to_bits() & !(1 << bit)) | ((enabled as u8) << bit);
+                *self = Quirks::from_bits(bits);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Quirks as flags, in the same order as names
+    pub fn flags(self) -> [bool; 5] {
+        [
+            self.shift_uses_vy,
+            self.load_store_increments_i,
+            self.logic_resets_vf,
+            self.jump_uses_vx,
+            self.clip_sprites,
+        ]
+    }
+}
@@ -0,0 +1,32 @@
+// Small deterministic random generator (xorshift64*), so the same seed always
+// produces the same game. Its whole state can be saved and compared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    // Create generator, zero is not a valid xorshift state so it gets replaced
+    pub fn new(seed: u64) -> Self {
+        Self { state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed } }
+    }
+
+    // Next random byte
+    pub fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    // Current state, used to restore generator later
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+}
+
+// Seed from the operating system, for games which don't need to be reproducible
+#[cfg(feature = "std")]
+pub fn random_seed() -> u64 {
+    rand::random()
+}
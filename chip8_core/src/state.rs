@@ -0,0 +1,192 @@
+// Save states: the whole emulator packed into a fixed-size buffer, without allocations.
+// Layout is little endian and versioned, so states can be stored on disk or sent over network.
+use core::fmt;
+use crate::bus::Bus;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::{Emulator, KEYS_NUM, RAM_SIZE, REGS_NUM, SCREEN_HEIGHT, SCREEN_WIDTH, STACK_SIZE};
+
+const MAGIC: [u8; 4] = *b"C8ST";
+const VERSION: u8 = 1;
+
+// Size of every save state in bytes
+pub const STATE_SIZE: usize = MAGIC.len() + 1
+    + 2 + RAM_SIZE + REGS_NUM + 2 + 2 + STACK_SIZE * 2
+    + 2 + SCREEN_WIDTH * SCREEN_HEIGHT / 8 + KEYS_NUM / 8
+    + 1 + 8 + 8 + 8;
+
+
+// Reasons why a save state can't be loaded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    // Buffer has a different size than STATE_SIZE
+    WrongSize(usize),
+    // Buffer doesn't start with the magic bytes
+    NotAState,
+    // State was made by an incompatible version
+    UnsupportedVersion(u8),
+    // Program counter or stack pointer point outside of memory or stack
+    Corrupted,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::WrongSize(size) => write!(f, "state has {} bytes, expected {}", size, STATE_SIZE),
+            StateError::NotAState => write!(f, "data isn't a save state"),
+            StateError::UnsupportedVersion(version) => write!(f, "unsupported state version {}", version),
+            StateError::Corrupted => write!(f, "state is corrupted"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+// Sequential writer into the state buffer
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, data: &[u8]) {
+        self.buffer[self.position..self.position + data.len()].copy_from_slice(data);
+        self.position += data.len();
+    }
+
+    // Pack booleans into bits, first one in the highest bit
+    fn bits(&mut self, flags: &[bool]) {
+        for chunk in flags.chunks(8) {
+            self.bytes(&[chunk.iter().fold(0, |byte, flag| (byte << 1) | *flag as u8)]);
+        }
+    }
+}
+
+// Sequential reader from the state buffer
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut out = [0; N];
+        out.copy_from_slice(&self.buffer[self.position..self.position + N]);
+        self.position += N;
+        out
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.bytes())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes())
+    }
+
+    fn bits(&mut self, flags: &mut [bool]) {
+        for chunk in flags.chunks_mut(8) {
+            let [byte] = self.bytes();
+            for (bit, flag) in chunk.iter_mut().enumerate() {
+                *flag = byte & (0x80 >> bit) != 0;
+            }
+        }
+    }
+}
+
+impl<B: Bus> Emulator<B> {
+    // Write whole state into the buffer, which has to be STATE_SIZE long
+    pub fn save_state(&self, buffer: &mut [u8]) -> Result<(), StateError> {
+        if buffer.len() != STATE_SIZE {
+            return Err(StateError::WrongSize(buffer.len()));
+        }
+
+        let mut writer = Writer { buffer, position: 0 };
+        writer.bytes(&MAGIC);
+        writer.bytes(&[VERSION]);
+        writer.bytes(&self.pc.to_le_bytes());
+        writer.bytes(self.bus.ram());
+        writer.bytes(&self.v_reg);
+        writer.bytes(&self.i_reg.to_le_bytes());
+        writer.bytes(&self.sp.to_le_bytes());
+        for value in self.stack {
+            writer.bytes(&value.to_le_bytes());
+        }
+        writer.bytes(&[self.delay_timer, self.sound_timer]);
+        writer.bits(&self.screen);
+        writer.bits(&self.keys);
+        writer.bytes(&[self.quirks.to_bits()]);
+        writer.bytes(&self.seed.to_le_bytes());
+        writer.bytes(&self.rng.state().to_le_bytes());
+        writer.bytes(&self.frame.to_le_bytes());
+        Ok(())
+    }
+
+    // Restore state saved earlier, emulator stays untouched if it fails
+    pub fn load_state(&mut self, buffer: &[u8]) -> Result<(), StateError> {
+        self.restore_state(buffer, true)
+    }
+
+    // Like load_state, but bus keeps what it learned, for going back to a state saved
+    // earlier in the same run like netplay rollback does
+    pub fn rewind_state(&mut self, buffer: &[u8]) -> Result<(), StateError> {
+        self.restore_state(buffer, false)
+    }
+
+    fn restore_state(&mut self, buffer: &[u8], reset_bus: bool) -> Result<(), StateError> {
+        if buffer.len() != STATE_SIZE {
+            return Err(StateError::WrongSize(buffer.len()));
+        }
+
+        let mut reader = Reader { buffer, position: 0 };
+        if reader.bytes::<4>() != MAGIC {
+            return Err(StateError::NotAState);
+        }
+        let [version] = reader.bytes();
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let pc = reader.u16();
+        let ram = reader.bytes::<RAM_SIZE>();
+        let v_reg = reader.bytes::<REGS_NUM>();
+        let i_reg = reader.u16();
+        let sp = reader.u16();
+        let mut stack = [0; STACK_SIZE];
+        for value in stack.iter_mut() {
+            *value = reader.u16();
+        }
+        if pc as usize >= RAM_SIZE || sp as usize > STACK_SIZE {
+            return Err(StateError::Corrupted);
+        }
+
+        let [delay_timer, sound_timer] = reader.bytes();
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        reader.bits(&mut screen);
+        let mut keys = [false; KEYS_NUM];
+        reader.bits(&mut keys);
+        let [quirks] = reader.bytes();
+
+        self.pc = pc;
+        self.bus.ram_mut().copy_from_slice(&ram);
+        if reset_bus {
+            // Whatever bus knew about old memory doesn't apply anymore
+            self.bus.reset();
+            self.bus.loaded(0..RAM_SIZE as u16);
+        }
+        self.v_reg = v_reg;
+        self.i_reg = i_reg;
+        self.sp = sp;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.screen = screen;
+        self.keys = keys;
+        self.quirks = Quirks::from_bits(quirks);
+        self.seed = reader.u64();
+        self.rng = Rng::new(reader.u64());
+        self.frame = reader.u64();
+        Ok(())
+    }
+}
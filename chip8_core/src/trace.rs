@@ -0,0 +1,225 @@
+// Execution trace: PC, opcode, disassembly and registers after every executed instruction.
+// Entries are plain copies of the registers, text is made only when they are written out,
+// so tracing stays cheap enough to run at full speed.
+//
+// Full format, one fixed-width line per instruction, easy to diff against other emulators:
+// 0200 6005 LD V0, 0x05      V 05 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I 0000 SP 0 DT 00 ST 00
+// Changes format, only what the instruction changed:
+// 0200 6005 LD V0, 0x05      V0=05
+// Log format, key:value pairs like Gameboy Doctor logs, which generic line diff tools and
+// scripts written for other emulators' logs compare field by field:
+// PC:0200 OP:6005 V0:05 V1:00 V2:00 ... VF:00 I:0000 SP:0 DT:00 ST:00
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{self, BufWriter, Write};
+use crate::disasm::Instruction;
+use crate::REGS_NUM;
+
+// Entries kept by default in the ring buffer
+pub const DEFAULT_RING_SIZE: usize = 100_000;
+// Column where registers start, after the disassembly
+const REGS_COLUMN: usize = 27;
+
+// Bits of TraceEntry::changed, V registers take the lowest 16
+pub const CHANGED_I: u32 = 1 << 16;
+pub const CHANGED_SP: u32 = 1 << 17;
+pub const CHANGED_DT: u32 = 1 << 18;
+pub const CHANGED_ST: u32 = 1 << 19;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    // All registers on every line
+    Full,
+    // Only registers the instruction changed
+    Changes,
+    // All registers as key:value pairs, without disassembly
+    Log,
+}
+
+impl TraceFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(TraceFormat::Full),
+            "changes" => Some(TraceFormat::Changes),
+            "log" => Some(TraceFormat::Log),
+            _ => None,
+        }
+    }
+}
+
+// Registers right after one instruction was executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub v_reg: [u8; REGS_NUM],
+    pub i_reg: u16,
+    pub sp: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    // Registers the instruction changed, see CHANGED_* constants
+    pub changed: u32,
+}
+
+impl TraceEntry {
+    // Append the entry as one line, without newline
+    pub fn format(&self, line: &mut String, format: TraceFormat) {
+        match format {
+            TraceFormat::Full => {
+                self.disassembly(line);
+                line.push('V');
+                for value in self.v_reg {
+                    let _ = write!(line, " {:02X}", value);
+                }
+                let _ = write!(line, " I {:04X} SP {:X} DT {:02X} ST {:02X}",
+                               self.i_reg, self.sp, self.delay_timer, self.sound_timer);
+            }
+            TraceFormat::Changes => {
+                self.disassembly(line);
+                for (reg, value) in self.v_reg.iter().enumerate() {
+                    if self.changed & (1 << reg) != 0 {
+                        let _ = write!(line, "V{:X}={:02X} ", reg, value);
+                    }
+                }
+                if self.changed & CHANGED_I != 0 {
+                    let _ = write!(line, "I={:04X} ", self.i_reg);
+                }
+                if self.changed & CHANGED_SP != 0 {
+                    let _ = write!(line, "SP={:X} ", self.sp);
+                }
+                if self.changed & CHANGED_DT != 0 {
+                    let _ = write!(line, "DT={:02X} ", self.delay_timer);
+                }
+                if self.changed & CHANGED_ST != 0 {
+                    let _ = write!(line, "ST={:02X} ", self.sound_timer);
+                }
+            }
+            TraceFormat::Log => {
+                let _ = write!(line, "PC:{:04X} OP:{:04X}", self.pc, self.opcode);
+                for (reg, value) in self.v_reg.iter().enumerate() {
+                    let _ = write!(line, " V{:X}:{:02X}", reg, value);
+                }
+                let _ = write!(line, " I:{:04X} SP:{:X} DT:{:02X} ST:{:02X}",
+                               self.i_reg, self.sp, self.delay_timer, self.sound_timer);
+            }
+        }
+
+        let trimmed = line.trim_end().len();
+        line.truncate(trimmed);
+    }
+
+    // PC, opcode and disassembly, padded up to the registers column
+    fn disassembly(&self, line: &mut String) {
+        let start = line.len();
+        let _ = write!(line, "{:04X} {:04X} {}", self.pc, self.opcode, Instruction(self.opcode));
+        let width = line.len() - start;
+        line.extend(std::iter::repeat_n(' ', REGS_COLUMN.saturating_sub(width).max(1)));
+    }
+}
+
+// Where entries go
+enum Sink {
+    // Last entries, oldest are dropped
+    Ring { entries: VecDeque<TraceEntry>, capacity: usize },
+    // Every entry written as text
+    Writer(BufWriter<Box<dyn Write + Send>>),
+}
+
+// Tracer attached to the emulator with Emulator::set_tracer
+pub struct Tracer {
+    sink: Sink,
+    format: TraceFormat,
+    // Tracing starts when this address is executed for the first time
+    from: Option<u16>,
+    active: bool,
+    line: String,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    // Keep last entries in memory, e.g. to dump them after a crash. Zero capacity keeps nothing
+    pub fn ring(capacity: usize, format: TraceFormat) -> Self {
+        Self::with_sink(Sink::Ring { entries: VecDeque::with_capacity(capacity), capacity }, format)
+    }
+
+    // Write every entry into a file or another writer
+    pub fn writer(out: impl Write + Send + 'static, format: TraceFormat) -> Self {
+        Self::with_sink(Sink::Writer(BufWriter::with_capacity(1 << 16, Box::new(out))), format)
+    }
+
+    fn with_sink(sink: Sink, format: TraceFormat) -> Self {
+        Self { sink, format, from: None, active: true, line: String::new(), error: None }
+    }
+
+    // Stay silent until PC reaches the address
+    pub fn start_at(mut self, pc: u16) -> Self {
+        self.from = Some(pc);
+        self.active = false;
+        self
+    }
+
+    pub fn format(&self) -> TraceFormat {
+        self.format
+    }
+
+    // Entries in the ring buffer, oldest first. Writer tracers keep nothing
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        match &self.sink {
+            Sink::Ring { entries, .. } => Some(entries.iter()),
+            Sink::Writer(_) => None,
+        }.into_iter().flatten()
+    }
+
+    // Write ring buffer as text
+    pub fn dump(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut line = String::new();
+        for entry in self.entries() {
+            line.clear();
+            entry.format(&mut line, self.format);
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    // Flush writer, reports the first error which happened while tracing
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        match &mut self.sink {
+            Sink::Writer(out) => out.flush(),
+            Sink::Ring { .. } => Ok(()),
+        }
+    }
+
+    // Check if instruction at the address should be traced
+    pub(crate) fn wants(&mut self, pc: u16) -> bool {
+        if !self.active && self.from == Some(pc) {
+            self.active = true;
+        }
+        self.active
+    }
+
+    pub(crate) fn record(&mut self, entry: TraceEntry) {
+        match &mut self.sink {
+            Sink::Ring { capacity: 0, .. } => (),
+            Sink::Ring { entries, capacity } => {
+                if entries.len() == *capacity {
+                    entries.pop_front();
+                }
+                entries.push_back(entry);
+            }
+            // After an error, e.g. full disk, tracing quietly stops
+            Sink::Writer(out) if self.error.is_none() => {
+                self.line.clear();
+                entry.format(&mut self.line, self.format);
+                self.line.push('\n');
+                if let Err(err) = out.write_all(self.line.as_bytes()) {
+                    self.error = Some(err);
+                }
+            }
+            Sink::Writer(_) => (),
+        }
+    }
+}
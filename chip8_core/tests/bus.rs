@@ -0,0 +1,117 @@
+use chip8_core::bus::{ConsolePort, Mapped, Memory, Protected, Timer, VipFramebuffer, Violation, VIP_FRAMEBUFFER};
+use chip8_core::Emulator;
+
+// LD I, 0x010; LD V0, 0xAB; LD [I], V0; loop: JP loop
+const FONT_WRITE: [u8; 8] = [0xA0, 0x10, 0x60, 0xAB, 0xF0, 0x55, 0x12, 0x06];
+
+fn protected(policy: Violation) -> Emulator<Protected<Memory>> {
+    let mut emu = Emulator::with_bus(Protected::new(Memory::new(), policy), 0);
+    emu.load_data(&FONT_WRITE);
+    emu
+}
+
+#[test]
+fn console_port_gets_written_bytes() {
+    // LD V0, 'h'; LD V1, 'i'; LD I, 0xFF0; LD [I], V1 writes both to the port; loop: JP loop
+    let rom = [0x60, b'h', 0x61, b'i', 0xAF, 0xF0, 0xF1, 0x55, 0x12, 0x08];
+    let mut output = Vec::new();
+    {
+        let port = ConsolePort::new(|byte| output.push(byte));
+        let mut emu = Emulator::with_bus(Mapped::new(Memory::new(), 0xFF0..0xFF1, port), 0);
+        emu.load_data(&rom);
+        emu.run_frame(10);
+        // Writes at 0xFF1 are past the port and go to RAM
+        assert_eq!(emu.ram()[0xFF0], 0);
+        assert_eq!(emu.ram()[0xFF1], b'i');
+    }
+    assert_eq!(output, b"h");
+}
+
+#[test]
+fn timer_counts_frames() {
+    // loop: LD I, 0xE00; LD V1, [I]; JP loop
+    let rom = [0xAE, 0x00, 0xF1, 0x65, 0x12, 0x00];
+    let mut emu = Emulator::with_bus(Mapped::new(Memory::new(), 0xE00..0xE02, Timer::new()), 0);
+    emu.load_data(&rom);
+    for _ in 0..300 {
+        emu.run_frame(10);
+    }
+    assert_eq!(emu.bus().peripheral.frames(), 300);
+    // Last read happened during frame 300, before it ended
+    assert_eq!(&emu.v_regs()[..2], [0x01, 0x2B]);
+    // Memory under the timer stays untouched
+    assert_eq!(&emu.ram()[0xE00..0xE02], [0, 0]);
+
+    emu.reset();
+    assert_eq!(emu.bus().peripheral.frames(), 0);
+}
+
+#[test]
+fn allowed_writes_are_counted() {
+    let mut emu = protected(Violation::Allow);
+    emu.run_frame(10);
+    assert_eq!(emu.ram()[0x010], 0xAB);
+    assert_eq!(emu.bus().violations(), 1);
+    assert_eq!(emu.bus().last_violation(), Some(0x010));
+}
+
+#[test]
+fn reset_forgets_violations() {
+    let mut emu = protected(Violation::Allow);
+    emu.run_frame(10);
+    emu.reset();
+    assert_eq!(emu.bus().violations(), 0);
+    assert_eq!(emu.bus().last_violation(), None);
+}
+
+#[test]
+fn ignored_writes_are_dropped() {
+    let mut emu = protected(Violation::Ignore);
+    let font = emu.ram()[0x010];
+    emu.run_frame(10);
+    assert_eq!(emu.ram()[0x010], font);
+    assert_eq!(emu.bus().violations(), 1);
+}
+
+#[test]
+#[should_panic(expected = "read-only 010")]
+fn forbidden_writes_panic() {
+    protected(Violation::Panic).run_frame(10);
+}
+
+#[test]
+fn extra_regions_are_protected() {
+    let mut bus = Protected::new(Memory::new(), Violation::Ignore);
+    assert!(bus.protect(0x300..0x310));
+    // LD I, 0x305; LD [I], V0; loop: JP loop
+    let mut emu = Emulator::with_bus(bus, 0);
+    emu.load_data(&[0xA3, 0x05, 0xF0, 0x55, 0x12, 0x04]);
+    emu.v_regs_mut()[0] = 7;
+    emu.run_frame(10);
+    assert_eq!(emu.ram()[0x305], 0);
+    assert_eq!(emu.bus().last_violation(), Some(0x305));
+}
+
+#[test]
+fn display_shows_up_at_0xf00() {
+    // LD V0, 0; LD V1, 8; LD F, V0; DRW V1, V0, 5; LD I, 0xF00; LD V2, [I]; loop: JP loop
+    let rom = [0x60, 0x00, 0x61, 0x08, 0xF0, 0x29, 0xD1, 0x05, 0xAF, 0x00, 0xF2, 0x65, 0x12, 0x0C];
+    let mut emu = Emulator::with_bus(VipFramebuffer::new(Memory::new()), 0);
+    emu.load_data(&rom);
+    emu.run_frame(10);
+
+    // Font of 0 drawn 8 pixels from the left lands in the second byte of every row
+    let framebuffer = &emu.ram()[VIP_FRAMEBUFFER as usize..];
+    for (row, expected) in [0xF0, 0x90, 0x90, 0x90, 0xF0].iter().enumerate() {
+        assert_eq!(framebuffer[row * 8..row * 8 + 2], [0x00, *expected]);
+    }
+    assert!(framebuffer[5 * 8..].iter().all(|byte| *byte == 0));
+    // Program reads pixels back
+    assert_eq!(&emu.v_regs()[..3], [0x00, 0xF0, 0x00]);
+
+    // CLS; loop: JP loop
+    emu.load_data(&[0x00, 0xE0, 0x12, 0x02]);
+    emu.set_pc(0x200);
+    emu.run_frame(10);
+    assert!(emu.ram()[VIP_FRAMEBUFFER as usize..].iter().all(|byte| *byte == 0));
+}
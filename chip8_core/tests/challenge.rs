@@ -0,0 +1,113 @@
+use chip8_core::challenge::{Challenge, Condition, Expr, Leaderboard, LEADERBOARD_SIZE};
+use chip8_core::Emulator;
+
+const CHALLENGE: &str = "
+# Lines at 0x3F0, score in BCD at 0x2F0
+achievement First line: ram[0x3F0] >= 1
+achievement Marathon: ram[0x3F0] >= 10 && v3 == 0 for 3 frames
+score ram[0x2F0] * 100 + ram[0x2F1] * 10 + ram[0x2F2]
+";
+
+// Game spinning in place, tests change its memory between frames
+fn game() -> Emulator {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&[0x12, 0x00]);
+    emu
+}
+
+fn frame(emu: &mut Emulator, challenge: &mut Challenge) -> Vec<String> {
+    emu.run_frame(10);
+    challenge.update(emu).iter().map(|achievement| achievement.name.clone()).collect()
+}
+
+#[test]
+fn values_follow_precedence() {
+    let mut emu = game();
+    emu.ram_mut()[0x300] = 7;
+    let eval = |text: &str| Expr::parse(text).unwrap().eval(&emu);
+    assert_eq!(eval("2 + 3 * 4"), 14);
+    assert_eq!(eval("(2 + 3) * 4"), 20);
+    assert_eq!(eval("ram[0x2FF + 1] - 10"), -3);
+    assert_eq!(eval("pc"), 0x200);
+}
+
+#[test]
+fn broken_definitions_are_refused() {
+    assert!(Condition::parse("ram[0x3F0] >= ").is_err());
+    assert!(Condition::parse("ram[0x3F0] 10").is_err());
+    assert!(Condition::parse("vG == 1").is_err());
+    assert!(Condition::parse("v1 == 1 for zero frames").is_err());
+    assert!(Expr::parse("(1 + 2").is_err());
+    assert_eq!(Challenge::parse("score 1\nbonus 5\n").unwrap_err(), "line 2: unknown entry bonus");
+}
+
+#[test]
+fn deep_nesting_is_refused() {
+    let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(Expr::parse(&nested(30)).is_ok());
+    assert_eq!(Expr::parse(&nested(100_000)).unwrap_err(), "longer than 256 tokens");
+    assert_eq!(Expr::parse(&nested(40)).unwrap_err(), "nested deeper than 32");
+    assert!(Expr::parse(&format!("{}0{}", "ram[".repeat(40), "]".repeat(40))).is_err());
+    assert!(Expr::parse(&vec!["1"; 1000].join(" + ")).is_err());
+}
+
+#[test]
+fn achievements_unlock_once() {
+    let mut emu = game();
+    let mut challenge = Challenge::parse(CHALLENGE).unwrap();
+    assert!(frame(&mut emu, &mut challenge).is_empty());
+
+    emu.ram_mut()[0x3F0] = 1;
+    assert_eq!(frame(&mut emu, &mut challenge), ["First line"]);
+    assert!(frame(&mut emu, &mut challenge).is_empty());
+
+    // Has to hold three frames in a row
+    emu.ram_mut()[0x3F0] = 10;
+    frame(&mut emu, &mut challenge);
+    frame(&mut emu, &mut challenge);
+    emu.ram_mut()[0x3F0] = 9;
+    frame(&mut emu, &mut challenge);
+    emu.ram_mut()[0x3F0] = 10;
+    frame(&mut emu, &mut challenge);
+    frame(&mut emu, &mut challenge);
+    assert_eq!(frame(&mut emu, &mut challenge), ["Marathon"]);
+    assert!(challenge.achievements.iter().all(|achievement| achievement.unlocked));
+}
+
+#[test]
+fn best_score_stays_after_game_over() {
+    let mut emu = game();
+    let mut challenge = Challenge::parse(CHALLENGE).unwrap();
+    emu.ram_mut()[0x2F0..0x2F3].copy_from_slice(&[1, 2, 5]);
+    frame(&mut emu, &mut challenge);
+    emu.ram_mut()[0x2F0..0x2F3].fill(0);
+    frame(&mut emu, &mut challenge);
+    assert_eq!(challenge.best_score(), Some(125));
+}
+
+#[test]
+fn leaderboard_keeps_best_scores() {
+    let mut leaderboard = Leaderboard::parse("50 ann\n\n120 bob\n").unwrap();
+    assert_eq!(leaderboard.entries, [(120, "bob".to_string()), (50, "ann".to_string())]);
+    assert!(leaderboard.is_record(121));
+    assert!(!leaderboard.is_record(120));
+
+    // Equal score goes below the older one
+    assert_eq!(leaderboard.insert(50, "cid"), Some(2));
+    assert_eq!(Leaderboard::parse(&leaderboard.to_string()).unwrap(), leaderboard);
+
+    for score in 0..LEADERBOARD_SIZE as i64 {
+        leaderboard.insert(1000 + score, "dan");
+    }
+    assert_eq!(leaderboard.entries.len(), LEADERBOARD_SIZE);
+    assert_eq!(leaderboard.insert(1, "eve"), None);
+    assert_eq!(leaderboard.entries[0].0, 1009);
+}
+
+#[test]
+fn leaderboard_sorts_extreme_scores() {
+    let leaderboard = Leaderboard::parse(&format!("{} low\n{} high\n0 zero\n", i64::MIN, i64::MAX)).unwrap();
+    assert_eq!(leaderboard.entries, [
+        (i64::MAX, "high".to_string()), (0, "zero".to_string()), (i64::MIN, "low".to_string()),
+    ]);
+}
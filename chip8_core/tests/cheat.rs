@@ -0,0 +1,74 @@
+use chip8_core::cheat::{format_cheats, parse_cheats, Cheat, CheatSearch, Compare};
+use chip8_core::Emulator;
+
+// Lives at 0x300 start at 9, every loop with key 5 held takes one:
+// LD I, 0x300; LD V0, 9; LD [I], V0; loop: LD I, 0x300; LD V0, [I]; LD V1, 5; SKNP V1; ADD V0, 0xFF; LD [I], V0; JP loop
+const LIVES: [u8; 20] = [
+    0xA3, 0x00, 0x60, 0x09, 0xF0, 0x55,
+    0xA3, 0x00, 0xF0, 0x65, 0x61, 0x05, 0xE1, 0xA1, 0x70, 0xFF, 0xF0, 0x55, 0x12, 0x06,
+];
+
+fn game() -> Emulator {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&LIVES);
+    emu.run_frame(10);
+    emu
+}
+
+// Hold key 5 for one frame, losing a few lives
+fn lose_lives(emu: &mut Emulator) {
+    emu.keypress(5, true);
+    emu.run_frame(10);
+    emu.keypress(5, false);
+    emu.run_frame(10);
+}
+
+#[test]
+fn codes_survive_text_round_trip() {
+    let text = "# lives\n2F0=03 lives\n\n-0x3a1=ff skip intro\n";
+    let cheats = parse_cheats(text).unwrap();
+    assert_eq!(cheats, [
+        Cheat { address: 0x2F0, value: 0x03, enabled: true, name: "lives".to_string() },
+        Cheat { address: 0x3A1, value: 0xFF, enabled: false, name: "skip intro".to_string() },
+    ]);
+    assert_eq!(format_cheats(&cheats), "2F0=03 lives\n-3A1=FF skip intro\n");
+    assert_eq!(parse_cheats(&format_cheats(&cheats)).unwrap(), cheats);
+}
+
+#[test]
+fn broken_codes_are_refused() {
+    assert!(Cheat::parse("2F0").is_err());
+    assert!(Cheat::parse("1000=01").is_err());
+    assert!(Cheat::parse("2F0=100").is_err());
+    assert!(Cheat::parse("xyz=01").is_err());
+    assert_eq!(parse_cheats("2F0=01\nnope\n").unwrap_err(), "line 2: expected address=value, got nope");
+}
+
+#[test]
+fn search_finds_the_lives_counter() {
+    let mut emu = game();
+    let mut search = CheatSearch::new(emu.ram());
+    assert!(search.filter(emu.ram(), Compare::Equal(9)) > 1);
+
+    emu.run_frame(10);
+    search.filter(emu.ram(), Compare::Unchanged);
+    lose_lives(&mut emu);
+    assert_eq!(search.filter(emu.ram(), Compare::Decreased), 1);
+    assert_eq!(search.candidates(), &[0x300]);
+
+    // Nothing increased since the last filter
+    assert_eq!(search.filter(emu.ram(), Compare::Increased), 0);
+}
+
+#[test]
+fn frozen_value_stays() {
+    let mut emu = game();
+    emu.cheats_mut().push(Cheat::new(0x300, 9));
+    lose_lives(&mut emu);
+    assert_eq!(emu.ram()[0x300], 9);
+
+    // Disabled cheats do nothing
+    emu.cheats_mut()[0].enabled = false;
+    lose_lives(&mut emu);
+    assert!(emu.ram()[0x300] < 9);
+}
@@ -0,0 +1,482 @@
+// Differential testing: Emulator runs in lock-step with a small reference model written
+// straight from the spec, on random programs under every combination of quirks. Full state
+// is compared after every instruction, a mismatch is shrunk to the shortest program which
+// still shows it.
+//
+// The model refuses to guess: when a program does something undefined (stack overflow,
+// memory past 0xFFF, key above F...) the run simply ends there.
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use chip8_core::Emulator;
+use chip8_core::disasm::Instruction;
+use chip8_core::quirks::Quirks;
+
+const RAM_SIZE: usize = 4096;
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+const START: u16 = 0x200;
+
+const PROGRAMS_PER_QUIRKS: u64 = 200;
+const PROGRAM_LENGTH: usize = 48;
+const STEPS: usize = 400;
+// Instructions between two 60Hz timer ticks
+const STEPS_PER_FRAME: usize = 10;
+
+// Tests swap the global panic hook, so they take turns
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70,
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0,
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0, 0x10, 0xF0,
+    0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0,
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+
+// Generator for the test itself, separate from the one in the emulator
+struct TestRng(u64);
+
+impl TestRng {
+    fn next(&mut self) -> u64 {
+        // splitmix64
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, limit: u64) -> u64 {
+        self.next() % limit
+    }
+}
+
+// Program wandered into behaviour the spec doesn't define
+#[derive(Debug)]
+struct Undefined;
+
+// Reference interpreter, written for clarity rather than speed
+#[derive(Clone)]
+struct Model {
+    pc: u16,
+    ram: [u8; RAM_SIZE],
+    v: [u8; 16],
+    i: u16,
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    screen: [bool; WIDTH * HEIGHT],
+    keys: [bool; 16],
+    quirks: Quirks,
+    rng: u64,
+    // Deliberate bug, to check that the harness notices and shrinks it
+    broken_carry: bool,
+}
+
+impl Model {
+    fn new(program: &[u8], quirks: Quirks, seed: u64) -> Self {
+        let mut ram = [0; RAM_SIZE];
+        ram[..FONT.len()].copy_from_slice(&FONT);
+        ram[START as usize..START as usize + program.len()].copy_from_slice(program);
+        Self {
+            pc: START,
+            ram,
+            v: [0; 16],
+            i: 0,
+            stack: Vec::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            screen: [false; WIDTH * HEIGHT],
+            keys: [false; 16],
+            quirks,
+            // xorshift64* seeded like the emulator, zero isn't a valid state
+            rng: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed },
+            broken_carry: false,
+        }
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        (self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn read(&self, addr: u16) -> Result<u8, Undefined> {
+        self.ram.get(addr as usize).copied().ok_or(Undefined)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) -> Result<(), Undefined> {
+        *self.ram.get_mut(addr as usize).ok_or(Undefined)? = value;
+        Ok(())
+    }
+
+    fn key(&self, value: u8) -> Result<bool, Undefined> {
+        self.keys.get(value as usize).copied().ok_or(Undefined)
+    }
+
+    fn step(&mut self) -> Result<(), Undefined> {
+        let opcode = u16::from_be_bytes([self.read(self.pc)?, self.read(self.pc + 1)?]);
+        self.pc += 2;
+
+        let x = ((opcode >> 8) & 0xF) as usize;
+        let y = ((opcode >> 4) & 0xF) as usize;
+        let n = opcode & 0xF;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+
+        match opcode >> 12 {
+            0x0 => match opcode {
+                0x0000 => (),
+                0x00E0 => self.screen = [false; WIDTH * HEIGHT],
+                0x00EE => self.pc = self.stack.pop().ok_or(Undefined)?,
+                _ => return Err(Undefined),
+            },
+            0x1 => self.pc = nnn,
+            0x2 => {
+                if self.stack.len() == 16 {
+                    return Err(Undefined);
+                }
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            }
+            0x3 => self.skip_if(self.v[x] == nn),
+            0x4 => self.skip_if(self.v[x] != nn),
+            0x5 if n == 0 => self.skip_if(self.v[x] == self.v[y]),
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => {
+                let (vx, vy) = (self.v[x], self.v[y]);
+                let logic_flag = if self.quirks.logic_resets_vf { Some(0) } else { None };
+                let shifted = if self.quirks.shift_uses_vy { vy } else { vx };
+                // Result goes into Vx first, flag into VF after it
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, logic_flag),
+                    0x2 => (vx & vy, logic_flag),
+                    0x3 => (vx ^ vy, logic_flag),
+                    0x4 => {
+                        let sum = vx as u16 + vy as u16;
+                        let carry = sum > 0xFF && !self.broken_carry;
+                        (sum as u8, Some(carry as u8))
+                    }
+                    // VF is 1 when there was no borrow
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x6 => (shifted >> 1, Some(shifted & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0xE => (shifted << 1, Some(shifted >> 7)),
+                    _ => return Err(Undefined),
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 if n == 0 => self.skip_if(self.v[x] != self.v[y]),
+            0xA => self.i = nnn,
+            0xB => {
+                let offset = if self.quirks.jump_uses_vx { self.v[x] } else { self.v[0] };
+                self.pc = nnn + offset as u16;
+            }
+            0xC => self.v[x] = self.random_byte() & nn,
+            0xD => {
+                let start_x = self.v[x] as usize % WIDTH;
+                let start_y = self.v[y] as usize % HEIGHT;
+                let mut collision = false;
+                for row in 0..n as usize {
+                    let sprite = self.read(self.i + row as u16)?;
+                    for column in 0..8 {
+                        if sprite & (0x80 >> column) == 0 {
+                            continue;
+                        }
+                        let (px, py) = (start_x + column, start_y + row);
+                        if self.quirks.clip_sprites && (px >= WIDTH || py >= HEIGHT) {
+                            continue;
+                        }
+                        let index = px % WIDTH + py % HEIGHT * WIDTH;
+                        collision |= self.screen[index];
+                        self.screen[index] = !self.screen[index];
+                    }
+                }
+                self.v[0xF] = collision as u8;
+            }
+            0xE if nn == 0x9E => self.skip_if(self.key(self.v[x])?),
+            0xE if nn == 0xA1 => self.skip_if(!self.key(self.v[x])?),
+            0xF => match nn {
+                0x07 => self.v[x] = self.delay_timer,
+                // Lowest held key, otherwise the instruction repeats
+                0x0A => match self.keys.iter().position(|held| *held) {
+                    Some(key) => self.v[x] = key as u8,
+                    None => self.pc -= 2,
+                },
+                0x15 => self.delay_timer = self.v[x],
+                0x18 => self.sound_timer = self.v[x],
+                0x1E => self.i = self.i.wrapping_add(self.v[x] as u16),
+                0x29 => {
+                    if self.v[x] > 0xF {
+                        return Err(Undefined);
+                    }
+                    self.i = self.v[x] as u16 * 5;
+                }
+                0x33 => {
+                    let value = self.v[x];
+                    self.write(self.i, value / 100)?;
+                    self.write(self.i + 1, value / 10 % 10)?;
+                    self.write(self.i + 2, value % 10)?;
+                }
+                0x55 => {
+                    for reg in 0..=x {
+                        self.write(self.i + reg as u16, self.v[reg])?;
+                    }
+                    if self.quirks.load_store_increments_i {
+                        self.i += x as u16 + 1;
+                    }
+                }
+                0x65 => {
+                    for reg in 0..=x {
+                        self.v[reg] = self.read(self.i + reg as u16)?;
+                    }
+                    if self.quirks.load_store_increments_i {
+                        self.i += x as u16 + 1;
+                    }
+                }
+                _ => return Err(Undefined),
+            },
+            _ => return Err(Undefined),
+        }
+        // Running off the end of memory
+        if self.pc as usize >= RAM_SIZE {
+            return Err(Undefined);
+        }
+        Ok(())
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc += 2;
+        }
+    }
+
+    fn timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+}
+
+// Describe the first difference between emulator and model
+fn compare(emu: &Emulator, model: &Model) -> Option<String> {
+    let stack = &emu.stack()[..(emu.sp() as usize).min(16)];
+    let same = emu.pc() == model.pc && emu.v_regs() == model.v && emu.i_reg() == model.i
+        && stack == model.stack && emu.delay_timer() == model.delay_timer && emu.sound_timer() == model.sound_timer
+        && emu.ram() == model.ram && emu.get_display() == model.screen;
+    if same {
+        return None;
+    }
+
+    let checks = [
+        ("PC", format!("{:03X}", emu.pc()), format!("{:03X}", model.pc)),
+        ("V", format!("{:02X?}", emu.v_regs()), format!("{:02X?}", model.v)),
+        ("I", format!("{:03X}", emu.i_reg()), format!("{:03X}", model.i)),
+        ("stack", format!("{:03X?}", stack), format!("{:03X?}", model.stack)),
+        ("DT", emu.delay_timer().to_string(), model.delay_timer.to_string()),
+        ("ST", emu.sound_timer().to_string(), model.sound_timer.to_string()),
+    ];
+    for (name, emulator, reference) in checks {
+        if emulator != reference {
+            return Some(format!("{} is {}, reference has {}", name, emulator, reference));
+        }
+    }
+    if let Some(addr) = (0..RAM_SIZE).find(|addr| emu.ram()[*addr] != model.ram[*addr]) {
+        return Some(format!("RAM {:03X} is {:02X}, reference has {:02X}", addr, emu.ram()[addr], model.ram[addr]));
+    }
+    if let Some(pixel) = (0..WIDTH * HEIGHT).find(|pixel| emu.get_display()[*pixel] != model.screen[*pixel]) {
+        return Some(format!("pixel {},{} differs", pixel % WIDTH, pixel / WIDTH));
+    }
+    None
+}
+
+// Everything needed to repeat one run
+#[derive(Clone)]
+struct Case {
+    program: Vec<u16>,
+    quirks: Quirks,
+    seed: u64,
+    broken_carry: bool,
+}
+
+enum Outcome {
+    // Both agreed until the end or until the program did something undefined
+    Agreed,
+    Mismatch { step: usize, message: String },
+}
+
+fn run(case: &Case) -> Outcome {
+    let program: Vec<u8> = case.program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+    let mut model = Model::new(&program, case.quirks, case.seed);
+    model.broken_carry = case.broken_carry;
+    let mut emu = Emulator::with_seed(case.seed);
+    emu.set_quirks(case.quirks);
+    emu.load_data(&program);
+
+    // Keys change now and then, the same way on both sides
+    let mut keys = TestRng(case.seed);
+    for step in 0..STEPS {
+        if keys.below(8) == 0 {
+            let (key, pressed) = (keys.below(16) as usize, keys.below(2) == 0);
+            model.keys[key] = pressed;
+            emu.keypress(key, pressed);
+        }
+
+        let mut next = model.clone();
+        if next.step().is_err() {
+            return Outcome::Agreed;
+        }
+        model = next;
+
+        if panic::catch_unwind(AssertUnwindSafe(|| emu.tick())).is_err() {
+            return Outcome::Mismatch { step, message: "emulator panicked".to_string() };
+        }
+        if (step + 1) % STEPS_PER_FRAME == 0 {
+            model.timers();
+            emu.time_tick();
+        }
+        if let Some(message) = compare(&emu, &model) {
+            return Outcome::Mismatch { step, message };
+        }
+    }
+    Outcome::Agreed
+}
+
+// Random instruction, jumps mostly land inside the program so it keeps running
+fn random_instruction(rng: &mut TestRng) -> u16 {
+    let x = rng.below(16) as u16;
+    let y = rng.below(16) as u16;
+    let n = rng.below(16) as u16;
+    let nn = rng.below(256) as u16;
+    let target = if rng.below(5) == 0 {
+        rng.below(0x1000) as u16
+    } else {
+        START + 2 * rng.below(PROGRAM_LENGTH as u64) as u16
+    };
+    // Data for I stays mostly in RAM after the program, or in fonts
+    let address = match rng.below(4) {
+        0 => rng.below(0x50) as u16,
+        _ => 0x300 + rng.below(0xC00) as u16,
+    };
+    const ARITHMETIC: [u16; 9] = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE];
+    const MISC: [u16; 11] = [0x07, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65, 0x9E, 0xA1];
+
+    match rng.below(20) {
+        0 => [0x00E0, 0x00EE][rng.below(2) as usize],
+        1 => 0x1000 | target,
+        2 => 0x2000 | target,
+        3 => 0x3000 | x << 8 | nn,
+        4 => 0x4000 | x << 8 | nn,
+        5 => 0x5000 | x << 8 | y << 4,
+        6 | 7 => 0x6000 | x << 8 | nn,
+        8 => 0x7000 | x << 8 | nn,
+        9..=11 => 0x8000 | x << 8 | y << 4 | ARITHMETIC[rng.below(9) as usize],
+        12 => 0x9000 | x << 8 | y << 4,
+        13 => 0xA000 | address,
+        14 => 0xB000 | target,
+        15 => 0xC000 | x << 8 | nn,
+        16 => 0xD000 | x << 8 | y << 4 | n,
+        _ => {
+            let low = MISC[rng.below(11) as usize];
+            (if low >= 0x9E { 0xE000 } else { 0xF000 }) | x << 8 | low
+        }
+    }
+}
+
+// Simplify the program one instruction at a time while the mismatch stays. Instructions are
+// first replaced with NOPs, which keeps jump targets in place, then removed
+fn shrink(mut case: Case) -> Case {
+    let fails = |candidate: &Case| matches!(run(candidate), Outcome::Mismatch { .. });
+    loop {
+        let mut changed = false;
+        for index in 0..case.program.len() {
+            if case.program[index] != 0 {
+                let mut candidate = case.clone();
+                candidate.program[index] = 0;
+                if fails(&candidate) {
+                    case = candidate;
+                    changed = true;
+                }
+            }
+        }
+        let mut index = 0;
+        while index < case.program.len() {
+            let mut candidate = case.clone();
+            candidate.program.remove(index);
+            if fails(&candidate) {
+                case = candidate;
+                changed = true;
+            } else {
+                index += 1;
+            }
+        }
+        if !changed {
+            return case;
+        }
+    }
+}
+
+fn describe(case: &Case) -> String {
+    let Outcome::Mismatch { step, message } = run(case) else {
+        return "no mismatch".to_string();
+    };
+    let mut text = format!("{} after step {} with {:?}, seed {}\n", message, step, case.quirks, case.seed);
+    for (index, opcode) in case.program.iter().enumerate() {
+        text += &format!("    {:03X}: {:04X}  {}\n", START as usize + index * 2, opcode, Instruction(*opcode));
+    }
+    text
+}
+
+// Find the first failing program and shrink it
+fn find_mismatch(broken_carry: bool) -> Option<Case> {
+    // Panics of the emulator are expected while searching, keep the output readable
+    panic::set_hook(Box::new(|_| ()));
+    let mut found = None;
+
+    'search: for bits in 0..32 {
+        for number in 0..PROGRAMS_PER_QUIRKS {
+            let seed = (bits << 32) | (number + 1);
+            let mut rng = TestRng(seed);
+            let case = Case {
+                program: (0..PROGRAM_LENGTH).map(|_| random_instruction(&mut rng)).collect(),
+                quirks: Quirks::from_bits(bits as u8),
+                seed,
+                broken_carry,
+            };
+            if matches!(run(&case), Outcome::Mismatch { .. }) {
+                found = Some(shrink(case));
+                break 'search;
+            }
+        }
+    }
+
+    let _ = panic::take_hook();
+    found
+}
+
+#[test]
+fn emulator_matches_reference() {
+    let _serial = SERIAL.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(case) = find_mismatch(false) {
+        panic!("Emulator differs from the reference:\n{}", describe(&case));
+    }
+}
+
+#[test]
+fn harness_shrinks_mismatches() {
+    let _serial = SERIAL.lock().unwrap_or_else(|err| err.into_inner());
+    let case = find_mismatch(true).expect("broken carry wasn't noticed");
+    // A few instructions are enough, e.g. set a register to FF and add it to itself.
+    // NOPs which hold jump targets in place don't count
+    let instructions = case.program.iter().filter(|opcode| **opcode != 0).count();
+    assert!(instructions <= 4, "shrunk only to:\n{}", describe(&case));
+}
@@ -0,0 +1,145 @@
+use chip8_core::filter::*;
+
+const ON: [u8; 4] = [255, 255, 255, 255];
+const OFF: [u8; 4] = [0, 0, 0, 255];
+
+// Image from rows of "#" (lit) and "." (unlit)
+fn image(rows: &[&str]) -> (Vec<u8>, usize, usize) {
+    let pixels = rows.iter().flat_map(|row| row.chars()).flat_map(|c| if c == '#' { ON } else { OFF }).collect();
+    (pixels, rows[0].len(), rows.len())
+}
+
+// Rows back from RGBA, blended pixels are "+"
+fn rows(rgba: &[u8], width: usize) -> Vec<String> {
+    rgba.chunks_exact(width * 4)
+        .map(|row| row.chunks_exact(4).map(|pixel| match [pixel[0], pixel[1], pixel[2], pixel[3]] {
+            ON => '#',
+            OFF => '.',
+            _ => '+',
+        }).collect())
+        .collect()
+}
+
+fn scale(filter: Filter, src: &[&str]) -> Vec<String> {
+    let (src, width, height) = image(src);
+    let scaler = Scaler { filter, scanlines: false };
+    let mut dst = vec![0; scaler.output_size(width, height)];
+    scaler.apply(&src, width, height, &mut dst);
+    rows(&dst, width * scaler.factor())
+}
+
+const DIAGONAL: [&str; 4] = ["#...", ".#..", "..#.", "...#"];
+const DOT: [&str; 3] = [".....", "..#..", "....."];
+
+#[test]
+fn lone_pixel_stays_square() {
+    for filter in [Filter::Nearest, Filter::Scale2x, Filter::Epx, Filter::XbrLite] {
+        let factor = filter.factor();
+        let expected: Vec<String> = DOT.iter()
+            .flat_map(|row| {
+                let row: String = row.chars().flat_map(|c| std::iter::repeat_n(c, factor)).collect();
+                std::iter::repeat_n(row, factor)
+            })
+            .collect();
+        assert_eq!(scale(filter, &DOT), expected, "{:?}", filter);
+    }
+    assert_eq!(scale(Filter::Scale3x, &DOT)[3..6], ["......###......"; 3]);
+}
+
+#[test]
+fn scale2x_and_epx_round_diagonals() {
+    let expected = [
+        "##......",
+        "#.#.....",
+        ".###....",
+        "..###...",
+        "...###..",
+        "....###.",
+        ".....#.#",
+        "......##",
+    ];
+    assert_eq!(scale(Filter::Scale2x, &DIAGONAL), expected);
+    assert_eq!(scale(Filter::Epx, &DIAGONAL), expected);
+}
+
+#[test]
+fn scale3x_rounds_diagonals() {
+    assert_eq!(scale(Filter::Scale3x, &DIAGONAL), [
+        "###.........",
+        "##.#........",
+        "#..#........",
+        ".#####......",
+        "...###......",
+        "...####.....",
+        ".....####...",
+        "......###...",
+        "......#####.",
+        "........#..#",
+        "........#.##",
+        ".........###",
+    ]);
+}
+
+#[test]
+fn xbr_blends_corners_along_edges() {
+    assert_eq!(scale(Filter::XbrLite, &DIAGONAL), [
+        "##......",
+        "#++.....",
+        ".+##....",
+        "..##+...",
+        "...+##..",
+        "....##+.",
+        ".....++#",
+        "......##",
+    ]);
+
+    // Blended corner is halfway between the colors
+    let (src, width, height) = image(&DIAGONAL);
+    let mut dst = vec![0; width * height * 16];
+    xbr_lite(&src, width, height, &mut dst);
+    let corner = (1 + 2 * width * 2) * 4;
+    assert_eq!(dst[corner..corner + 4], [127, 127, 127, 255]);
+}
+
+#[test]
+fn scanlines_darken_every_second_row() {
+    let (src, width, height) = image(&["#."]);
+    let scaler = Scaler { filter: Filter::Nearest, scanlines: true };
+    assert_eq!(scaler.factor(), 2);
+    let mut dst = vec![0; scaler.output_size(width, height)];
+    scaler.apply(&src, width, height, &mut dst);
+    assert_eq!(rows(&dst, 4), ["##..", "++.."]);
+    assert_eq!(dst[16..20], [127, 127, 127, 255]);
+}
+
+#[test]
+fn output_fits_biggest_buffer() {
+    for filter in Filter::ALL {
+        for scanlines in [false, true] {
+            let scaler = Scaler { filter, scanlines };
+            let size = scaler.output_size(chip8_core::SCREEN_WIDTH, chip8_core::SCREEN_HEIGHT);
+            assert!(size <= MAX_OUTPUT_SIZE, "{:?}", scaler);
+            assert_eq!(size, chip8_core::RGBA_SIZE * scaler.factor() * scaler.factor());
+        }
+    }
+    let scale3x = Scaler { filter: Filter::Scale3x, scanlines: false };
+    assert_eq!(scale3x.output_size(chip8_core::SCREEN_WIDTH, chip8_core::SCREEN_HEIGHT), MAX_OUTPUT_SIZE);
+}
+
+#[test]
+fn filters_are_found_by_name() {
+    for filter in Filter::ALL {
+        assert_eq!(Filter::from_name(filter.name()), Some(filter));
+    }
+    assert_eq!(Filter::from_name("blur"), None);
+    assert_eq!(Filter::XbrLite.next(), Filter::Nearest);
+}
+
+#[test]
+fn image_is_centered_in_window() {
+    assert_eq!(fit(64, 32, 200, 100, ScaleMode::Integer), Viewport { x: 4, y: 2, width: 192, height: 96 });
+    assert_eq!(fit(64, 32, 200, 200, ScaleMode::Aspect), Viewport { x: 0, y: 50, width: 200, height: 100 });
+    assert_eq!(fit(64, 32, 200, 200, ScaleMode::Stretch), Viewport { x: 0, y: 0, width: 200, height: 200 });
+    // Window smaller than the image still shows it at least once
+    assert_eq!(fit(64, 32, 50, 20, ScaleMode::Integer), Viewport { x: 0, y: 0, width: 64, height: 32 });
+}
@@ -0,0 +1,143 @@
+// Scripted GDB client talking to the stub over TCP, the way a debugger would.
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+use chip8_core::gdb::{GdbEvent, GdbServer};
+use chip8_core::{Emulator, TICKS_PER_FRAME};
+
+// LD V0, 0x01; ADD V0, 0x01; JP 0x202
+const ROM: [u8; 6] = [0x60, 0x01, 0x70, 0x01, 0x12, 0x02];
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    // Send packet and return the reply, acknowledging it
+    fn send(&mut self, packet: &str) -> String {
+        let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", packet, checksum).unwrap();
+        assert_eq!(self.byte(), b'+', "packet {} wasn't acknowledged", packet);
+
+        while self.byte() != b'$' {}
+        let mut reply = Vec::new();
+        loop {
+            match self.byte() {
+                b'#' => break,
+                // Run-length encoding, previous byte repeats count - 29 more times
+                b'*' => {
+                    let count = self.byte() - 29;
+                    let last = *reply.last().unwrap();
+                    reply.extend(std::iter::repeat_n(last, count as usize));
+                }
+                byte => reply.push(byte),
+            }
+        }
+        let _checksum = [self.byte(), self.byte()];
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    // PC out of the registers packet, V0-VF, I, PC, SP, DT, ST with 16-bit ones little endian
+    fn pc(&mut self) -> u16 {
+        let regs = self.send("g");
+        u16::from_str_radix(&format!("{}{}", &regs[38..40], &regs[36..38]), 16).unwrap()
+    }
+}
+
+// Stop reply with SIGTRAP, either S05 or T05 with details
+fn stopped(reply: &str) -> bool {
+    reply.get(1..3) == Some("05")
+}
+
+#[test]
+fn debugger_controls_the_game() {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&ROM);
+    let mut server = GdbServer::bind(0).unwrap();
+    let port = server.port();
+
+    let client = thread::spawn(move || {
+        let mut gdb = Client { stream: TcpStream::connect(("127.0.0.1", port)).unwrap() };
+        gdb.stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+
+        assert!(gdb.send("qSupported:swbreak+").contains("PacketSize"));
+        assert!(gdb.send("qXfer:features:read:target.xml:0,fff").contains("org.chip8.core"));
+        assert_eq!(gdb.send("?").get(..1), Some("T"));
+        // Game ran until the debugger attached, now it's stopped in the loop
+        assert!([0x202, 0x204].contains(&gdb.pc()));
+        assert_eq!(gdb.send("m200,6"), "600170011202");
+
+        // Break on JP, then step back to ADD
+        assert_eq!(gdb.send("Z0,204,2"), "OK");
+        assert!(stopped(&gdb.send("c")));
+        assert_eq!(gdb.pc(), 0x204);
+        assert_eq!(gdb.send("z0,204,2"), "OK");
+        assert!(stopped(&gdb.send("s")));
+        assert_eq!(gdb.pc(), 0x202);
+
+        // V0 = 0x42, the rest as it was
+        let regs = gdb.send("g");
+        assert_eq!(gdb.send(&format!("G42{}", &regs[2..])), "OK");
+        assert!(gdb.send("g").starts_with("42"));
+        assert_eq!(gdb.send("M300,2:abcd"), "OK");
+        assert_eq!(gdb.send("m300,2"), "abcd");
+        gdb.send("D");
+    });
+
+    let mut events = Vec::new();
+    while !client.is_finished() {
+        events.extend(server.poll(&mut emu));
+        server.run_frame(&mut emu, TICKS_PER_FRAME);
+        thread::sleep(Duration::from_millis(1));
+    }
+    client.join().unwrap();
+    events.extend(server.poll(&mut emu));
+
+    assert!(matches!(events.as_slice(), [GdbEvent::Connected(_), GdbEvent::Disconnected(_)]), "{:?}", events);
+    assert!(!server.connected());
+    assert_eq!(emu.ram()[0x300..0x302], [0xAB, 0xCD]);
+    // Game runs freely again, counting up from 0x42
+    let before = emu.v_regs()[0];
+    server.run_frame(&mut emu, TICKS_PER_FRAME);
+    assert_ne!(emu.v_regs()[0], before);
+}
+
+#[test]
+fn detach_lets_the_game_run() {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&ROM);
+    let mut server = GdbServer::bind(0).unwrap();
+    let port = server.port();
+
+    let client = thread::spawn(move || {
+        let mut gdb = Client { stream: TcpStream::connect(("127.0.0.1", port)).unwrap() };
+        gdb.stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(gdb.send("?").get(..1), Some("T"));
+        // Server closes the connection, reset when it didn't read our last acknowledgement
+        match gdb.stream.read(&mut [0]) {
+            Ok(read) => assert_eq!(read, 0),
+            Err(err) => assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset),
+        }
+    });
+
+    while !server.halted() {
+        server.poll(&mut emu);
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert!(matches!(server.detach(&mut emu, "game closed"), Some(GdbEvent::Disconnected(_))));
+    client.join().unwrap();
+    assert!(!server.connected());
+    assert_eq!(server.detach(&mut emu, "game closed"), None);
+
+    let frame = emu.frame();
+    server.run_frame(&mut emu, TICKS_PER_FRAME);
+    assert_eq!(emu.frame(), frame + 1);
+}
@@ -0,0 +1,58 @@
+use chip8_core::bus::Memory;
+use chip8_core::guard::{Guard, Issue, IssueKind};
+use chip8_core::state::STATE_SIZE;
+use chip8_core::Emulator;
+
+fn run(rom: &[u8]) -> Emulator<Guard> {
+    let mut emu = Emulator::with_bus(Guard::new(Memory::new()), 0);
+    emu.load_data(rom);
+    emu.run_frame(10);
+    emu
+}
+
+#[test]
+fn uninitialized_read_is_reported() {
+    // LD I, 0x800; LD V0, [I]; loop: JP loop
+    let emu = run(&[0xA8, 0x00, 0xF0, 0x65, 0x12, 0x04]);
+    assert_eq!(emu.bus().issues(), [Issue { kind: IssueKind::UninitializedRead, addr: 0x800, pc: 0x202 }]);
+    assert_eq!(emu.bus().total(), 1);
+}
+
+#[test]
+fn executing_data_is_reported() {
+    // JP 0x300, nothing was ever loaded there
+    let emu = run(&[0x13, 0x00]);
+    let issues = emu.bus().issues();
+    assert_eq!(issues[0], Issue { kind: IssueKind::UninitializedRead, addr: 0x300, pc: 0x300 });
+    assert!(emu.bus().is_code(0x300));
+}
+
+#[test]
+fn code_overwrite_is_reported() {
+    // LD I, 0x200; LD [I], V0; loop: JP loop
+    let emu = run(&[0xA2, 0x00, 0xF0, 0x55, 0x12, 0x04]);
+    assert_eq!(emu.bus().issues(), [Issue { kind: IssueKind::CodeOverwrite, addr: 0x200, pc: 0x202 }]);
+}
+
+#[test]
+fn reset_forgets_issues() {
+    let mut emu = run(&[0xA8, 0x00, 0xF0, 0x65, 0x12, 0x04]);
+    emu.reset();
+    assert!(emu.bus().issues().is_empty());
+    assert_eq!(emu.bus().total(), 0);
+}
+
+#[test]
+fn rewind_keeps_issues() {
+    let mut emu = Emulator::with_bus(Guard::new(Memory::new()), 0);
+    emu.load_data(&[0xA8, 0x00, 0xF0, 0x65, 0x12, 0x04]);
+    let mut state = [0; STATE_SIZE];
+    emu.save_state(&mut state).unwrap();
+    emu.run_frame(10);
+    assert_eq!(emu.bus().total(), 1);
+
+    emu.rewind_state(&state).unwrap();
+    assert_eq!(emu.bus().total(), 1);
+    emu.load_state(&state).unwrap();
+    assert_eq!(emu.bus().total(), 0);
+}
@@ -0,0 +1,113 @@
+use chip8_core::movie::{Movie, MovieError, MovieEvent, MoviePlayer, MovieRecorder};
+use chip8_core::Emulator;
+
+const TICKS: usize = 10;
+const FRAMES: u64 = 50;
+
+// Counter at 0x300 goes up every loop key 5 is held:
+// loop: LD I, 0x300; LD V0, [I]; LD V1, 5; SKNP V1; ADD V0, 1; LD [I], V0; JP loop
+const COUNTER: [u8; 14] = [
+    0xA3, 0x00, 0xF0, 0x65, 0x61, 0x05, 0xE1, 0xA1, 0x70, 0x01, 0xF0, 0x55, 0x12, 0x00,
+];
+
+// Play the game for a while, holding key 5 now and then
+fn record() -> (Movie, u64) {
+    let mut emu = Emulator::with_seed(42);
+    emu.load_data(&COUNTER);
+    let mut recorder = MovieRecorder::new(&emu, &COUNTER, TICKS);
+    recorder.set_hash_interval(10);
+
+    for frame in 0..FRAMES {
+        if frame % 7 == 3 {
+            recorder.keypress(&mut emu, 5, true);
+        }
+        if frame % 7 == 5 {
+            recorder.keypress(&mut emu, 5, false);
+        }
+        emu.run_frame(TICKS);
+        recorder.end_frame(&emu);
+    }
+    (recorder.finish(), emu.state_hash())
+}
+
+fn replay(movie: Movie) -> Result<Emulator, MovieError> {
+    let mut emu = Emulator::with_seed(0);
+    let mut player = MoviePlayer::new(movie, &mut emu, &COUNTER)?;
+    for _ in 0..FRAMES {
+        player.run_frame(&mut emu)?;
+    }
+    assert!(player.finished());
+    Ok(emu)
+}
+
+#[test]
+fn recorded_game_replays_the_same() {
+    let (movie, hash) = record();
+    assert!(movie.events.iter().any(|event| matches!(event, MovieEvent::Key { .. })));
+    assert!(movie.events.iter().any(|event| matches!(event, MovieEvent::Hash { .. })));
+
+    let movie = Movie::parse(&movie.to_string()).unwrap();
+    let emu = replay(movie).unwrap();
+    assert_eq!(emu.state_hash(), hash);
+    assert_ne!(emu.ram()[0x300], 0);
+}
+
+#[test]
+fn changed_input_is_a_desync() {
+    let (mut movie, _) = record();
+    // Drop the first key press, the counter falls behind from there on
+    let first = movie.events.iter().position(|event| matches!(event, MovieEvent::Key { .. })).unwrap();
+    let removed = movie.events.remove(first);
+
+    let next_hash = movie.events.iter()
+        .find_map(|event| match event {
+            MovieEvent::Hash { frame, .. } if *frame >= removed.frame() => Some(*frame),
+            _ => None,
+        })
+        .unwrap();
+    match replay(movie) {
+        Err(MovieError::Desync { frame, .. }) => assert_eq!(frame, next_hash),
+        other => panic!("expected desync, got {:?}", other.map(|emu| emu.frame())),
+    }
+}
+
+#[test]
+fn other_rom_is_refused() {
+    let (movie, _) = record();
+    let mut emu = Emulator::with_seed(0);
+    let other = [0x12, 0x00];
+    assert!(matches!(MoviePlayer::new(movie, &mut emu, &other), Err(MovieError::RomMismatch { .. })));
+}
+
+#[test]
+fn key_state_has_to_be_zero_or_one() {
+    let (movie, _) = record();
+    let text = movie.to_string();
+    let broken = text.replacen(" 5 1\n", " 5 2\n", 1);
+    assert_ne!(text, broken);
+    assert!(matches!(Movie::parse(&broken), Err(MovieError::Parse { .. })));
+}
+
+#[test]
+fn ending_frame_before_first_one_is_ignored() {
+    let emu = Emulator::with_seed(0);
+    let mut recorder = MovieRecorder::new(&emu, &COUNTER, TICKS);
+    recorder.end_frame(&emu);
+    assert!(recorder.finish().events.is_empty());
+}
+
+#[test]
+fn out_of_range_numbers_are_refused() {
+    let (movie, _) = record();
+    let text = movie.to_string();
+    let broken = [
+        text.replacen("ticks 10\n", "ticks 0\n", 1),
+        text.replacen("ticks 10\n", "ticks 1001\n", 1),
+        text.replacen("quirks 00\n", "quirks 100\n", 1),
+        text.replacen(" 5 1\n", " 15 1\n", 1),
+    ];
+    for broken in broken {
+        assert_ne!(text, broken);
+        assert!(matches!(Movie::parse(&broken), Err(MovieError::Parse { .. })), "{}", broken);
+    }
+}
@@ -0,0 +1,148 @@
+// Two netplay peers over localhost, driven from one thread frame by frame
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use chip8_core::netplay::{Connection, Host, Netplay, NetplayError};
+use chip8_core::cheat::Cheat;
+use chip8_core::quirks::Quirks;
+use chip8_core::Emulator;
+
+// Counts held keys into V2 and mixes random numbers into V4, so both keys and seed matter:
+// LD V1, 0; SKNP V1; ADD V2, 1; ADD V1, 1; SE V1, 16; JP 0x202; RND V3, 0xFF; ADD V4, V3; JP 0x200
+const ROM: [u8; 18] = [
+    0x61, 0x00, 0xE1, 0xA1, 0x72, 0x01, 0x71, 0x01, 0x31, 0x10,
+    0x12, 0x02, 0xC3, 0xFF, 0x84, 0x34, 0x12, 0x00,
+];
+
+struct Player {
+    emu: Emulator,
+    netplay: Netplay,
+}
+
+// Host with given delay and rollback, guest joins with a different seed, quirks and a cheat
+fn connect(delay: u8, rollback: u8, guest_rom: &'static [u8]) -> (Result<Player, NetplayError>, Result<Player, NetplayError>) {
+    let host = Host::bind(0, delay, rollback).unwrap();
+    let port = host.port();
+    let hosting = thread::spawn(move || {
+        let mut emu = Emulator::with_seed(1);
+        emu.set_quirks(Quirks::COSMAC_VIP);
+        let netplay = host.accept().unwrap().start(&mut emu, &ROM, 12)?;
+        Ok(Player { emu, netplay })
+    });
+
+    let mut emu = Emulator::with_seed(2);
+    emu.cheats_mut().push(Cheat::parse("300=FF").unwrap());
+    let guest = Connection::join(("127.0.0.1", port)).unwrap()
+        .start(&mut emu, guest_rom, 7)
+        .map(|netplay| Player { emu, netplay });
+    (hosting.join().unwrap(), guest)
+}
+
+// Run both to the frame, pressing keys from the scripts: (frame, key, pressed)
+fn play(host: &mut Player, guest: &mut Player, frames: u64, host_keys: &[(u64, usize, bool)],
+        guest_keys: &[(u64, usize, bool)]) -> Result<(), NetplayError> {
+    while host.netplay.frame() < frames || guest.netplay.frame() < frames {
+        for (player, script) in [(&mut *host, host_keys), (&mut *guest, guest_keys)] {
+            if player.netplay.frame() >= frames {
+                continue;
+            }
+            let frame = player.netplay.frame();
+            for (_, key, pressed) in script.iter().filter(|(at, ..)| *at == frame) {
+                player.netplay.keypress(*key, *pressed);
+            }
+            player.netplay.run_frame(&mut player.emu)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn guest_takes_settings_of_host() {
+    let (host, guest) = connect(2, 0, &ROM);
+    let (host, guest) = (host.unwrap(), guest.unwrap());
+    assert!(host.netplay.is_host());
+    assert!(!guest.netplay.is_host());
+    assert_eq!(guest.emu.seed(), 1);
+    assert_eq!(guest.emu.quirks(), Quirks::COSMAC_VIP);
+    assert_eq!(guest.netplay.ticks_per_frame(), 12);
+    assert_eq!(guest.netplay.delay(), 2);
+    assert!(guest.emu.cheats().is_empty());
+}
+
+#[test]
+fn players_stay_in_sync_with_input_delay() {
+    let (host, guest) = connect(2, 0, &ROM);
+    let (mut host, mut guest) = (host.unwrap(), guest.unwrap());
+    play(&mut host, &mut guest, 300, &[(10, 0x1, true), (40, 0x1, false)], &[(20, 0xC, true), (90, 0xC, false)])
+        .unwrap();
+
+    assert_eq!(host.emu.frame(), 300);
+    assert_eq!(host.emu.state_hash(), guest.emu.state_hash());
+    assert_ne!(host.emu.v_regs()[2], 0);
+    assert_eq!(host.netplay.rollbacks(), 0);
+}
+
+#[test]
+fn late_keys_are_rolled_back() {
+    let (host, guest) = connect(0, 8, &ROM);
+    let (mut host, mut guest) = (host.unwrap(), guest.unwrap());
+    play(&mut host, &mut guest, 300, &[(15, 0x4, true), (25, 0x4, false)], &[(30, 0x5, true), (60, 0x5, false)])
+        .unwrap();
+
+    assert_eq!(host.emu.frame(), guest.emu.frame());
+    assert_eq!(host.emu.state_hash(), guest.emu.state_hash());
+    assert!(host.netplay.rollbacks() > 0);
+}
+
+#[test]
+fn different_roms_are_refused() {
+    let (host, guest) = connect(2, 0, &[0x12, 0x00]);
+    assert!(matches!(host, Err(NetplayError::RomMismatch { .. })));
+    assert!(matches!(guest, Err(NetplayError::RomMismatch { .. })));
+}
+
+#[test]
+fn desync_is_found() {
+    let (host, guest) = connect(1, 4, &ROM);
+    let (mut host, mut guest) = (host.unwrap(), guest.unwrap());
+    play(&mut host, &mut guest, 60, &[], &[]).unwrap();
+
+    guest.emu.ram_mut()[0x300] ^= 0xFF;
+    let result = play(&mut host, &mut guest, 120, &[], &[]);
+    assert!(matches!(result, Err(NetplayError::Desync { .. })), "{:?}", result);
+}
+
+#[test]
+fn quitting_player_stops_netplay() {
+    let (host, guest) = connect(2, 0, &ROM);
+    let (mut host, guest) = (host.unwrap(), guest.unwrap());
+    drop(guest);
+
+    let result = (0..1000).map(|_| host.netplay.run_frame(&mut host.emu)).find(Result::is_err);
+    assert!(matches!(result, Some(Err(NetplayError::Closed))), "{:?}", result);
+}
+
+#[test]
+fn keys_far_in_the_future_are_refused() {
+    let host = Host::bind(0, 2, 4).unwrap();
+    let port = host.port();
+    let hosting = thread::spawn(move || {
+        let mut emu = Emulator::with_seed(1);
+        let mut netplay = host.accept().unwrap().start(&mut emu, &ROM, 12).unwrap();
+        (0..1000).map(|_| netplay.run_frame(&mut emu)).find(Result::is_err)
+    });
+
+    // Peer agrees to everything by sending the hello of the host back, then floods the future
+    let mut peer = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut hello = [0; 59];
+    peer.read_exact(&mut hello).unwrap();
+    let mut reply = hello.to_vec();
+    reply.push(2);
+    reply.extend_from_slice(&1_000_000u64.to_le_bytes());
+    reply.extend_from_slice(&0xFFFFu16.to_le_bytes());
+    // Sent together, so the keys are there before the first frame
+    peer.write_all(&reply).unwrap();
+
+    let result = hosting.join().unwrap();
+    assert!(matches!(result, Some(Err(NetplayError::Protocol(_)))), "{:?}", result);
+}
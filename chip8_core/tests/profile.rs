@@ -0,0 +1,51 @@
+use chip8_core::profile::Profiler;
+use chip8_core::{Emulator, START_MEMORY_ADDR};
+
+// LD V0, 0; loop: ADD V0, 1; JP loop; CLS and a stray byte that never run
+const LOOP: [u8; 9] = [0x60, 0x00, 0x70, 0x01, 0x12, 0x02, 0x00, 0xE0, 0xFF];
+
+fn profiled() -> Emulator {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&LOOP);
+    emu.set_profiler(Some(Profiler::new()));
+    emu.run_frame(21);
+    emu
+}
+
+#[test]
+fn loop_gets_counted() {
+    let emu = profiled();
+    let profiler = emu.profiler().unwrap();
+    assert_eq!(profiler.total(), 21);
+    assert_eq!([profiler.hits(0x200), profiler.hits(0x202), profiler.hits(0x204), profiler.hits(0x206)], [1, 10, 10, 0]);
+    assert_eq!(profiler.hotspots(2), [(0x202, 10), (0x204, 10)]);
+    assert_eq!(profiler.opcode_types(), [("1NNN", 10), ("7XNN", 10), ("6XNN", 1)]);
+}
+
+#[test]
+fn heat_map_shows_hits_and_dead_code() {
+    let emu = profiled();
+    let end = START_MEMORY_ADDR + LOOP.len() as u16;
+    let mut report = Vec::new();
+    emu.profiler().unwrap().write_heat_map(emu.ram(), START_MEMORY_ADDR..end, &mut report).unwrap();
+    assert_eq!(String::from_utf8(report).unwrap(), "\
+; 21 instructions executed
+;
+; Hotspots
+;   0x202           10  47.6%  ADD V0, 0x01
+;   0x204           10  47.6%  JP 0x202
+;   0x200            1   4.8%  LD V0, 0x00
+;
+; Opcode types
+;   1NNN           10  47.6%
+;   7XNN           10  47.6%
+;   6XNN            1   4.8%
+;
+0x200  6000  LD V0, 0x00                       1   4.8% ##
+0x202  7001  ADD V0, 0x01                     10  47.6% ####################
+0x204  1202  JP 0x202                         10  47.6% ####################
+;;;; 0x206-0x208 never executed, 3 bytes
+0x206  00E0  CLS                               -
+0x208  FF    DB 0xFF
+");
+}
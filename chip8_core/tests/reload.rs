@@ -0,0 +1,43 @@
+// Reloading a rebuilt ROM while it runs, used by frontends watching the file
+use chip8_core::{Emulator, START_MEMORY_ADDR};
+
+// LD V0, 0x2A; LD I, 0x300; JP 0x204
+const OLD: [u8; 6] = [0x60, 0x2A, 0xA3, 0x00, 0x12, 0x04];
+// LD V0, 0x11; LD I, 0x300; CLS
+const NEW: [u8; 6] = [0x60, 0x11, 0xA3, 0x00, 0x00, 0xE0];
+
+fn running_old() -> Emulator {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&OLD);
+    for _ in 0..10 {
+        emu.tick();
+    }
+    emu
+}
+
+#[test]
+fn reload_starts_new_rom_from_the_beginning() {
+    let mut emu = running_old();
+    emu.reload(&NEW, false);
+    assert_eq!(emu.pc(), START_MEMORY_ADDR);
+    assert_eq!(emu.v_regs()[0], 0);
+    assert_eq!(emu.frame(), 0);
+    assert_eq!(&emu.ram()[0x200..0x206], &NEW);
+
+    emu.tick();
+    assert_eq!(emu.v_regs()[0], 0x11);
+}
+
+#[test]
+fn reload_can_keep_registers() {
+    let mut emu = running_old();
+    emu.reload(&NEW, true);
+    assert_eq!(emu.pc(), 0x204);
+    assert_eq!(emu.v_regs()[0], 0x2A);
+    assert_eq!(emu.i_reg(), 0x300);
+    assert_eq!(&emu.ram()[0x200..0x206], &NEW);
+
+    // Continues inside of the new code
+    emu.tick();
+    assert_eq!(emu.pc(), 0x206);
+}
@@ -0,0 +1,104 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use chip8_core::trace::{TraceEntry, TraceFormat, Tracer, CHANGED_I};
+use chip8_core::Emulator;
+
+// LD V0, 5; LD I, 0x300; loop: ADD V1, 1; JP loop
+const COUNTER: [u8; 8] = [0x60, 0x05, 0xA3, 0x00, 0x71, 0x01, 0x12, 0x04];
+
+fn entry() -> TraceEntry {
+    let mut v_reg = [0; 16];
+    v_reg[0] = 0x05;
+    v_reg[0xF] = 0x01;
+    TraceEntry {
+        pc: 0x202, opcode: 0xA300, v_reg, i_reg: 0x300, sp: 1, delay_timer: 0x3C, sound_timer: 0,
+        changed: CHANGED_I,
+    }
+}
+
+fn format(format: TraceFormat) -> String {
+    let mut line = String::new();
+    entry().format(&mut line, format);
+    line
+}
+
+// Writer the test can read after the tracer is done with it
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn full_format_has_every_register() {
+    assert_eq!(format(TraceFormat::Full),
+               "0202 A300 LD I, 0x300      V 05 00 00 00 00 00 00 00 00 00 00 00 00 00 00 01 I 0300 SP 1 DT 3C ST 00");
+}
+
+#[test]
+fn changes_format_has_changed_registers() {
+    assert_eq!(format(TraceFormat::Changes), "0202 A300 LD I, 0x300      I=0300");
+}
+
+#[test]
+fn log_format_is_key_value_pairs() {
+    assert_eq!(format(TraceFormat::Log),
+               "PC:0202 OP:A300 V0:05 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 \
+                V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:01 I:0300 SP:1 DT:3C ST:00");
+    assert_eq!(TraceFormat::from_name("log"), Some(TraceFormat::Log));
+    assert_eq!(TraceFormat::from_name("json"), None);
+}
+
+#[test]
+fn ring_keeps_last_entries() {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&COUNTER);
+    emu.set_tracer(Some(Tracer::ring(4, TraceFormat::Changes)));
+    emu.run_frame(10);
+
+    let tracer = emu.take_tracer().unwrap();
+    let entries: Vec<_> = tracer.entries().collect();
+    assert_eq!(entries.len(), 4);
+    // 10 instructions: two setup ones, then 4 loops of ADD and JP
+    assert_eq!(entries.iter().map(|entry| entry.pc).collect::<Vec<_>>(), [0x204, 0x206, 0x204, 0x206]);
+    assert_eq!(entries[2].v_reg[1], 4);
+
+    let mut dump = Vec::new();
+    tracer.dump(&mut dump).unwrap();
+    let text = String::from_utf8(dump).unwrap();
+    assert_eq!(text.lines().count(), 4);
+    assert!(text.starts_with("0204 7101 ADD V1, 0x01"));
+}
+
+#[test]
+fn empty_ring_keeps_nothing() {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&COUNTER);
+    emu.set_tracer(Some(Tracer::ring(0, TraceFormat::Full)));
+    emu.run_frame(10);
+    assert_eq!(emu.take_tracer().unwrap().entries().count(), 0);
+}
+
+#[test]
+fn writer_starts_at_address() {
+    let out = Shared::default();
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(&COUNTER);
+    emu.set_tracer(Some(Tracer::writer(out.clone(), TraceFormat::Log).start_at(0x204)));
+    emu.run_frame(10);
+    emu.take_tracer().unwrap().flush().unwrap();
+
+    let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines.len(), 8);
+    assert!(lines[0].starts_with("PC:0204 OP:7101 V0:05 V1:01 "));
+    assert!(lines[7].starts_with("PC:0206 OP:1204 V0:05 V1:04 "));
+}
@@ -0,0 +1,48 @@
+// ROMs which used to index past memory or the stack, they have to keep running instead.
+// Fuzz targets in fuzz/ look for more of these.
+use chip8_core::{Emulator, MAX_ROM_SIZE, RAM_SIZE, START_MEMORY_ADDR};
+
+fn run(rom: &[u8]) -> Emulator {
+    let mut emu = Emulator::with_seed(0);
+    emu.load_data(rom);
+    for _ in 0..1000 {
+        emu.tick();
+    }
+    emu
+}
+
+#[test]
+fn pc_wraps_at_end_of_memory() {
+    // JP 0xFFF, last opcode byte is read from 0x000
+    let emu = run(&[0x1F, 0xFF]);
+    assert!((emu.pc() as usize) < RAM_SIZE);
+}
+
+#[test]
+fn i_based_access_wraps() {
+    // LD I, 0xFFF; LD B, V0; LD [I], VF; LD VF, [I]; DRW V0, V0, 15; JP 0x200
+    run(&[0xAF, 0xFF, 0xF0, 0x33, 0xFF, 0x55, 0xFF, 0x65, 0xD0, 0x0F, 0x12, 0x00]);
+}
+
+#[test]
+fn stack_overflow_and_underflow_are_ignored() {
+    // CALL 0x200 forever
+    let emu = run(&[0x22, 0x00]);
+    assert_eq!(emu.sp() as usize, emu.stack().len());
+    // RET with empty stack
+    let emu = run(&[0x00, 0xEE]);
+    assert_eq!(emu.sp(), 0);
+}
+
+#[test]
+fn unknown_opcodes_and_keys_are_skipped() {
+    // Unknown opcode; LD V0, 0xFF; SKP V0; SKNP V0; JP 0x200
+    run(&[0xFF, 0xFF, 0x60, 0xFF, 0xE0, 0x9E, 0xE0, 0xA1, 0x12, 0x00]);
+}
+
+#[test]
+fn oversized_rom_is_cut_off() {
+    let emu = run(&vec![0x12; RAM_SIZE]);
+    assert_eq!(emu.ram().len(), RAM_SIZE);
+    assert_eq!(START_MEMORY_ADDR as usize + MAX_ROM_SIZE, RAM_SIZE);
+}
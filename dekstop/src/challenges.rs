@@ -0,0 +1,89 @@
+// Achievements and leaderboard of the game, defined in challenges/<ROM SHA-1>.txt.
+// Best scores are kept next to it in challenges/<ROM SHA-1>.scores
+use std::fs;
+use std::path::PathBuf;
+use chip8_core::bus::Bus;
+use chip8_core::challenge::{Challenge, Leaderboard};
+use chip8_core::Emulator;
+use crate::overlay::Overlay;
+
+const CHALLENGES_DIR: &str = "challenges";
+
+
+pub struct Challenges {
+    challenge: Challenge,
+    leaderboard: Leaderboard,
+    scores_path: PathBuf,
+    // Scores of games played with cheats don't go on the leaderboard
+    cheated: bool,
+    record_shown: bool,
+}
+
+impl Challenges {
+    // None when the game has no challenges
+    pub fn load(rom: &[u8], overlay: &mut Overlay) -> Option<Self> {
+        let hash = romdb::sha1(rom);
+        let path = PathBuf::from(CHALLENGES_DIR).join(format!("{}.txt", hash));
+        let challenge = Challenge::parse(&fs::read_to_string(&path).ok()?)
+            .map_err(|err| overlay.notify(format!("Invalid challenges in {}: {}", path.display(), err)))
+            .ok()?;
+
+        let scores_path = PathBuf::from(CHALLENGES_DIR).join(format!("{}.scores", hash));
+        let leaderboard = match fs::read_to_string(&scores_path) {
+            Ok(text) => Leaderboard::parse(&text).unwrap_or_else(|err| {
+                overlay.notify(format!("Invalid leaderboard in {}: {}", scores_path.display(), err));
+                Leaderboard::default()
+            }),
+            Err(_) => Leaderboard::default(),
+        };
+        overlay.notify(format!("{} achievements to unlock", challenge.achievements.len()));
+        Some(Self { challenge, leaderboard, scores_path, cheated: false, record_shown: false })
+    }
+
+    // Check conditions after a frame
+    pub fn update(&mut self, emu: &Emulator<impl Bus>, overlay: &mut Overlay) {
+        self.cheated |= emu.cheats().iter().any(|cheat| cheat.enabled);
+        for achievement in self.challenge.update(emu) {
+            overlay.notify(format!("Achievement unlocked: {}", achievement.name));
+        }
+
+        if let Some(score) = self.challenge.best_score() {
+            if !self.record_shown && !self.cheated && score > 0 && self.leaderboard.is_record(score) {
+                overlay.notify(format!("New high score {}", score));
+                self.record_shown = true;
+            }
+        }
+    }
+
+    pub fn show_leaderboard(&self, overlay: &mut Overlay) {
+        if self.leaderboard.entries.is_empty() {
+            overlay.notify("No high scores yet");
+        }
+        for (place, (score, player)) in self.leaderboard.entries.iter().enumerate() {
+            overlay.notify(format!("{}. {} {}", place + 1, score, player));
+        }
+    }
+
+    // Put the best score of the game on the leaderboard
+    pub fn finish(mut self, player: &str) {
+        let unlocked = self.challenge.achievements.iter().filter(|achievement| achievement.unlocked).count();
+        println!("Unlocked {} of {} achievements", unlocked, self.challenge.achievements.len());
+
+        let Some(score) = self.challenge.best_score().filter(|score| *score > 0) else {
+            return;
+        };
+        if self.cheated {
+            println!("Score {} not saved, cheats were used", score);
+            return;
+        }
+        match self.leaderboard.insert(score, player) {
+            Some(place) => {
+                match fs::write(&self.scores_path, self.leaderboard.to_string()) {
+                    Ok(()) => println!("Score {} is number {} on the leaderboard", score, place + 1),
+                    Err(err) => eprintln!("Failed to save leaderboard: {}", err),
+                }
+            }
+            None => println!("Score {} didn't make the leaderboard", score),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// Cheat panel, opened with F3: searches memory for counters like lives and manages codes
+// of the game. Codes are saved in cheats/<ROM SHA-1>.txt and loaded whenever the game starts.
+use std::fs;
+use std::path::PathBuf;
+use chip8_core::bus::Bus;
+use chip8_core::cheat::{format_cheats, parse_cheats, Cheat, CheatSearch, Compare};
+use chip8_core::Emulator;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::{pixels::Color, rect::Rect, render::{BlendMode, Canvas}, video::Window};
+use crate::overlay::{draw_text, Overlay, LINE_HEIGHT};
+
+const CHEATS_DIR: &str = "cheats";
+// Candidates listed while searching, the rest are only counted
+const SHOWN_CANDIDATES: usize = 8;
+const MARGIN: i32 = 8;
+const HELP: [&str; 3] = [
+    "new - start search, = 5 - equal, changed, same, up, down",
+    "add 2F0=03 name - add code, on 1 / off 1 / del 1 - change code",
+    "F3 or esc - close",
+];
+
+
+pub struct CheatPanel {
+    pub open: bool,
+    input: String,
+    search: Option<CheatSearch>,
+    // Answer to the last command
+    message: String,
+    path: PathBuf,
+}
+
+impl CheatPanel {
+    // Panel for the ROM, its saved codes go straight into the emulator
+    pub fn load(rom: &[u8], emu: &mut Emulator<impl Bus>, overlay: &mut Overlay) -> Self {
+        let path = PathBuf::from(CHEATS_DIR).join(format!("{}.txt", romdb::sha1(rom)));
+        if let Ok(text) = fs::read_to_string(&path) {
+            match parse_cheats(&text) {
+                Ok(cheats) => {
+                    overlay.notify(format!("Loaded {} cheat codes", cheats.len()));
+                    *emu.cheats_mut() = cheats;
+                }
+                Err(err) => overlay.notify(format!("Invalid cheats in {}: {}", path.display(), err)),
+            }
+        }
+        Self { open: false, input: String::new(), search: None, message: String::new(), path }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    // Typing while the panel is open, returns false for events the rest of the program should get
+    pub fn handle(&mut self, event: &Event, emu: &mut Emulator<impl Bus>) -> bool {
+        if !self.open {
+            return false;
+        }
+        match event {
+            Event::TextInput {text, ..} => self.input.push_str(text),
+            Event::KeyDown {keycode: Some(Keycode::Backspace), ..} => {
+                self.input.pop();
+            },
+            Event::KeyDown {keycode: Some(Keycode::Return | Keycode::KpEnter), ..} => {
+                let command = std::mem::take(&mut self.input);
+                self.message = self.run(command.trim(), emu).unwrap_or_else(|err| err);
+            },
+            // Hotkeys keep working
+            Event::KeyDown {keycode: Some(key), ..} if is_hotkey(*key) => return false,
+            Event::KeyDown {..} => (),
+            _ => return false,
+        }
+        true
+    }
+
+    // Execute a command, the answer is shown under the input line
+    fn run(&mut self, command: &str, emu: &mut Emulator<impl Bus>) -> Result<String, String> {
+        let (name, argument) = match command.strip_prefix('=') {
+            Some(value) => ("=", value),
+            None => command.split_once(' ').unwrap_or((command, "")),
+        };
+        let argument = argument.trim();
+        let compare = match name {
+            "new" => {
+                self.search = Some(CheatSearch::new(emu.ram()));
+                return Ok("Search started, every address is a candidate".to_string());
+            }
+            "=" => Compare::Equal(parse_value(argument)?),
+            "changed" => Compare::Changed,
+            "same" => Compare::Unchanged,
+            "up" => Compare::Increased,
+            "down" => Compare::Decreased,
+            "add" => {
+                let cheat = Cheat::parse(argument)?;
+                let added = format!("Added {}", cheat);
+                emu.cheats_mut().push(cheat);
+                return self.save(emu.cheats()).map(|_| added);
+            }
+            "on" | "off" | "del" => {
+                let index = argument.parse::<usize>().ok()
+                    .and_then(|number| number.checked_sub(1))
+                    .filter(|index| *index < emu.cheats().len())
+                    .ok_or_else(|| format!("No code number {}", argument))?;
+                match name {
+                    "del" => {
+                        emu.cheats_mut().remove(index);
+                    }
+                    _ => emu.cheats_mut()[index].enabled = name == "on",
+                }
+                return self.save(emu.cheats()).map(|_| "Codes saved".to_string());
+            }
+            _ => return Err(format!("Unknown command {}", name)),
+        };
+
+        let search = self.search.as_mut().ok_or("Start a search with new first")?;
+        let left = search.filter(emu.ram(), compare);
+        Ok(format!("{} addresses left", left))
+    }
+
+    fn save(&self, cheats: &[Cheat]) -> Result<(), String> {
+        let _ = fs::create_dir_all(CHEATS_DIR);
+        fs::write(&self.path, format_cheats(cheats))
+            .map_err(|err| format!("Failed to save {}: {}", self.path.display(), err))
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, emu: &Emulator<impl Bus>) {
+        if !self.open {
+            return;
+        }
+        let (window_width, window_height) = canvas.output_size().unwrap();
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+        canvas.fill_rect(Rect::new(0, 0, window_width, window_height)).unwrap();
+        canvas.set_blend_mode(BlendMode::None);
+
+        let mut lines: Vec<String> = HELP.iter().map(|line| line.to_string()).collect();
+        lines.push(String::new());
+        if let Some(search) = &self.search {
+            let candidates = search.candidates();
+            let shown: Vec<String> = candidates.iter().take(SHOWN_CANDIDATES)
+                .map(|address| format!("{:03X}={:02X}", address, emu.ram()[*address as usize]))
+                .collect();
+            lines.push(format!("Search: {} addresses {}", candidates.len(), shown.join(" ")));
+        }
+        if emu.cheats().is_empty() {
+            lines.push("No codes".to_string());
+        }
+        for (number, cheat) in emu.cheats().iter().enumerate() {
+            lines.push(format!("{}. {}", number + 1, cheat));
+        }
+        lines.push(String::new());
+        lines.push(format!("> {}_", self.input));
+        lines.push(self.message.clone());
+
+        let mut y = MARGIN;
+        for line in &lines {
+            if !line.is_empty() {
+                draw_text(canvas, line, MARGIN, y);
+            }
+            y += LINE_HEIGHT;
+        }
+    }
+}
+
+// Decimal, or hex with 0x, as scores are usually shown in decimal
+fn parse_value(text: &str) -> Result<u8, String> {
+    let value = match text.strip_prefix("0x") {
+        Some(digits) => u8::from_str_radix(digits, 16),
+        None => text.parse(),
+    };
+    value.map_err(|_| format!("Invalid value {}", text))
+}
+
+fn is_hotkey(key: Keycode) -> bool {
+    [Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5, Keycode::F6, Keycode::F7, Keycode::F8,
+     Keycode::F9, Keycode::F10, Keycode::F11, Keycode::F12, Keycode::Escape].contains(&key)
+}
@@ -0,0 +1,189 @@
+use std::env;
+use chip8_core::netplay::{DEFAULT_DELAY, DEFAULT_ROLLBACK};
+use chip8_core::quirks::Quirks;
+use chip8_core::trace::TraceFormat;
+
+pub const USAGE: &str = "Usage: cargo run [options] [game/path]
+
+Without a game, or with a directory instead, the launcher lists games to choose from.
+
+Options:
+    --quirks <vip|schip|modern>   interpreter to imitate
+    --seed <number>               seed of random numbers
+    --record-movie <file>         save every key press into a movie
+    --play-movie <file>           replay a movie recorded earlier
+    --check-memory                report code overwriting itself, writes below 0x200
+                                  and reads of uninitialized memory
+    --break-on-memory             like --check-memory, but also pause when it happens
+    --trace <file>                write every executed instruction into the file
+    --trace-from <address>        start tracing when this address (in hex) gets executed
+    --trace-format <full|changes|log>
+                                  all registers on every line, only changed ones, or
+                                  key:value pairs like Gameboy Doctor logs
+    --profile <file>              count executions and write a heat map of the ROM on exit
+    --gdb <port>                  let GDB attach on localhost, e.g. target remote :1234
+    --watch                       reload the ROM whenever its file changes
+    --keep-registers              with --watch, keep registers, stack and timers on reload
+    --host <port>                 wait for the second player, who joins on localhost or
+                                  through a forwarded port
+    --join <address:port>         play together with a player who hosts the same game
+    --delay <frames>              when hosting, frames before key presses count (default 2)
+    --rollback <frames>           when hosting, frames run ahead of late keys of the other
+                                  player, rolled back if they turn out wrong (default 8)
+    --player <name>               name put on leaderboards, the user name by default
+    --script <file>               run a Rhai script with access to the emulator";
+
+
+// Settings given on command line
+#[derive(Debug)]
+pub struct Options {
+    // Game or directory for the launcher
+    pub rom: Option<String>,
+    pub quirks: Option<Quirks>,
+    pub seed: Option<u64>,
+    pub record_movie: Option<String>,
+    pub play_movie: Option<String>,
+    pub check_memory: bool,
+    pub break_on_memory: bool,
+    pub trace: Option<String>,
+    pub trace_from: Option<u16>,
+    pub trace_format: Option<TraceFormat>,
+    pub profile: Option<String>,
+    pub gdb: Option<u16>,
+    pub watch: bool,
+    pub keep_registers: bool,
+    pub host: Option<u16>,
+    pub join: Option<String>,
+    pub delay: u8,
+    pub rollback: u8,
+    // Name put on the leaderboard
+    pub player: String,
+    pub script: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            rom: None,
+            quirks: None,
+            seed: None,
+            record_movie: None,
+            play_movie: None,
+            check_memory: false,
+            break_on_memory: false,
+            trace: None,
+            trace_from: None,
+            trace_format: None,
+            profile: None,
+            gdb: None,
+            watch: false,
+            keep_registers: false,
+            host: None,
+            join: None,
+            delay: DEFAULT_DELAY,
+            rollback: DEFAULT_ROLLBACK,
+            script: None,
+            player: env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_else(|_| "player".to_string()),
+        }
+    }
+}
+
+impl Options {
+    // Parse arguments, without name of the program
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            // Options with a value take exactly one
+            let mut value = || args.next().cloned().ok_or_else(|| format!("Missing value for {}", arg));
+
+            match arg.as_str() {
+                "--quirks" => {
+                    let name = value()?;
+                    options.quirks = Some(Quirks::preset(&name).ok_or_else(|| format!("Unknown quirks {}", name))?);
+                }
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = Some(seed.parse().map_err(|_| format!("Invalid seed {}", seed))?);
+                }
+                "--record-movie" => options.record_movie = Some(value()?),
+                "--play-movie" => options.play_movie = Some(value()?),
+                "--check-memory" => options.check_memory = true,
+                "--break-on-memory" => {
+                    options.check_memory = true;
+                    options.break_on_memory = true;
+                }
+                "--trace" => options.trace = Some(value()?),
+                "--trace-from" => {
+                    let address = value()?;
+                    let digits = address.trim_start_matches("0x").trim_start_matches("0X");
+                    options.trace_from = Some(u16::from_str_radix(digits, 16)
+                        .map_err(|_| format!("Invalid address {}", address))?);
+                }
+                "--trace-format" => {
+                    let name = value()?;
+                    options.trace_format = Some(TraceFormat::from_name(&name)
+                        .ok_or_else(|| format!("Unknown trace format {}", name))?);
+                }
+                "--profile" => options.profile = Some(value()?),
+                "--gdb" => {
+                    let port = value()?;
+                    options.gdb = Some(port.parse().map_err(|_| format!("Invalid port {}", port))?);
+                }
+                "--watch" => options.watch = true,
+                "--keep-registers" => options.keep_registers = true,
+                "--host" => {
+                    let port = value()?;
+                    options.host = Some(port.parse().map_err(|_| format!("Invalid port {}", port))?);
+                }
+                "--join" => options.join = Some(value()?),
+                "--delay" => {
+                    let frames = value()?;
+                    options.delay = frames.parse().map_err(|_| format!("Invalid delay {}", frames))?;
+                }
+                "--rollback" => {
+                    let frames = value()?;
+                    options.rollback = frames.parse().map_err(|_| format!("Invalid rollback {}", frames))?;
+                }
+                "--player" => options.player = value()?,
+                "--script" => options.script = Some(value()?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ if options.rom.is_none() => options.rom = Some(arg.clone()),
+                _ => return Err(format!("Unexpected argument {}", arg)),
+            }
+        }
+
+        if options.record_movie.is_some() && options.play_movie.is_some() {
+            return Err("Can't record and play a movie at the same time".to_string());
+        }
+
+        if options.trace.is_none() && (options.trace_from.is_some() || options.trace_format.is_some()) {
+            return Err("Tracing options need --trace".to_string());
+        }
+        if options.keep_registers && !options.watch {
+            return Err("--keep-registers needs --watch".to_string());
+        }
+
+        // Keys pressed by scripts wouldn't get into movies or to the other player
+        if options.script.is_some() && (options.record_movie.is_some() || options.play_movie.is_some()
+            || options.host.is_some() || options.join.is_some()) {
+            return Err("Scripts can't be combined with movies or netplay".to_string());
+        }
+
+        // Both players have to run exactly the same thing
+        if options.host.is_some() || options.join.is_some() {
+            if options.host.is_some() && options.join.is_some() {
+                return Err("Can't host and join at the same time".to_string());
+            }
+            if options.rom.is_none() {
+                return Err("Netplay needs a game".to_string());
+            }
+            if options.record_movie.is_some() || options.play_movie.is_some() || options.trace.is_some()
+                || options.profile.is_some() || options.gdb.is_some() || options.watch {
+                return Err("Netplay can't be combined with movies, tracing, profiling, --gdb or --watch".to_string());
+            }
+        }
+        Ok(options)
+    }
+}
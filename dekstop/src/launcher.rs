@@ -0,0 +1,229 @@
+// In-window launcher: ROMs of a directory in a grid, with their titles from the database
+// and thumbnails made by running every ROM for a moment without showing it.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use chip8_core::*;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::{pixels::{Color, PixelFormatEnum}, rect::Rect, render::{Canvas, Texture, TextureCreator},
+           video::{Window, WindowContext}};
+use crate::overlay::{draw_text, text_width, LINE_HEIGHT};
+
+// Frames run before the thumbnail is taken, most games are past their first screen by then
+const PREVIEW_FRAMES: usize = 90;
+// Fixed seed, so thumbnails look the same on every scan
+const PREVIEW_SEED: u64 = 0;
+const THUMBNAIL_SCALE: u32 = 3;
+const THUMBNAIL_WIDTH: u32 = SCREEN_WIDTH as u32 * THUMBNAIL_SCALE;
+const THUMBNAIL_HEIGHT: u32 = SCREEN_HEIGHT as u32 * THUMBNAIL_SCALE;
+const CELL_PADDING: u32 = 12;
+const CELL_WIDTH: u32 = THUMBNAIL_WIDTH + 2 * CELL_PADDING;
+const CELL_HEIGHT: u32 = THUMBNAIL_HEIGHT + 3 * CELL_PADDING + LINE_HEIGHT as u32;
+const HEADER_HEIGHT: i32 = 40;
+const MARGIN: i32 = 8;
+
+
+struct Entry<'a> {
+    path: PathBuf,
+    title: String,
+    rom: Vec<u8>,
+    thumbnail: Texture<'a>,
+}
+
+// ROM running headlessly, the way it would run as a game
+struct Preview {
+    emu: Emulator,
+    ticks: usize,
+    palette: Palette,
+}
+
+impl Preview {
+    fn boot(rom: &[u8]) -> Self {
+        let game = romdb::identify(rom);
+        let mut emu = Emulator::with_seed(PREVIEW_SEED);
+        if let Some(game) = game {
+            game.apply(&mut emu);
+        }
+        emu.load_data(rom);
+
+        let mut preview = Self {
+            emu,
+            ticks: game.map_or(TICKS_PER_FRAME, romdb::Game::ticks_per_frame),
+            palette: game.and_then(romdb::Game::palette).unwrap_or_default(),
+        };
+        for _ in 0..PREVIEW_FRAMES {
+            preview.emu.run_frame(preview.ticks);
+        }
+        preview
+    }
+
+    fn draw(&self, texture: &mut Texture) {
+        let mut pixels = [0; RGBA_SIZE];
+        self.emu.write_rgba(&mut pixels, &self.palette);
+        texture.update(None, &pixels, SCREEN_WIDTH * 4).unwrap();
+    }
+}
+
+pub struct Launcher<'a> {
+    dir: PathBuf,
+    entries: Vec<Entry<'a>>,
+    selected: usize,
+    // First row on the screen, follows the selection
+    scroll: usize,
+    // Selected game keeps running inside of its thumbnail
+    preview: Option<Box<Preview>>,
+}
+
+impl<'a> Launcher<'a> {
+    // Read every file of the directory which could be a ROM and make its thumbnail
+    pub fn scan(texture_creator: &'a TextureCreator<WindowContext>, dir: &Path) -> io::Result<Self> {
+        let mut entries = Vec::new();
+        // Files which can't be read are left out, one of them shouldn't hide the rest
+        for file in fs::read_dir(dir)?.flatten() {
+            let path = file.path();
+            // Anything bigger wouldn't fit into memory, so it isn't even read
+            let fits = fs::metadata(&path)
+                .is_ok_and(|metadata| metadata.is_file() && metadata.len() <= MAX_ROM_SIZE as u64);
+            if !fits {
+                continue;
+            }
+            let Ok(rom) = fs::read(&path) else {
+                continue;
+            };
+            if rom.is_empty() || rom.len() > MAX_ROM_SIZE {
+                continue;
+            }
+
+            // Unknown ROMs are shown with their file name
+            let title = match romdb::identify(&rom) {
+                Some(game) => game.title.clone(),
+                None => path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            };
+            let mut thumbnail = texture_creator
+                .create_texture_streaming(PixelFormatEnum::RGBA32, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+                .unwrap();
+            Preview::boot(&rom).draw(&mut thumbnail);
+            entries.push(Entry { path, title, rom, thumbnail });
+        }
+        entries.sort_by_key(|entry| entry.title.to_lowercase());
+
+        let mut launcher = Self { dir: dir.to_path_buf(), entries, selected: 0, scroll: 0, preview: None };
+        launcher.select(0);
+        Ok(launcher)
+    }
+
+    // Move selection, the newly selected game starts running in its thumbnail
+    fn select(&mut self, index: usize) {
+        let index = index.min(self.entries.len().saturating_sub(1));
+        if index == self.selected && self.preview.is_some() {
+            return;
+        }
+        self.selected = index;
+        self.preview = self.entries.get(index).map(|entry| Box::new(Preview::boot(&entry.rom)));
+    }
+
+    // React to keys and mouse, returns path of the game to start
+    pub fn handle(&mut self, event: &Event, canvas: &Canvas<Window>) -> Option<PathBuf> {
+        let columns = columns(canvas);
+        match event {
+            Event::KeyDown {keycode: Some(Keycode::Left), ..} => self.select(self.selected.saturating_sub(1)),
+            Event::KeyDown {keycode: Some(Keycode::Right), ..} => self.select(self.selected + 1),
+            Event::KeyDown {keycode: Some(Keycode::Up), ..} => self.select(self.selected.saturating_sub(columns)),
+            Event::KeyDown {keycode: Some(Keycode::Down), ..} => self.select(self.selected + columns),
+            Event::KeyDown {keycode: Some(Keycode::Return | Keycode::KpEnter), repeat: false, ..} => {
+                return self.entries.get(self.selected).map(|entry| entry.path.clone());
+            },
+            // First click selects, clicking the selected game starts it
+            Event::MouseButtonDown {mouse_btn: MouseButton::Left, x, y, ..} => {
+                let index = self.entry_at(*x, *y, columns)?;
+                if index == self.selected {
+                    return Some(self.entries[index].path.clone());
+                }
+                self.select(index);
+            },
+            _ => ()
+        }
+        None
+    }
+
+    // Advance the live preview by one frame
+    pub fn update(&mut self) {
+        if let Some(preview) = self.preview.as_mut() {
+            preview.emu.run_frame(preview.ticks);
+            preview.draw(&mut self.entries[self.selected].thumbnail);
+        }
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>) {
+        let (_, window_height) = canvas.output_size().unwrap();
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+
+        let header = format!("{} - arrows choose, enter plays, esc quits", self.dir.display());
+        draw_text(canvas, &header, MARGIN, MARGIN + 4);
+        if self.entries.is_empty() {
+            draw_text(canvas, "No games found", MARGIN, HEADER_HEIGHT + MARGIN);
+            return;
+        }
+
+        // Scroll just enough for the selected game to be visible
+        let columns = columns(canvas);
+        let rows = ((window_height as i32 - HEADER_HEIGHT) / CELL_HEIGHT as i32).max(1) as usize;
+        let row = self.selected / columns;
+        if row < self.scroll {
+            self.scroll = row;
+        }
+        else if row >= self.scroll + rows {
+            self.scroll = row + 1 - rows;
+        }
+
+        for (index, entry) in self.entries.iter().enumerate().skip(self.scroll * columns).take(rows * columns) {
+            let (x, y) = self.cell_position(index, columns);
+            if index == self.selected {
+                canvas.set_draw_color(Color::RGB(60, 60, 60));
+                canvas.fill_rect(Rect::new(x, y, CELL_WIDTH, CELL_HEIGHT)).unwrap();
+            }
+
+            let thumbnail = Rect::new(x + CELL_PADDING as i32, y + CELL_PADDING as i32, THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT);
+            canvas.copy(&entry.thumbnail, None, thumbnail).unwrap();
+
+            let title = shorten(&entry.title, CELL_WIDTH - CELL_PADDING);
+            let title_x = x + (CELL_WIDTH as i32 - text_width(&title) as i32) / 2;
+            draw_text(canvas, &title, title_x, thumbnail.bottom() + CELL_PADDING as i32);
+        }
+    }
+
+    // Top left corner of the cell, relative to the scrolled grid
+    fn cell_position(&self, index: usize, columns: usize) -> (i32, i32) {
+        let column = index % columns;
+        let row = index / columns - self.scroll;
+        (MARGIN + (column as u32 * CELL_WIDTH) as i32, HEADER_HEIGHT + (row as u32 * CELL_HEIGHT) as i32)
+    }
+
+    fn entry_at(&self, x: i32, y: i32, columns: usize) -> Option<usize> {
+        if x < MARGIN || y < HEADER_HEIGHT {
+            return None;
+        }
+        let column = (x - MARGIN) as usize / CELL_WIDTH as usize;
+        let row = (y - HEADER_HEIGHT) as usize / CELL_HEIGHT as usize + self.scroll;
+        let index = row * columns + column;
+        (column < columns && index < self.entries.len()).then_some(index)
+    }
+}
+
+// Cells fitting next to each other in the window
+fn columns(canvas: &Canvas<Window>) -> usize {
+    let (window_width, _) = canvas.output_size().unwrap();
+    ((window_width as i32 - 2 * MARGIN) / CELL_WIDTH as i32).max(1) as usize
+}
+
+// Cut text so it fits into the width
+fn shorten(text: &str, width: u32) -> String {
+    let mut text = text.to_string();
+    while text_width(&text) > width {
+        text.pop();
+    }
+    text
+}
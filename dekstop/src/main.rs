@@ -1,34 +1,92 @@
+mod challenges;
+mod cheats;
+mod cli;
+mod launcher;
+mod overlay;
+mod recorder;
+mod scheduler;
+mod script;
+mod screen;
+mod session;
+mod watch;
+
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use chip8_core::*;
+use chip8_core::bus::Memory;
+use chip8_core::gdb::GdbServer;
+use chip8_core::guard::Guard;
+use chip8_core::movie::Movie;
+use chip8_core::netplay::{Connection, Host};
 use sdl2::event::Event;
-use std::fs::File;
-use std::io::Read;
-use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window, keyboard::Keycode};
+use sdl2::keyboard::Keycode;
+use sdl2::keyboard::Mod;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use cli::{Options, USAGE};
+use launcher::Launcher;
+use overlay::Overlay;
+use screen::{toggle_fullscreen, Screen};
+use recorder::{Recorder, VideoFormat};
+use scheduler::{Scheduler, FAST_FORWARD_BUDGET};
+use session::Session;
 use std::time::{Duration, Instant};
-const CYCLE_DURATION: Duration = Duration::from_nanos(1_000_000_000 / CLOCK_SPEED);
-
-
 
 const SCREEN_SCALE: u32 = 15;
 const WINDOW_WIDTH: u32 = SCREEN_WIDTH as u32 * SCREEN_SCALE;
 const WINDOW_HEIGHT: u32 = SCREEN_HEIGHT as u32 * SCREEN_SCALE;
-const TICKS_PER_FRAME: usize = 10;
+const WINDOW_TITLE: &str = "Rust Chip 8 Emulator";
+
+// Window shows either the list of games or the one being played
+enum Mode<'a> {
+    Launcher(Launcher<'a>),
+    Game(Box<Session>),
+}
 
 
 fn main() {
     // Get env arguments
-    let args: Vec<_> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: cargo run game/path");
+    let args: Vec<_> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}\n\n{}", err, USAGE);
+            return;
+        }
+    };
+    // Directory given instead of a game only opens the launcher
+    let rom_path = options.rom.as_deref().filter(|path| !Path::new(path).is_dir());
+    let games_dir = games_dir(options.rom.as_deref());
+
+    // Movie is read once, every started game gets checked against it
+    let movie = options.play_movie.as_ref().map(|path| {
+        let text = fs::read_to_string(path).expect("Failed to open the movie");
+        Movie::parse(&text).unwrap_or_else(|err| panic!("Invalid movie: {}", err))
+    });
+
+    if rom_path.is_none() && (options.host.is_some() || options.join.is_some()) {
+        println!("Netplay needs a game, not a directory\n\n{}", USAGE);
         return;
     }
+    // Other player connects before the window opens, hosting waits here until they do
+    let connection = match (options.host, &options.join) {
+        (Some(port), _) => {
+            let host = Host::bind(port, options.delay, options.rollback).expect("Failed to listen for the other player");
+            println!("Waiting for the other player on port {}", host.port());
+            Some(host.accept().expect("Failed to accept the other player"))
+        }
+        (None, Some(address)) => Some(Connection::join(address.as_str()).expect("Failed to connect to the other player")),
+        (None, None) => None,
+    };
 
     // Create a window
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let window = video_subsystem
-        .window("Rust Chip 8 Emulator", WINDOW_WIDTH, WINDOW_HEIGHT)
+        .window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT)
         .position_centered()
+        .resizable()
         .opengl()
         .build().unwrap();
     // Canvas for drawing graphics
@@ -36,84 +94,331 @@ fn main() {
     canvas.clear();
     canvas.present();
 
-    let mut emu = Emulator::new();
-    // Open ROM from arguments, read it and load into emulator
-    let mut rom = File::open(&args[1]).expect("Failed to open the file");
-    let mut game_buffer = Vec::new();
-    rom.read_to_end(&mut game_buffer).unwrap();
-    emu.load_data(&game_buffer);
+    // Display scaled and streamed into the window every frame
+    let texture_creator = canvas.texture_creator();
+    let mut screen = Screen::new(&texture_creator);
 
-    let timer_duration = Duration::from_nanos(1_000_000_000 / 60); // 60Hz
-    let mut last_timer_update = Instant::now();
+    let mut scheduler = Scheduler::new();
+    let mut overlay = Overlay::new();
+    let mut recorder: Option<Recorder> = None;
+
+    // Game from arguments starts right away, otherwise there's a list to choose from
+    let mut mode = match rom_path {
+        Some(path) => {
+            let rom = fs::read(path).expect("Failed to open the file");
+            let mut session = start_game(rom, Path::new(path), &options, movie.as_ref(), &mut screen, &mut canvas, &mut overlay);
+            if let Some(connection) = connection {
+                session.connect(connection, &mut overlay).unwrap_or_else(|err| panic!("Netplay failed: {}", err));
+            }
+            Mode::Game(session)
+        }
+        None => match Launcher::scan(&texture_creator, &games_dir) {
+            Ok(launcher) => Mode::Launcher(launcher),
+            Err(err) => {
+                println!("Can't open games in {}: {}\n\n{}", games_dir.display(), err, USAGE);
+                return;
+            }
+        },
+    };
+
+    // Debugger may attach any time, the game runs normally until it does
+    let mut gdb = options.gdb.map(|port| {
+        let server = GdbServer::bind(port).expect("Failed to listen for GDB");
+        println!("Waiting for GDB on port {}", server.port());
+        server
+    });
 
     let mut event_pump = sdl_context.event_pump().unwrap();
+    // Set when a script quits
+    let mut exit_code = None;
     // The entire program loop
     'programLoop: loop {
+        // Game picked in the launcher or dropped on the window, or a request to go back to the launcher
+        let mut chosen = None;
+        let mut back_to_launcher = false;
+
         // Poll events and match them
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit{..} | Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
+                Event::Quit{..} => {
                     break 'programLoop;
                 },
-                Event::KeyDown {keycode: Some(key), ..} => {
+                // Escape closes the cheat panel first
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => match &mut mode {
+                    Mode::Game(session) if session.cheats.open => {
+                        session.cheats.toggle();
+                        continue;
+                    }
+                    _ => break 'programLoop,
+                },
+                Event::KeyDown {keycode: Some(Keycode::F11), ..} => {
+                    toggle_fullscreen(&mut canvas);
+                    continue;
+                },
+                // Released in any mode, Tab could be let go after going back to the launcher
+                Event::KeyUp {keycode: Some(Keycode::Tab), ..} => {
+                    scheduler.set_turbo(false);
+                    continue;
+                },
+                // Dropped ROM replaces whatever is in the window
+                Event::DropFile {filename, ..} => {
+                    chosen = Some(PathBuf::from(filename));
+                    continue;
+                },
+                _ => ()
+            }
+
+            let session = match &mut mode {
+                Mode::Game(session) => session,
+                Mode::Launcher(launcher) => {
+                    chosen = launcher.handle(&event, &canvas).or(chosen);
+                    continue;
+                }
+            };
+            // Cheat panel takes typing while it's open
+            if session.cheats.handle(&event, &mut session.emu) {
+                continue;
+            }
+            match event {
+                Event::KeyDown {keycode: Some(Keycode::F1), repeat: false, ..} => {
+                    back_to_launcher = true;
+                },
+                // Cheats would change only this player's game
+                Event::KeyDown {keycode: Some(Keycode::F3), repeat: false, ..} => {
+                    if session.is_online() {
+                        overlay.notify("Cheats are off during netplay");
+                    } else {
+                        session.cheats.toggle();
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::F4), repeat: false, ..} => match &session.challenges {
+                    Some(challenges) => challenges.show_leaderboard(&mut overlay),
+                    None => overlay.notify("No challenges for this game"),
+                },
+                Event::KeyDown {keycode: Some(Keycode::F9), ..} => {
+                    screen.scaler.filter = screen.scaler.filter.next();
+                },
+                Event::KeyDown {keycode: Some(Keycode::F8), ..} => {
+                    screen.scaler.scanlines = !screen.scaler.scanlines;
+                },
+                Event::KeyDown {keycode: Some(Keycode::F7), ..} => {
+                    screen.next_mode();
+                },
+                Event::KeyDown {keycode: Some(Keycode::F12), ..} => {
+                    let (image, width, height) = screen.image();
+                    match recorder::screenshot(image, width, height, screen.window_scale(&canvas)) {
+                        Ok(path) => overlay.notify(format!("Saved screenshot to {}", path.display())),
+                        Err(err) => overlay.notify(format!("Failed to save screenshot: {}", err)),
+                    }
+                },
+                // Shift + F10 records raw frames for ffmpeg instead of GIF
+                Event::KeyDown {keycode: Some(Keycode::F10), keymod, ..} => {
+                    recorder = match recorder.take() {
+                        Some(active) => {
+                            match active.finish() {
+                                Ok(path) => overlay.notify(format!("Saved recording to {}", path.display())),
+                                Err(err) => overlay.notify(format!("Failed to save recording: {}", err)),
+                            }
+                            None
+                        }
+                        None => {
+                            let format = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                                VideoFormat::Raw
+                            } else {
+                                VideoFormat::Gif
+                            };
+                            let (_, width, height) = screen.image();
+                            Recorder::start(format, width, height)
+                                .map_err(|err| overlay.notify(format!("Failed to start recording: {}", err)))
+                                .ok()
+                        }
+                    };
+                },
+                Event::KeyDown {keycode: Some(Keycode::P), repeat: false, ..} => {
+                    scheduler.toggle_pause();
+                },
+                Event::KeyDown {keycode: Some(Keycode::N), ..} => {
+                    scheduler.advance();
+                },
+                Event::KeyDown {keycode: Some(Keycode::Minus), ..} => {
+                    scheduler.slower();
+                },
+                Event::KeyDown {keycode: Some(Keycode::Equals), ..} => {
+                    scheduler.faster();
+                },
+                Event::KeyDown {keycode: Some(Keycode::Tab), ..} => {
+                    scheduler.set_turbo(true);
+                },
+                Event::KeyDown {keycode: Some(key), repeat: false, ..} if session.accepts_keys() => {
                     if let Some(btn) = key_to_button(key) {
-                        emu.keypress(btn, true);
+                        session.press_key(btn, true);
                     }
                 },
-                Event::KeyUp {keycode: Some(key), ..} => {
+                Event::KeyUp {keycode: Some(key), ..} if session.accepts_keys() => {
                     if let Some(btn) = key_to_button(key) {
-                        emu.keypress(btn, false);
+                        session.press_key(btn, false);
                     }
                 },
                 _ => ()
             }
         }
 
-        let cycle_start = Instant::now();
-        for _ in 0..TICKS_PER_FRAME {
-            emu.tick();
+        if let Some(path) = chosen {
+            match fs::read(&path) {
+                Ok(rom) => {
+                    scheduler = Scheduler::new();
+                    let session = start_game(rom, &path, &options, movie.as_ref(), &mut screen, &mut canvas, &mut overlay);
+                    if let Mode::Game(previous) = std::mem::replace(&mut mode, Mode::Game(session)) {
+                        close_game(previous, gdb.as_mut(), &options, &mut overlay);
+                    }
+                }
+                Err(err) => overlay.notify(format!("Failed to open {}: {}", path.display(), err)),
+            }
+        }
+        // Directory is scanned again, so new games show up
+        if back_to_launcher {
+            match Launcher::scan(&texture_creator, &games_dir) {
+                Ok(launcher) => {
+                    // Pause, speed and turbo of the game don't apply to the previews
+                    scheduler = Scheduler::new();
+                    if let Mode::Game(session) = std::mem::replace(&mut mode, Mode::Launcher(launcher)) {
+                        close_game(session, gdb.as_mut(), &options, &mut overlay);
+                    }
+                    canvas.window_mut().set_title(WINDOW_TITLE).unwrap();
+                }
+                Err(err) => overlay.notify(format!("Can't open games in {}: {}", games_dir.display(), err)),
+            }
         }
 
-        if cycle_start.duration_since(last_timer_update) >= timer_duration {
-            emu.time_tick();
-            last_timer_update = cycle_start;
+        // Run as many frames as the scheduler asks for, but don't freeze the window
+        let started = Instant::now();
+        let due = scheduler.frames_due(started);
+        match &mut mode {
+            Mode::Game(session) => {
+                session.check_watch(&options, &mut overlay);
+                if let Some(event) = gdb.as_mut().and_then(|gdb| gdb.poll(&mut session.emu)) {
+                    overlay.notify(event.to_string());
+                }
+
+                for _ in 0..due {
+                    // Stop right after the frame with a new memory issue
+                    if session.run_frame(gdb.as_mut(), &mut overlay) {
+                        scheduler.pause();
+                        break;
+                    }
+
+                    // Record every emulated frame, stop if disk refuses to take more
+                    if let Some(active) = recorder.as_mut() {
+                        screen.update(&session.emu);
+                        let (image, width, height) = screen.image();
+                        match active.add_frame(image, width, height, session.emu.sound_active()) {
+                            Ok(Some(path)) => overlay.notify(format!(
+                                "Screen size changed, saved recording to {} and started a new one", path.display()
+                            )),
+                            Ok(None) => (),
+                            Err(err) => {
+                                overlay.notify(format!("Recording stopped: {}", err));
+                                // Keep what was recorded so far playable
+                                let _ = recorder.take().map(Recorder::finish);
+                            }
+                        }
+                    }
+
+                    if started.elapsed() >= FAST_FORWARD_BUDGET {
+                        break;
+                    }
+                }
+
+                // Draw results, vsync paces the loop
+                screen.update(&session.emu);
+                screen.draw(&mut canvas);
+                if let Some(script) = &session.script {
+                    script.draw(&mut canvas);
+                }
+                session.cheats.draw(&mut canvas, &session.emu);
+                overlay.status = scheduler.status();
+
+                // Bots playing regression tests end the program with their result
+                exit_code = session.script.as_ref().and_then(script::Script::exit_code);
+                if exit_code.is_some() {
+                    break 'programLoop;
+                }
+            }
+            Mode::Launcher(launcher) => {
+                for _ in 0..due {
+                    launcher.update();
+                    if started.elapsed() >= FAST_FORWARD_BUDGET {
+                        break;
+                    }
+                }
+                launcher.draw(&mut canvas);
+                overlay.status.clear();
+            }
         }
-        // Continue emulation and draw results
-        draw_display(&emu, &mut canvas);
+        overlay.draw(&mut canvas);
+        canvas.present();
 
-        let elapsed = cycle_start.elapsed();
-        if elapsed < CYCLE_DURATION {
-            std::thread::sleep(CYCLE_DURATION - elapsed);
+        // Don't spin when vsync isn't available
+        if due == 0 {
+            std::thread::sleep(Duration::from_millis(1));
         }
     }
+
+    // Don't leave unfinished recordings behind
+    if let Some(active) = recorder {
+        if let Ok(path) = active.finish() {
+            println!("Saved recording to {}", path.display());
+        }
+    }
+    if let Mode::Game(session) = mode {
+        session.finish(&options);
+    }
+    if let Some(code) = exit_code {
+        std::process::exit(code);
+    }
 }
 
 
-// Draw the entire frame from emulator to screen canvas
-fn draw_display(emu: &Emulator, canvas: &mut Canvas<Window>) {
-    // Clear display with black color
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+// Load the game and show what the database knows about it
+fn start_game(rom: Vec<u8>, path: &Path, options: &Options, movie: Option<&Movie>, screen: &mut Screen,
+              canvas: &mut Canvas<Window>, overlay: &mut Overlay) -> Box<Session> {
+    let session = Session::start(rom, path, options, movie, overlay);
+    let title = match session.game {
+        Some(game) => game.title.clone(),
+        None => path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+    };
+    canvas.window_mut().set_title(&format!("{} - {}", WINDOW_TITLE, title)).unwrap();
+    screen.palette = session.game.and_then(romdb::Game::palette).unwrap_or_default();
+    Box::new(session)
+}
 
-    // Draw every white pixel on the screen
-    let display = emu.get_display();
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
-    for (number, pixel) in display.iter().enumerate() {
-        if *pixel {
-            // Convert 1D array into 2D (x, y)
-            let x = (number % SCREEN_WIDTH) as u32;
-            let y = (number / SCREEN_WIDTH) as u32;
-
-            // Draw a rectangle at position (x, y) with size of (scale, scale)
-            let rect = Rect::new(
-                (x * SCREEN_SCALE) as i32,
-                (y * SCREEN_SCALE) as i32,
-                SCREEN_SCALE, SCREEN_SCALE
-            );
-            canvas.fill_rect(rect).unwrap()
-        }
+// Write reports of the game, a debugger attached to it is let go so it can't hold the next one stopped
+fn close_game(mut session: Box<Session>, gdb: Option<&mut GdbServer<Guard<Memory>>>, options: &Options,
+              overlay: &mut Overlay) {
+    if let Some(event) = gdb.and_then(|gdb| gdb.detach(&mut session.emu, "the game was closed")) {
+        overlay.notify(event.to_string());
     }
-    canvas.present();
+    session.finish(options);
+}
+
+// Launcher lists the directory given instead of a game, or the one the game is in.
+// Without either it's games of the repository, run from its root or one of the crates
+fn games_dir(rom: Option<&str>) -> PathBuf {
+    match rom.map(Path::new) {
+        Some(path) if path.is_dir() => path.to_path_buf(),
+        Some(path) => match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        },
+        None if Path::new("games").is_dir() => PathBuf::from("games"),
+        None => PathBuf::from("../games"),
+    }
+}
+
+// Keyboard key of Chip8 button, the opposite of key_to_button
+fn button_name(button: usize) -> &'static str {
+    const NAMES: [&str; 16] = ["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V"];
+    NAMES[button & 0xF]
 }
 
 // Convert key to Chip8 button
@@ -0,0 +1,151 @@
+use std::time::{Duration, Instant};
+use sdl2::{pixels::Color, rect::Rect, render::{BlendMode, Canvas}, video::Window};
+
+// How long notifications stay on the screen
+const NOTIFICATION_TIME: Duration = Duration::from_secs(3);
+// Size of one font pixel in window pixels
+const TEXT_SCALE: u32 = 3;
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+const MARGIN: i32 = 8;
+// Distance between two lines of text
+pub const LINE_HEIGHT: i32 = ((GLYPH_HEIGHT + 2) * TEXT_SCALE) as i32;
+
+
+// Text drawn over the game: status in the top right corner, notifications at the bottom
+pub struct Overlay {
+    pub status: String,
+    notifications: Vec<(String, Instant)>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self { status: String::new(), notifications: Vec::new() }
+    }
+
+    // Show message for a few seconds, it's also printed to the console
+    pub fn notify(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        println!("{}", message);
+        self.notifications.push((message, Instant::now()));
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>) {
+        let (window_width, window_height) = canvas.output_size().unwrap();
+
+        if !self.status.is_empty() {
+            let x = window_width as i32 - text_width(&self.status) as i32 - MARGIN;
+            draw_text(canvas, &self.status, x, MARGIN);
+        }
+
+        // Newest notification at the bottom
+        self.notifications.retain(|(_, shown)| shown.elapsed() < NOTIFICATION_TIME);
+        let mut y = window_height as i32 - MARGIN - LINE_HEIGHT * self.notifications.len() as i32;
+        for (message, _) in &self.notifications {
+            draw_text(canvas, message, MARGIN, y);
+            y += LINE_HEIGHT;
+        }
+    }
+}
+
+// Width of text in window pixels
+pub fn text_width(text: &str) -> u32 {
+    text.chars().count() as u32 * (GLYPH_WIDTH + 1) * TEXT_SCALE
+}
+
+// Draw text on a dark box, so it's readable over any game
+pub fn draw_text(canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32) {
+    let padding = TEXT_SCALE as i32;
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 180));
+    canvas.fill_rect(Rect::new(
+        x - padding, y - padding,
+        text_width(text) + padding as u32, GLYPH_HEIGHT * TEXT_SCALE + 2 * padding as u32
+    )).unwrap();
+
+    // Collect every lit font pixel and draw them in a single call
+    let mut rects = Vec::new();
+    for (number, character) in text.chars().enumerate() {
+        let glyph = glyph(character);
+        let glyph_x = x + (number as u32 * (GLYPH_WIDTH + 1) * TEXT_SCALE) as i32;
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) != 0 {
+                    rects.push(Rect::new(
+                        glyph_x + (column * TEXT_SCALE) as i32,
+                        y + (row as u32 * TEXT_SCALE) as i32,
+                        TEXT_SCALE, TEXT_SCALE
+                    ));
+                }
+            }
+        }
+    }
+
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    canvas.fill_rects(&rects).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+}
+
+// 3x5 pixel font, every row is three bits from left to right
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
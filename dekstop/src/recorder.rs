@@ -0,0 +1,228 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use chip8_core::filter::nearest;
+
+// Directory all screenshots and recordings land in
+const OUTPUT_DIR: &str = "recordings";
+
+const SAMPLE_RATE: u32 = 44_100;
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / 60;
+const BEEP_FREQUENCY: u32 = 440;
+const BEEP_VOLUME: i16 = 8_000;
+
+
+// Format of recorded video
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoFormat {
+    // Animated GIF, ready to attach to bug reports
+    Gif,
+    // Raw RGBA frames in one file, to be encoded by ffmpeg
+    Raw,
+}
+
+enum VideoWriter {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Raw(BufWriter<File>),
+}
+
+// Recording of frames along with beeper's sound
+pub struct Recorder {
+    format: VideoFormat,
+    video: VideoWriter,
+    video_path: PathBuf,
+    audio: WavWriter,
+    width: usize,
+    height: usize,
+    frames: u64,
+    // Position of the beeper's square wave, keeps it continuous between frames
+    phase: u32,
+}
+
+impl Recorder {
+    // Begin recording frames of given size
+    pub fn start(format: VideoFormat, width: usize, height: usize) -> io::Result<Self> {
+        let base = output_path("recording");
+        let video_path = base.with_extension(match format {
+            VideoFormat::Gif => "gif",
+            VideoFormat::Raw => "rgba",
+        });
+
+        let file = BufWriter::new(File::create(&video_path)?);
+        let video = match format {
+            VideoFormat::Gif => {
+                let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[])
+                    .map_err(io::Error::other)?;
+                encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+                VideoWriter::Gif(encoder)
+            }
+            VideoFormat::Raw => VideoWriter::Raw(file),
+        };
+
+        Ok(Self {
+            format,
+            video,
+            video_path,
+            audio: WavWriter::create(&base.with_extension("wav"))?,
+            width,
+            height,
+            frames: 0,
+            phase: 0,
+        })
+    }
+
+    // Add one 60Hz frame. When the size changes, like when the game switches to hi-res, the recording
+    // so far is finished and a new one continues at the new size, its video path is returned
+    pub fn add_frame(&mut self, rgba: &[u8], width: usize, height: usize, beeping: bool)
+                     -> io::Result<Option<PathBuf>> {
+        let mut finished = None;
+        if width != self.width || height != self.height {
+            let next = Recorder::start(self.format, width, height)?;
+            finished = Some(mem::replace(self, next).finish()?);
+        }
+
+        match &mut self.video {
+            VideoWriter::Gif(encoder) => {
+                let mut frame = indexed_frame(rgba, width as u16, height as u16);
+                // GIF delays are in hundredths of second, spread them to average 60 FPS
+                frame.delay = ((self.frames + 1) * 100 / 60 - self.frames * 100 / 60) as u16;
+                encoder.write_frame(&frame).map_err(io::Error::other)?;
+            }
+            VideoWriter::Raw(file) => file.write_all(rgba)?,
+        }
+        self.frames += 1;
+
+        // Square wave while the sound timer is active, silence otherwise
+        let half_period = SAMPLE_RATE / BEEP_FREQUENCY / 2;
+        for _ in 0..SAMPLES_PER_FRAME {
+            let sample = if !beeping {
+                0
+            }
+            else if (self.phase / half_period) & 1 == 0 {
+                BEEP_VOLUME
+            }
+            else {
+                -BEEP_VOLUME
+            };
+            self.phase = self.phase.wrapping_add(1);
+            self.audio.write_sample(sample)?;
+        }
+
+        Ok(finished)
+    }
+
+    // Finish both files, return path of the video
+    pub fn finish(self) -> io::Result<PathBuf> {
+        match self.video {
+            VideoWriter::Gif(encoder) => {
+                encoder.into_inner().map_err(io::Error::other)?.flush()?;
+            }
+            VideoWriter::Raw(mut file) => {
+                file.flush()?;
+                println!(
+                    "Encode with: ffmpeg -f rawvideo -pixel_format rgba -video_size {}x{} -framerate 60 -i {} -i {} out.mp4",
+                    self.width, self.height,
+                    self.video_path.display(),
+                    self.video_path.with_extension("wav").display()
+                );
+            }
+        }
+        self.audio.finish()?;
+
+        Ok(self.video_path)
+    }
+}
+
+// Save RGBA image scaled up by a whole factor as PNG, return its path
+pub fn screenshot(rgba: &[u8], width: usize, height: usize, scale: usize) -> io::Result<PathBuf> {
+    let scale = scale.max(1);
+    let mut scaled = vec![0; rgba.len() * scale * scale];
+    nearest(rgba, width, height, scale, &mut scaled);
+
+    let path = output_path("screenshot").with_extension("png");
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(&path)?), (width * scale) as u32, (height * scale) as u32
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&scaled).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+
+    Ok(path)
+}
+
+// Unique path without extension inside of output directory
+fn output_path(kind: &str) -> PathBuf {
+    let _ = fs::create_dir_all(OUTPUT_DIR);
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    Path::new(OUTPUT_DIR).join(format!("chip8-{}-{}-{:03}", kind, time.as_secs(), time.subsec_millis()))
+}
+
+// Build GIF frame with its own palette, quantize only if there are too many colors
+fn indexed_frame(rgba: &[u8], width: u16, height: u16) -> gif::Frame<'static> {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut indices = Vec::with_capacity(rgba.len() / 4);
+
+    for pixel in rgba.chunks_exact(4) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match palette.iter().position(|known| *known == color) {
+            Some(index) => index,
+            None if palette.len() < 256 => {
+                palette.push(color);
+                palette.len() - 1
+            }
+            None => return gif::Frame::from_rgba_speed(width, height, &mut rgba.to_vec(), 10),
+        };
+        indices.push(index as u8);
+    }
+
+    gif::Frame::from_palette_pixels(width, height, indices, palette.concat(), None)
+}
+
+// Mono 16-bit PCM WAV file, sizes in header are filled in when finished
+struct WavWriter {
+    file: BufWriter<File>,
+    samples: u32,
+}
+
+impl WavWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // PCM, one channel
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        // Block align and bits per sample
+        file.write_all(&2u16.to_le_bytes())?;
+        file.write_all(&16u16.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self { file, samples: 0 })
+    }
+
+    fn write_sample(&mut self, sample: i16) -> io::Result<()> {
+        self.samples += 1;
+        self.file.write_all(&sample.to_le_bytes())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let data_size = self.samples * 2;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(36 + data_size).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&data_size.to_le_bytes())?;
+        self.file.flush()
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+// Speed multipliers user can switch between, past the last one emulation is unlimited
+const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 1.5, 2.0, 4.0, 8.0];
+const NORMAL_SPEED: usize = 2;
+// Frames owed after a stall are dropped past this, so the game doesn't jump forward
+const MAX_BACKLOG: f64 = 4.0;
+// Longest time emulation may take between two presented frames while fast-forwarding
+pub const FAST_FORWARD_BUDGET: Duration = Duration::from_millis(14);
+
+
+// Decides how many 60Hz frames to emulate, independently of display's refresh rate
+pub struct Scheduler {
+    speed: usize,
+    paused: bool,
+    // Fast-forward while turbo key is held
+    turbo: bool,
+    // Frames requested by frame advance
+    steps: u32,
+    // Emulated frames owed since last update, fractional at slow speeds
+    backlog: f64,
+    last_update: Instant,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            speed: NORMAL_SPEED,
+            paused: false,
+            turbo: false,
+            steps: 0,
+            backlog: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len());
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.backlog = 0.0;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.backlog = 0.0;
+    }
+
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+    }
+
+    // Run exactly one more frame, pausing emulation if it's running
+    pub fn advance(&mut self) {
+        self.paused = true;
+        self.steps += 1;
+    }
+
+    // Check if emulation should run as fast as possible
+    pub fn unlimited(&self) -> bool {
+        !self.paused && (self.turbo || self.speed == SPEEDS.len())
+    }
+
+    // Number of frames to emulate now, unlimited speed returns u32::MAX and relies on time budget
+    pub fn frames_due(&mut self, now: Instant) -> u32 {
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        self.last_update = now;
+
+        if self.paused {
+            return std::mem::take(&mut self.steps);
+        }
+        if self.unlimited() {
+            return u32::MAX;
+        }
+
+        self.backlog = (self.backlog + elapsed * 60.0 * SPEEDS[self.speed]).min(MAX_BACKLOG);
+        let due = self.backlog.floor();
+        self.backlog -= due;
+        due as u32
+    }
+
+    // Short description of current speed for on-screen display, empty at normal speed
+    pub fn status(&self) -> String {
+        if self.paused {
+            "PAUSED".to_string()
+        }
+        else if self.unlimited() {
+            ">>".to_string()
+        }
+        else if self.speed == NORMAL_SPEED {
+            String::new()
+        }
+        else {
+            format!("{}X", SPEEDS[self.speed])
+        }
+    }
+}
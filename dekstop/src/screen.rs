@@ -0,0 +1,113 @@
+use chip8_core::*;
+use chip8_core::bus::Bus;
+use chip8_core::filter::{fit, ScaleMode, Scaler, MAX_OUTPUT_SIZE};
+use sdl2::{pixels::{Color, PixelFormatEnum}, rect::Rect, render::{Canvas, Texture, TextureCreator},
+           video::{FullscreenType, Window, WindowContext}};
+
+
+// Everything needed to present emulator's display inside of the window
+pub struct Screen<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: Texture<'a>,
+    // Scale of the last updated frame, changes along with filter
+    factor: usize,
+
+    pub scaler: Scaler,
+    pub mode: ScaleMode,
+    pub palette: Palette,
+
+    frame: [u8; RGBA_SIZE],
+    scaled: Vec<u8>,
+}
+
+impl<'a> Screen<'a> {
+    // Create screen with texture for unfiltered display
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let scaler = Scaler::default();
+        Self {
+            texture_creator,
+            texture: Self::create_texture(texture_creator, scaler.factor()),
+            factor: scaler.factor(),
+            scaler,
+            mode: ScaleMode::Integer,
+            palette: Palette::default(),
+            frame: [0; RGBA_SIZE],
+            scaled: vec![0; MAX_OUTPUT_SIZE],
+        }
+    }
+
+    // Streaming texture big enough for display scaled by factor
+    fn create_texture(texture_creator: &'a TextureCreator<WindowContext>, factor: usize) -> Texture<'a> {
+        texture_creator
+            .create_texture_streaming(
+                PixelFormatEnum::RGBA32,
+                (SCREEN_WIDTH * factor) as u32, (SCREEN_HEIGHT * factor) as u32
+            )
+            .unwrap()
+    }
+
+    // Cycle through scaling modes
+    pub fn next_mode(&mut self) {
+        self.mode = match self.mode {
+            ScaleMode::Integer => ScaleMode::Aspect,
+            ScaleMode::Aspect => ScaleMode::Stretch,
+            ScaleMode::Stretch => ScaleMode::Integer,
+        };
+    }
+
+    // Filtered display from the last drawn frame, along with its width and height
+    pub fn image(&self) -> (&[u8], usize, usize) {
+        let (width, height) = (SCREEN_WIDTH * self.factor, SCREEN_HEIGHT * self.factor);
+        (&self.scaled[..width * height * 4], width, height)
+    }
+
+    // Whole number of times the image currently fits in the window
+    pub fn window_scale(&self, canvas: &Canvas<Window>) -> usize {
+        let (window_width, _) = canvas.output_size().unwrap();
+        let (_, width, _) = self.image();
+        (window_width as usize / width).max(1)
+    }
+
+    // Convert display into filtered pixels, done for every emulated frame
+    pub fn update(&mut self, emu: &Emulator<impl Bus>) {
+        self.factor = self.scaler.factor();
+        emu.write_rgba(&mut self.frame, &self.palette);
+        let size = self.scaler.output_size(SCREEN_WIDTH, SCREEN_HEIGHT);
+        self.scaler.apply(&self.frame, SCREEN_WIDTH, SCREEN_HEIGHT, &mut self.scaled[..size]);
+    }
+
+    // Draw the last updated frame to screen canvas
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>) {
+        // Recreate texture if filter changed its size
+        let (_, width, height) = self.image();
+        let query = self.texture.query();
+        if query.width as usize != width || query.height as usize != height {
+            self.texture = Self::create_texture(self.texture_creator, self.factor);
+        }
+
+        // Upload all pixels at once
+        self.texture.update(None, &self.scaled[..width * height * 4], width * 4).unwrap();
+
+        // Letterbox the image inside of the window
+        let (window_width, window_height) = canvas.output_size().unwrap();
+        let viewport = fit(
+            SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, window_width, window_height, self.mode
+        );
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        canvas.copy(
+            &self.texture, None,
+            Rect::new(viewport.x as i32, viewport.y as i32, viewport.width, viewport.height)
+        ).unwrap();
+    }
+}
+
+// Switch between windowed and fullscreen mode
+pub fn toggle_fullscreen(canvas: &mut Canvas<Window>) {
+    let window = canvas.window_mut();
+    let new_state = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    window.set_fullscreen(new_state).unwrap();
+}
@@ -0,0 +1,283 @@
+// Rhai scripts given with --script: bots, HUDs and tools for ROM developers. The script runs once when
+// the game starts and registers callbacks with on_frame, which run after every frame:
+//
+// on_frame(|| {
+//     text(8, 8, `lines ${peek(0x3F0)}`);
+//     if frame() % 30 == 0 { press(5) } else { release(5) }
+// });
+//
+// Functions: peek(address), poke(address, value), reg(n), set_reg(n, value), i_reg(), set_i_reg(value),
+// pc(), set_pc(address), delay_timer(), sound_timer(), frame(), pixel(x, y), press(key), release(key),
+// step(frames), on_frame(callback), text(x, y, message), notify(message), quit(code)
+//
+// A run of the script or of the callbacks can step at most MAX_RUN_FRAMES and is stopped after
+// MAX_OPERATIONS, so a stuck script fails instead of freezing the window.
+use std::cell::RefCell;
+use std::fs;
+use std::mem;
+use std::rc::Rc;
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, AST};
+use sdl2::{render::Canvas, video::Window};
+use chip8_core::{ADDR_MASK, SCREEN_HEIGHT, SCREEN_WIDTH};
+use chip8_core::bus::Memory;
+use chip8_core::guard::Guard;
+use crate::overlay::{draw_text, Overlay};
+use crate::session::Chip8;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// Frames step can run during one run of the script or of the callbacks, ten seconds of the game
+const MAX_RUN_FRAMES: i64 = 600;
+// Limits of one run of the script or of a callback
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+
+
+// Everything functions of the script work with
+struct State {
+    // The game's emulator is swapped in here while the script runs
+    emu: Chip8,
+    ticks_per_frame: usize,
+    // Frames step may still run before the current run fails
+    frames_left: i64,
+    callbacks: Vec<FnPtr>,
+    // Text drawn over the game until the next frame
+    texts: Vec<(i32, i32, String)>,
+    notifications: Vec<String>,
+    exit_code: Option<i32>,
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    state: Rc<RefCell<State>>,
+}
+
+impl Script {
+    // Compile the script and run it once on the freshly loaded game
+    pub fn start(path: &str, emu: &mut Chip8, ticks_per_frame: usize, overlay: &mut Overlay) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path, err))?;
+        Self::from_source(&source, emu, ticks_per_frame, overlay).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    fn from_source(source: &str, emu: &mut Chip8, ticks_per_frame: usize, overlay: &mut Overlay) -> Result<Self, String> {
+        let state = Rc::new(RefCell::new(State {
+            emu: Chip8::with_bus(Guard::new(Memory::new()), 0),
+            ticks_per_frame,
+            frames_left: MAX_RUN_FRAMES,
+            callbacks: Vec::new(),
+            texts: Vec::new(),
+            notifications: Vec::new(),
+            exit_code: None,
+        }));
+        let engine = create_engine(&state);
+        let ast = engine.compile(source).map_err(|err| err.to_string())?;
+
+        let script = Self { engine, ast, state };
+        script.with_emulator(emu, overlay, |script| script.engine.run_ast(&script.ast))?;
+        Ok(script)
+    }
+
+    // Call every callback after a frame of the game
+    pub fn run_frame(&mut self, emu: &mut Chip8, overlay: &mut Overlay) -> Result<(), String> {
+        let callbacks = {
+            let mut state = self.state.borrow_mut();
+            state.texts.clear();
+            state.callbacks.clone()
+        };
+        self.with_emulator(emu, overlay, |script| {
+            // Whatever callbacks return is ignored
+            for callback in &callbacks {
+                let _ = callback.call::<Dynamic>(&script.engine, &script.ast, ())?;
+            }
+            Ok(())
+        })
+    }
+
+    // Code given by quit(), the program should end with it
+    pub fn exit_code(&self) -> Option<i32> {
+        self.state.borrow().exit_code
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>) {
+        for (x, y, text) in &self.state.borrow().texts {
+            draw_text(canvas, text, *x, *y);
+        }
+    }
+
+    // Lend the emulator to functions of the script, which can't borrow it from the session
+    fn with_emulator(&self, emu: &mut Chip8, overlay: &mut Overlay,
+                     run: impl FnOnce(&Self) -> ScriptResult<()>) -> Result<(), String> {
+        {
+            let mut state = self.state.borrow_mut();
+            mem::swap(emu, &mut state.emu);
+            state.frames_left = MAX_RUN_FRAMES;
+        }
+        let result = run(self);
+        let mut state = self.state.borrow_mut();
+        mem::swap(emu, &mut state.emu);
+
+        for message in state.notifications.drain(..) {
+            overlay.notify(message);
+        }
+        result.map_err(|err| err.to_string())
+    }
+}
+
+fn create_engine(state: &Rc<RefCell<State>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.on_print(|text| println!("{}", text));
+
+    let shared = state.clone();
+    engine.register_fn("peek", move |address: i64| -> i64 {
+        shared.borrow().emu.ram()[address as usize & ADDR_MASK as usize] as i64
+    });
+    let shared = state.clone();
+    engine.register_fn("poke", move |address: i64, value: i64| {
+        shared.borrow_mut().emu.ram_mut()[address as usize & ADDR_MASK as usize] = value as u8;
+    });
+    let shared = state.clone();
+    engine.register_fn("reg", move |index: i64| -> ScriptResult<i64> {
+        Ok(shared.borrow().emu.v_regs()[register(index)?] as i64)
+    });
+    let shared = state.clone();
+    engine.register_fn("set_reg", move |index: i64, value: i64| -> ScriptResult<()> {
+        shared.borrow_mut().emu.v_regs_mut()[register(index)?] = value as u8;
+        Ok(())
+    });
+    let shared = state.clone();
+    engine.register_fn("i_reg", move || shared.borrow().emu.i_reg() as i64);
+    let shared = state.clone();
+    engine.register_fn("set_i_reg", move |value: i64| shared.borrow_mut().emu.set_i_reg(value as u16));
+    let shared = state.clone();
+    engine.register_fn("pc", move || shared.borrow().emu.pc() as i64);
+    let shared = state.clone();
+    engine.register_fn("set_pc", move |address: i64| shared.borrow_mut().emu.set_pc(address as u16));
+    let shared = state.clone();
+    engine.register_fn("delay_timer", move || shared.borrow().emu.delay_timer() as i64);
+    let shared = state.clone();
+    engine.register_fn("sound_timer", move || shared.borrow().emu.sound_timer() as i64);
+    let shared = state.clone();
+    engine.register_fn("frame", move || shared.borrow().emu.frame() as i64);
+    let shared = state.clone();
+    engine.register_fn("pixel", move |x: i64, y: i64| {
+        let (x, y) = (x.rem_euclid(SCREEN_WIDTH as i64) as usize, y.rem_euclid(SCREEN_HEIGHT as i64) as usize);
+        shared.borrow().emu.get_display()[y * SCREEN_WIDTH + x]
+    });
+
+    // Keys and frames
+    let shared = state.clone();
+    engine.register_fn("press", move |key: i64| -> ScriptResult<()> {
+        shared.borrow_mut().emu.keypress(button(key)?, true);
+        Ok(())
+    });
+    let shared = state.clone();
+    engine.register_fn("release", move |key: i64| -> ScriptResult<()> {
+        shared.borrow_mut().emu.keypress(button(key)?, false);
+        Ok(())
+    });
+    let shared = state.clone();
+    engine.register_fn("step", move |frames: i64| -> ScriptResult<()> {
+        let mut state = shared.borrow_mut();
+        if frames > state.frames_left {
+            return Err(format!("Step can run at most {} frames per run", MAX_RUN_FRAMES).into());
+        }
+        state.frames_left -= frames.max(0);
+        let ticks = state.ticks_per_frame;
+        for _ in 0..frames.max(0) {
+            state.emu.run_frame(ticks);
+        }
+        Ok(())
+    });
+    let shared = state.clone();
+    engine.register_fn("on_frame", move |callback: FnPtr| shared.borrow_mut().callbacks.push(callback));
+
+    // Output
+    let shared = state.clone();
+    engine.register_fn("text", move |x: i64, y: i64, text: &str| {
+        shared.borrow_mut().texts.push((x as i32, y as i32, text.to_string()));
+    });
+    let shared = state.clone();
+    engine.register_fn("notify", move |message: &str| shared.borrow_mut().notifications.push(message.to_string()));
+    let shared = state.clone();
+    engine.register_fn("quit", move |code: i64| shared.borrow_mut().exit_code = Some(code as i32));
+    engine
+}
+
+fn register(index: i64) -> ScriptResult<usize> {
+    usize::try_from(index).ok().filter(|index| *index < 16)
+        .ok_or_else(|| format!("No register V{}", index).into())
+}
+
+fn button(key: i64) -> ScriptResult<usize> {
+    usize::try_from(key).ok().filter(|key| *key < 16)
+        .ok_or_else(|| format!("No key {}", key).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts frames with key 0 held into V2, one loop per frame of 3 ticks: SKNP V0; ADD V2, 1; JP 0x200
+    const HOLD_COUNTER: [u8; 6] = [0xE0, 0xA1, 0x72, 0x01, 0x12, 0x00];
+
+    fn game() -> Chip8 {
+        let mut emu = Chip8::with_bus(Guard::new(Memory::new()), 0);
+        emu.load_data(&HOLD_COUNTER);
+        emu
+    }
+
+    fn start(source: &str, emu: &mut Chip8) -> Result<Script, String> {
+        Script::from_source(source, emu, 3, &mut Overlay::new())
+    }
+
+    #[test]
+    fn script_presses_keys_and_reads_registers() {
+        let mut emu = game();
+        let script = start("press(0); step(3); release(0); step(3); quit(reg(2))", &mut emu).unwrap();
+        assert_eq!(script.exit_code(), Some(3));
+        assert_eq!(emu.v_regs()[2], 3);
+        assert_eq!(emu.frame(), 6);
+    }
+
+    #[test]
+    fn callbacks_run_after_every_frame() {
+        let mut emu = game();
+        let mut overlay = Overlay::new();
+        let mut script = start("on_frame(|| { set_reg(3, reg(3) + 1); if reg(3) == 2 { quit(7) } })", &mut emu).unwrap();
+        for _ in 0..2 {
+            assert_eq!(script.exit_code(), None);
+            emu.run_frame(3);
+            script.run_frame(&mut emu, &mut overlay).unwrap();
+        }
+        assert_eq!(script.exit_code(), Some(7));
+        assert_eq!(emu.v_regs()[3], 2);
+    }
+
+    #[test]
+    fn frame_budget_is_given_back_every_frame() {
+        let mut emu = game();
+        let mut overlay = Overlay::new();
+        let mut script = start("step(600); on_frame(|| step(600))", &mut emu).unwrap();
+        for _ in 0..3 {
+            script.run_frame(&mut emu, &mut overlay).unwrap();
+        }
+        assert_eq!(emu.frame(), 2400);
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        let mut emu = game();
+        let error = start("step(601)", &mut emu).err().unwrap();
+        assert!(error.contains("at most 600 frames"), "{}", error);
+        let error = start("for i in 0..1000 { step(1) }", &mut emu).err().unwrap();
+        assert!(error.contains("at most 600 frames"), "{}", error);
+        assert!(start("loop {}", &mut emu).is_err());
+        assert!(start("fn down(n) { down(n + 1) } down(0)", &mut emu).is_err());
+        assert!(start("reg(16)", &mut emu).is_err());
+        // The emulator is given back even when the script fails
+        assert_eq!(&emu.ram()[0x200..0x206], HOLD_COUNTER);
+    }
+}
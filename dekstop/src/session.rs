@@ -0,0 +1,282 @@
+// One played game, from loading the ROM to saving its reports, so the launcher
+// can start any number of them in a single run of the program
+use std::fs;
+use std::path::{Path, PathBuf};
+use chip8_core::*;
+use chip8_core::bus::Memory;
+use chip8_core::gdb::GdbServer;
+use chip8_core::guard::{self, Guard};
+use chip8_core::rng::random_seed;
+use chip8_core::profile::Profiler;
+use chip8_core::trace::{TraceFormat, Tracer};
+use chip8_core::movie::{Movie, MoviePlayer, MovieRecorder};
+use chip8_core::netplay::{Connection, Netplay, NetplayError};
+use romdb::Game;
+use crate::challenges::Challenges;
+use crate::cheats::CheatPanel;
+use crate::cli::Options;
+use crate::overlay::Overlay;
+use crate::script::Script;
+use crate::watch::FileWatch;
+
+// Memory is always guarded, reports are only shown with --check-memory
+pub type Chip8 = Emulator<Guard<Memory>>;
+
+
+pub struct Session {
+    pub emu: Chip8,
+    pub game: Option<&'static Game>,
+    pub cheats: CheatPanel,
+    pub challenges: Option<Challenges>,
+    // Given with --script, it runs after every frame
+    pub script: Option<Script>,
+    path: PathBuf,
+    rom: Vec<u8>,
+    // Set with --watch, the ROM gets reloaded when it changes
+    watch: Option<FileWatch>,
+    ticks_per_frame: usize,
+    movie_player: Option<MoviePlayer>,
+    movie_recorder: Option<MovieRecorder>,
+    // Keys of the other player come over the network, frames run when netplay allows
+    netplay: Option<Netplay>,
+    reported_issues: usize,
+}
+
+impl Session {
+    // Set up emulator with everything asked for on command line and load the game
+    pub fn start(rom: Vec<u8>, path: &Path, options: &Options, movie: Option<&Movie>, overlay: &mut Overlay) -> Self {
+        let mut emu = Emulator::with_bus(Guard::new(Memory::new()), options.seed.unwrap_or_else(random_seed));
+        emu.bus_mut().set_enabled(options.check_memory);
+        emu.bus_mut().set_break_on_issue(options.break_on_memory);
+        if let Some(quirks) = options.quirks {
+            emu.set_quirks(quirks);
+        }
+
+        // Trace goes straight into the file while the game runs
+        if let Some(path) = &options.trace {
+            let file = fs::File::create(path).expect("Failed to create the trace");
+            let mut tracer = Tracer::writer(file, options.trace_format.unwrap_or(TraceFormat::Full));
+            if let Some(pc) = options.trace_from {
+                tracer = tracer.start_at(pc);
+            }
+            emu.set_tracer(Some(tracer));
+        }
+        if options.profile.is_some() {
+            emu.set_profiler(Some(Profiler::new()));
+        }
+
+        emu.load_data(&rom);
+        println!("ROM SHA-1 {}", romdb::sha1(&rom));
+
+        // Known games get their settings, quirks given on command line win
+        let game = romdb::identify(&rom);
+        if let Some(game) = game {
+            println!("Recognized {} ({})", game.title, game.platform.name());
+            for control in &game.controls {
+                let keys: Vec<&str> = control.keys.iter().map(|key| crate::button_name(*key as usize)).collect();
+                println!("    {} - {}", keys.join(" / "), control.action);
+            }
+            if options.quirks.is_none() {
+                game.apply(&mut emu);
+            }
+        }
+        let ticks_per_frame = game.map_or(TICKS_PER_FRAME, Game::ticks_per_frame);
+        let cheats = CheatPanel::load(&rom, &mut emu, overlay);
+        let challenges = Challenges::load(&rom, overlay);
+
+        let script = options.script.as_ref().and_then(|path| {
+            Script::start(path, &mut emu, ticks_per_frame, overlay)
+                .map_err(|err| overlay.notify(format!("Script failed: {}", err)))
+                .ok()
+        });
+
+        // Movie either replays input recorded earlier or records the current one
+        let movie_player = movie.and_then(|movie| {
+            MoviePlayer::new(movie.clone(), &mut emu, &rom)
+                .map_err(|err| overlay.notify(format!("Can't play movie: {}", err)))
+                .ok()
+        });
+        let movie_recorder = options.record_movie.as_ref()
+            .map(|_| MovieRecorder::new(&emu, &rom, ticks_per_frame));
+
+        Self {
+            emu,
+            game,
+            cheats,
+            challenges,
+            script,
+            path: path.to_path_buf(),
+            rom,
+            watch: options.watch.then(|| FileWatch::new(path)),
+            ticks_per_frame,
+            movie_player,
+            movie_recorder,
+            netplay: None,
+            reported_issues: 0,
+        }
+    }
+
+    // Agree on the game with the other player, which restarts it for both
+    pub fn connect(&mut self, connection: Connection, overlay: &mut Overlay) -> Result<(), NetplayError> {
+        let netplay = connection.start(&mut self.emu, &self.rom, self.ticks_per_frame)?;
+        self.ticks_per_frame = netplay.ticks_per_frame();
+        let role = if netplay.is_host() { "host" } else { "guest" };
+        overlay.notify(format!("Netplay started as {}, keys count after {} frames", role, netplay.delay()));
+        self.netplay = Some(netplay);
+        Ok(())
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.netplay.is_some()
+    }
+
+    // Keyboard is ignored while movie is playing
+    pub fn accepts_keys(&self) -> bool {
+        self.movie_player.is_none()
+    }
+
+    // Press key in the emulator, remember it if movie is being recorded
+    pub fn press_key(&mut self, btn: usize, pressed: bool) {
+        if let Some(netplay) = self.netplay.as_mut() {
+            netplay.keypress(btn, pressed);
+            return;
+        }
+        match self.movie_recorder.as_mut() {
+            Some(movie) => movie.keypress(&mut self.emu, btn, pressed),
+            None => self.emu.keypress(btn, pressed),
+        }
+    }
+
+    // Run one frame, either from the movie, the network or live, when debugging GDB decides if it runs.
+    // Returns true when emulation should stop after it
+    pub fn run_frame(&mut self, gdb: Option<&mut GdbServer<Guard<Memory>>>, overlay: &mut Overlay) -> bool {
+        let frame = self.emu.frame();
+        let emu = &mut self.emu;
+        if let Some(netplay) = self.netplay.as_mut() {
+            // Frame waits when the other player is too far behind, it runs on one of the next loops
+            if let Err(err) = netplay.run_frame(emu) {
+                overlay.notify(format!("Netplay stopped: {}", err));
+                self.netplay = None;
+            }
+        }
+        else if let Some(player) = self.movie_player.as_mut() {
+            if let Err(err) = player.run_frame(emu) {
+                overlay.notify(format!("Movie stopped: {}", err));
+                self.movie_player = None;
+            }
+            else if player.finished() {
+                overlay.notify(format!("Movie finished at frame {}", emu.frame()));
+                self.movie_player = None;
+            }
+        }
+        else {
+            match gdb {
+                Some(gdb) => gdb.run_frame(emu, self.ticks_per_frame),
+                None => emu.run_frame(self.ticks_per_frame),
+            }
+            if let Some(movie) = self.movie_recorder.as_mut() {
+                movie.end_frame(emu);
+            }
+        }
+
+        // Conditions count frames, so they're only checked when one ran
+        if self.emu.frame() != frame {
+            if let Some(challenges) = self.challenges.as_mut() {
+                challenges.update(&self.emu, overlay);
+            }
+            if let Some(Err(err)) = self.script.as_mut().map(|script| script.run_frame(&mut self.emu, overlay)) {
+                overlay.notify(format!("Script stopped: {}", err));
+                self.script = None;
+            }
+        }
+
+        // Show memory issues as they come, stop right after the frame with a new one.
+        // Reset and loaded states forget the issues found before
+        let issues = self.emu.bus().issues();
+        for issue in &issues[self.reported_issues.min(issues.len())..] {
+            overlay.notify(format!("Memory: {}", issue));
+        }
+        self.reported_issues = issues.len();
+        self.emu.bus_mut().take_break()
+    }
+
+    // Load the new build of the ROM when --watch noticed one
+    pub fn check_watch(&mut self, options: &Options, overlay: &mut Overlay) {
+        if !self.watch.as_mut().is_some_and(FileWatch::changed) {
+            return;
+        }
+        let rom = match fs::read(&self.path) {
+            Ok(rom) => rom,
+            Err(err) => {
+                overlay.notify(format!("Failed to reload {}: {}", self.path.display(), err));
+                return;
+            }
+        };
+        self.emu.reload(&rom, options.keep_registers);
+        self.rom = rom;
+        self.reported_issues = 0;
+
+        // Counts and movies of the old build don't match the new one
+        if let Some(profiler) = self.emu.profiler_mut() {
+            profiler.clear();
+        }
+        if self.movie_player.take().is_some() {
+            overlay.notify("Movie stopped, the ROM changed");
+        }
+        if let (Some(movie), Some(path)) = (self.movie_recorder.take(), &options.record_movie) {
+            save_movie(movie, path);
+        }
+        overlay.notify(format!("Reloaded {}", self.path.display()));
+    }
+
+    // Print and save everything collected while playing
+    pub fn finish(mut self, options: &Options) {
+        if options.check_memory {
+            print_memory_report(self.emu.bus());
+        }
+        if let (Some(mut tracer), Some(path)) = (self.emu.take_tracer(), &options.trace) {
+            match tracer.flush() {
+                Ok(()) => println!("Saved trace to {}", path),
+                Err(err) => eprintln!("Failed to save trace: {}", err),
+            }
+        }
+        if let (Some(profiler), Some(path)) = (self.emu.profiler(), &options.profile) {
+            let rom_end = START_MEMORY_ADDR + self.rom.len().min(MAX_ROM_SIZE) as u16;
+            let saved = fs::File::create(path).and_then(|mut file| {
+                profiler.write_heat_map(self.emu.ram(), START_MEMORY_ADDR..rom_end, &mut file)
+            });
+            match saved {
+                Ok(()) => println!("Saved profile to {}", path),
+                Err(err) => eprintln!("Failed to save profile: {}", err),
+            }
+        }
+        if let (Some(movie), Some(path)) = (self.movie_recorder, &options.record_movie) {
+            save_movie(movie, path);
+        }
+        if let Some(challenges) = self.challenges {
+            challenges.finish(&options.player);
+        }
+    }
+}
+
+fn save_movie(movie: MovieRecorder, path: &str) {
+    match fs::write(path, movie.finish().to_string()) {
+        Ok(()) => println!("Saved movie to {}", path),
+        Err(err) => eprintln!("Failed to save movie: {}", err),
+    }
+}
+
+// Print every memory issue found during the session
+fn print_memory_report(guard: &Guard<Memory>) {
+    if guard.total() == 0 {
+        println!("No memory issues found");
+        return;
+    }
+    println!("Memory issues ({} bad accesses):", guard.total());
+    for issue in guard.issues() {
+        println!("    {}", issue);
+    }
+    if guard.issues().len() == guard::MAX_ISSUES {
+        println!("    ... only the first {} are listed", guard::MAX_ISSUES);
+    }
+}
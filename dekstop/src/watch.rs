@@ -0,0 +1,48 @@
+// Notices when a file changes on disk by polling its modification time,
+// a few checks per second cost nothing and work the same on every system
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+
+pub struct FileWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // Change seen on the last check, reported once the file stops changing
+    pending: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl FileWatch {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.to_path_buf(), modified: modified(path), pending: None, last_check: Instant::now() }
+    }
+
+    // Check if the file changed, true only once it's done being written
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        // File can be missing for a moment while the build replaces it
+        let current = modified(&self.path);
+        if current.is_none() || current == self.modified {
+            self.pending = None;
+            return false;
+        }
+        if current != self.pending {
+            self.pending = current;
+            return false;
+        }
+        self.modified = current;
+        self.pending = None;
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
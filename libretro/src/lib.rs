@@ -0,0 +1,371 @@
+// libretro core wrapping chip8_core, so ROMs can run inside RetroArch and other frontends.
+// libretro API is global, so the core and frontend's callbacks live in statics.
+// Every exported function follows the libretro.h contract, the frontend guarantees valid pointers
+#![allow(clippy::missing_safety_doc)]
+
+pub mod ffi;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+use chip8_core::*;
+use chip8_core::quirks::Quirks;
+use chip8_core::state::STATE_SIZE;
+use ffi::*;
+
+const SAMPLE_RATE: u32 = 44_100;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / 60) as usize;
+const BEEP_FREQUENCY: u32 = 440;
+const BEEP_VOLUME: i16 = 8_000;
+
+// RetroPad button for every Chip8 key, chosen so that the usual 2/4/6/8 movement lands on the d-pad
+const JOYPAD_MAP: [(c_uint, usize, &CStr); 16] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2, c"Key 2 (up)"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8, c"Key 8 (down)"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4, c"Key 4 (left)"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6, c"Key 6 (right)"),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x5, c"Key 5 (action)"),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x0, c"Key 0"),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0x1, c"Key 1"),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0x3, c"Key 3"),
+    (RETRO_DEVICE_ID_JOYPAD_L, 0x7, c"Key 7"),
+    (RETRO_DEVICE_ID_JOYPAD_R, 0x9, c"Key 9"),
+    (RETRO_DEVICE_ID_JOYPAD_L2, 0xA, c"Key A"),
+    (RETRO_DEVICE_ID_JOYPAD_R2, 0xB, c"Key B"),
+    (RETRO_DEVICE_ID_JOYPAD_L3, 0xC, c"Key C"),
+    (RETRO_DEVICE_ID_JOYPAD_R3, 0xD, c"Key D"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xE, c"Key E"),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xF, c"Key F"),
+];
+
+// Keyboard keys (RETROK codes are ASCII) in the same layout as the other frontends
+const KEYBOARD_MAP: [(c_uint, usize); 16] = [
+    (b'1' as c_uint, 0x1), (b'2' as c_uint, 0x2), (b'3' as c_uint, 0x3), (b'4' as c_uint, 0xC),
+    (b'q' as c_uint, 0x4), (b'w' as c_uint, 0x5), (b'e' as c_uint, 0x6), (b'r' as c_uint, 0xD),
+    (b'a' as c_uint, 0x7), (b's' as c_uint, 0x8), (b'd' as c_uint, 0x9), (b'f' as c_uint, 0xE),
+    (b'z' as c_uint, 0xA), (b'x' as c_uint, 0x0), (b'c' as c_uint, 0xB), (b'v' as c_uint, 0xF),
+];
+
+const OPTION_QUIRKS: &CStr = c"chip8_quirks";
+const OPTION_TICKS: &CStr = c"chip8_ipf";
+
+
+// Callbacks given by the frontend
+struct Callbacks {
+    environment: Option<retro_environment_t>,
+    video_refresh: Option<retro_video_refresh_t>,
+    audio_sample_batch: Option<retro_audio_sample_batch_t>,
+    input_poll: Option<retro_input_poll_t>,
+    input_state: Option<retro_input_state_t>,
+}
+
+// Loaded game
+struct Core {
+    emu: Emulator,
+    rom: Vec<u8>,
+    ticks_per_frame: usize,
+    palette: Palette,
+    rgba: [u8; RGBA_SIZE],
+    video: [u32; SCREEN_WIDTH * SCREEN_HEIGHT],
+    audio: [i16; SAMPLES_PER_FRAME * 2],
+    // Position of the beeper's square wave
+    phase: u32,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+// Locks never stay poisoned, a panic in one call shouldn't kill the frontend forever
+fn callbacks() -> MutexGuard<'static, Callbacks> {
+    CALLBACKS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn core() -> MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Call frontend's environment callback
+unsafe fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match callbacks().environment {
+        Some(callback) => callback(cmd, data),
+        None => false,
+    }
+}
+
+// Read core option, returns None if frontend doesn't know it
+unsafe fn variable(key: &CStr) -> Option<String> {
+    let mut variable = retro_variable { key: key.as_ptr(), value: ptr::null() };
+    if !environment(RETRO_ENVIRONMENT_GET_VARIABLE, &mut variable as *mut _ as *mut c_void) || variable.value.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(variable.value).to_string_lossy().into_owned())
+}
+
+// Apply core options to the loaded game
+unsafe fn apply_options(core: &mut Core) {
+    if let Some(quirks) = variable(OPTION_QUIRKS).and_then(|name| Quirks::preset(&name)) {
+        core.emu.set_quirks(quirks);
+    }
+    if let Some(ticks) = variable(OPTION_TICKS).and_then(|ticks| ticks.parse().ok()) {
+        core.ticks_per_frame = ticks;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
+    *info = retro_system_info {
+        library_name: c"Chip8-Rust".as_ptr(),
+        library_version: c"0.1.0".as_ptr(),
+        valid_extensions: c"ch8|c8|rom".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_info) {
+    *info = retro_system_av_info {
+        geometry: retro_game_geometry {
+            base_width: SCREEN_WIDTH as c_uint,
+            base_height: SCREEN_HEIGHT as c_uint,
+            max_width: SCREEN_WIDTH as c_uint,
+            max_height: SCREEN_HEIGHT as c_uint,
+            aspect_ratio: SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32,
+        },
+        timing: retro_system_timing { fps: 60.0, sample_rate: SAMPLE_RATE as f64 },
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(callback: retro_environment_t) {
+    callbacks().environment = Some(callback);
+
+    // Core options, first value is the default one
+    let variables = [
+        retro_variable { key: OPTION_QUIRKS.as_ptr(), value: c"Quirks; modern|vip|schip".as_ptr() },
+        retro_variable {
+            key: OPTION_TICKS.as_ptr(),
+            value: c"Instructions per frame; 10|5|15|20|30|50|100|200|500|1000".as_ptr(),
+        },
+        retro_variable { key: ptr::null(), value: ptr::null() },
+    ];
+    callback(RETRO_ENVIRONMENT_SET_VARIABLES, variables.as_ptr() as *mut c_void);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: retro_video_refresh_t) {
+    callbacks().video_refresh = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: retro_audio_sample_t) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: retro_audio_sample_batch_t) {
+    callbacks().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: retro_input_poll_t) {
+    callbacks().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: retro_input_state_t) {
+    callbacks().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *core() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const retro_game_info) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+    let rom = std::slice::from_raw_parts((*game).data as *const u8, (*game).size).to_vec();
+    if rom.len() > MAX_ROM_SIZE {
+        return false;
+    }
+
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, &mut format as *mut _ as *mut c_void) {
+        return false;
+    }
+
+    let mut descriptors: Vec<retro_input_descriptor> = JOYPAD_MAP.iter()
+        .map(|(id, _, description)| retro_input_descriptor {
+            port: 0, device: RETRO_DEVICE_JOYPAD, index: 0, id: *id, description: description.as_ptr(),
+        })
+        .collect();
+    descriptors.push(retro_input_descriptor { port: 0, device: 0, index: 0, id: 0, description: ptr::null() });
+    environment(RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS, descriptors.as_mut_ptr() as *mut c_void);
+
+    let mut emu = Emulator::new();
+    emu.load_data(&rom);
+    let mut core = Core {
+        emu,
+        rom,
+        ticks_per_frame: TICKS_PER_FRAME,
+        palette: Palette::default(),
+        rgba: [0; RGBA_SIZE],
+        video: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        audio: [0; SAMPLES_PER_FRAME * 2],
+        phase: 0,
+    };
+    apply_options(&mut core);
+
+    *self::core() = Some(core);
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_type: c_uint, _info: *const retro_game_info, _num: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *core() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = core().as_mut() {
+        core.emu.reset();
+        core.emu.load_data(&core.rom);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    let mut guard = core();
+    let Some(core) = guard.as_mut() else {
+        return;
+    };
+    let (video_refresh, audio_sample_batch, input_poll, input_state) = {
+        let callbacks = callbacks();
+        (callbacks.video_refresh, callbacks.audio_sample_batch, callbacks.input_poll, callbacks.input_state)
+    };
+
+    let mut updated = false;
+    if environment(RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE, &mut updated as *mut _ as *mut c_void) && updated {
+        apply_options(core);
+    }
+
+    // Key is pressed if either its RetroPad button or keyboard key is held
+    if let (Some(poll), Some(state)) = (input_poll, input_state) {
+        poll();
+        let mut keys = [false; 16];
+        for (id, btn, _) in JOYPAD_MAP {
+            keys[btn] |= state(0, RETRO_DEVICE_JOYPAD, 0, id) != 0;
+        }
+        for (id, btn) in KEYBOARD_MAP {
+            keys[btn] |= state(0, RETRO_DEVICE_KEYBOARD, 0, id) != 0;
+        }
+        for (btn, pressed) in keys.iter().enumerate() {
+            core.emu.keypress(btn, *pressed);
+        }
+    }
+
+    core.emu.run_frame(core.ticks_per_frame);
+
+    // Convert RGBA display into XRGB8888 pixels
+    core.emu.write_rgba(&mut core.rgba, &core.palette);
+    for (pixel, rgba) in core.video.iter_mut().zip(core.rgba.chunks_exact(4)) {
+        *pixel = u32::from_be_bytes([0, rgba[0], rgba[1], rgba[2]]);
+    }
+    if let Some(video_refresh) = video_refresh {
+        video_refresh(core.video.as_ptr() as *const c_void, SCREEN_WIDTH as c_uint, SCREEN_HEIGHT as c_uint, SCREEN_WIDTH * 4);
+    }
+
+    // Square wave while the sound timer is active, silence otherwise
+    let beeping = core.emu.sound_active();
+    let half_period = SAMPLE_RATE / BEEP_FREQUENCY / 2;
+    for frame in core.audio.chunks_exact_mut(2) {
+        let sample = match (beeping, (core.phase / half_period) & 1) {
+            (false, _) => 0,
+            (true, 0) => BEEP_VOLUME,
+            (true, _) => -BEEP_VOLUME,
+        };
+        core.phase = core.phase.wrapping_add(1);
+        frame.copy_from_slice(&[sample, sample]);
+    }
+    if let Some(audio_sample_batch) = audio_sample_batch {
+        audio_sample_batch(core.audio.as_ptr(), SAMPLES_PER_FRAME);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    match core().as_ref() {
+        Some(core) if !data.is_null() && size >= STATE_SIZE => {
+            let buffer = std::slice::from_raw_parts_mut(data as *mut u8, STATE_SIZE);
+            core.emu.save_state(buffer).is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    match core().as_mut() {
+        Some(core) if !data.is_null() && size >= STATE_SIZE => {
+            let buffer = std::slice::from_raw_parts(data as *const u8, STATE_SIZE);
+            core.emu.load_state(buffer).is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match core().as_mut() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => core.emu.ram_mut().as_mut_ptr() as *mut c_void,
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match core().as_ref() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => core.emu.ram().len(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,134 @@
+// Minimal libretro frontend, drives the core only through its C functions
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::{c_uint, c_void};
+use std::ptr;
+use std::sync::Mutex;
+use chip8_libretro::*;
+use chip8_libretro::ffi::*;
+
+// What the core handed to the frontend
+struct Frontend {
+    variables: Vec<String>,
+    pixel_format: Option<c_uint>,
+    descriptors: usize,
+    video: Vec<u32>,
+    video_size: (c_uint, c_uint, usize),
+    audio_frames: usize,
+    polls: usize,
+}
+
+static FRONTEND: Mutex<Frontend> = Mutex::new(Frontend {
+    variables: Vec::new(),
+    pixel_format: None,
+    descriptors: 0,
+    video: Vec::new(),
+    video_size: (0, 0, 0),
+    audio_frames: 0,
+    polls: 0,
+});
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    let mut frontend = FRONTEND.lock().unwrap();
+    match cmd {
+        RETRO_ENVIRONMENT_SET_VARIABLES => {
+            let mut variable = data as *const retro_variable;
+            while !(*variable).key.is_null() {
+                frontend.variables.push(CStr::from_ptr((*variable).key).to_string_lossy().into_owned());
+                variable = variable.add(1);
+            }
+            true
+        }
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+            frontend.pixel_format = Some(*(data as *const c_uint));
+            true
+        }
+        RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS => {
+            let mut descriptor = data as *const retro_input_descriptor;
+            while !(*descriptor).description.is_null() {
+                frontend.descriptors += 1;
+                descriptor = descriptor.add(1);
+            }
+            true
+        }
+        // Options stay on their defaults
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+    let mut frontend = FRONTEND.lock().unwrap();
+    let pixels = std::slice::from_raw_parts(data as *const u32, pitch / 4 * height as usize);
+    frontend.video = pixels.to_vec();
+    frontend.video_size = (width, height, pitch);
+}
+
+unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    FRONTEND.lock().unwrap().audio_frames += frames;
+    frames
+}
+
+unsafe extern "C" fn input_poll() {
+    FRONTEND.lock().unwrap().polls += 1;
+}
+
+unsafe extern "C" fn input_state(_port: c_uint, _device: c_uint, _index: c_uint, _id: c_uint) -> i16 {
+    0
+}
+
+// The core is global, so everything runs in one test
+#[test]
+fn plays_pong_through_c_api() {
+    unsafe {
+        assert_eq!(retro_api_version(), RETRO_API_VERSION);
+        retro_set_environment(environment);
+        retro_set_video_refresh(video_refresh);
+        retro_set_audio_sample_batch(audio_sample_batch);
+        retro_set_input_poll(input_poll);
+        retro_set_input_state(input_state);
+        retro_init();
+        assert_eq!(FRONTEND.lock().unwrap().variables, ["chip8_quirks", "chip8_ipf"]);
+
+        let rom = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../games/PONG")).unwrap();
+        let game = retro_game_info {
+            path: ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: ptr::null(),
+        };
+        assert!(retro_load_game(&game));
+        assert_eq!(FRONTEND.lock().unwrap().pixel_format, Some(RETRO_PIXEL_FORMAT_XRGB8888));
+        assert_eq!(FRONTEND.lock().unwrap().descriptors, 16);
+        assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 4096);
+
+        for _ in 0..120 {
+            retro_run();
+        }
+        {
+            let frontend = FRONTEND.lock().unwrap();
+            assert_eq!(frontend.video_size, (64, 32, 64 * 4));
+            assert!(frontend.video.iter().any(|pixel| *pixel != 0), "nothing was drawn");
+            assert_eq!(frontend.audio_frames, 120 * 735);
+            assert_eq!(frontend.polls, 120);
+        }
+
+        // Save, play on, restore, and the same frames have to come out again
+        let mut state = vec![0u8; retro_serialize_size()];
+        assert!(retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()));
+        let mut frames = Vec::new();
+        for _ in 0..60 {
+            retro_run();
+            frames.push(FRONTEND.lock().unwrap().video.clone());
+        }
+        assert!(retro_unserialize(state.as_ptr() as *const c_void, state.len()));
+        for frame in frames {
+            retro_run();
+            assert_eq!(FRONTEND.lock().unwrap().video, frame);
+        }
+
+        retro_reset();
+        retro_unload_game();
+        assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 0);
+        retro_deinit();
+    }
+}
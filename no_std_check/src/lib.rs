@@ -0,0 +1,56 @@
+// Example of driving chip8_core from firmware: display doubled into 128x64 SSD1306 buffer.
+// There is no global allocator and no std, so this only links if the core needs neither.
+#![no_std]
+#![allow(clippy::missing_safety_doc)]
+
+use core::panic::PanicInfo;
+use chip8_core::*;
+
+// SSD1306 keeps 8 vertical pixels in every byte, one page is 8 rows of the display
+pub const OLED_WIDTH: usize = SCREEN_WIDTH * 2;
+pub const OLED_HEIGHT: usize = SCREEN_HEIGHT * 2;
+pub const OLED_BUFFER_SIZE: usize = OLED_WIDTH * OLED_HEIGHT / 8;
+
+
+// Everything lives in a static, firmware gets the seed from a hardware generator
+static mut EMULATOR: Option<Emulator> = None;
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_start(rom: *const u8, len: usize, seed: u64) {
+    let rom = core::slice::from_raw_parts(rom, len.min(MAX_ROM_SIZE));
+    let mut emu = Emulator::with_seed(seed);
+    emu.load_data(rom);
+    *core::ptr::addr_of_mut!(EMULATOR) = Some(emu);
+}
+
+// Run one 60Hz frame with the keys held as bits, then fill OLED buffer
+#[no_mangle]
+pub unsafe extern "C" fn chip8_frame(keys: u16, oled: *mut u8) -> bool {
+    let Some(emu) = (*core::ptr::addr_of_mut!(EMULATOR)).as_mut() else {
+        return false;
+    };
+    for btn in 0..16 {
+        emu.keypress(btn, keys & (1 << btn) != 0);
+    }
+    emu.run_frame(TICKS_PER_FRAME);
+
+    let oled = core::slice::from_raw_parts_mut(oled, OLED_BUFFER_SIZE);
+    let display = emu.get_display();
+    for (index, byte) in oled.iter_mut().enumerate() {
+        let x = index % OLED_WIDTH;
+        let page = index / OLED_WIDTH;
+        *byte = 0;
+        for bit in 0..8 {
+            let y = page * 8 + bit;
+            if display[x / 2 + y / 2 * SCREEN_WIDTH] {
+                *byte |= 1 << bit;
+            }
+        }
+    }
+    emu.sound_active()
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
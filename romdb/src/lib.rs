@@ -0,0 +1,133 @@
+// Database of known ROMs, embedded from games.toml. Games are recognized by SHA-1 of the file,
+// so renamed ROMs still get their title, controls and the settings they need.
+use std::sync::OnceLock;
+use serde::Deserialize;
+use chip8_core::bus::Bus;
+use chip8_core::quirks::Quirks;
+use chip8_core::{Emulator, Palette, TICKS_PER_FRAME};
+
+const DATABASE: &str = include_str!("../games.toml");
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Platform {
+    #[serde(rename = "chip8")]
+    Chip8,
+    #[serde(rename = "schip")]
+    SuperChip,
+    #[serde(rename = "xochip")]
+    XoChip,
+}
+
+impl Platform {
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+}
+
+// What some keys do in the game
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Control {
+    // CHIP-8 keys, 0x0-0xF
+    pub keys: Vec<u8>,
+    pub action: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct GamePalette {
+    pub on: String,
+    pub off: String,
+}
+
+// One known ROM, fields are described in games.toml
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Game {
+    pub sha1: String,
+    pub file: Option<String>,
+    pub title: String,
+    pub author: Option<String>,
+    pub year: Option<u16>,
+    pub platform: Platform,
+    pub quirks: Option<String>,
+    pub ipf: Option<usize>,
+    pub palette: Option<GamePalette>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub controls: Vec<Control>,
+    pub keypad: Option<Vec<u8>>,
+}
+
+impl Game {
+    // Quirks the game needs, None if any interpreter runs it
+    pub fn quirks(&self) -> Option<Quirks> {
+        self.quirks.as_deref().and_then(Quirks::preset)
+    }
+
+    // Instructions per frame the game should run at
+    pub fn ticks_per_frame(&self) -> usize {
+        self.ipf.unwrap_or(TICKS_PER_FRAME)
+    }
+
+    pub fn palette(&self) -> Option<Palette> {
+        let palette = self.palette.as_ref()?;
+        Some(Palette { on: parse_color(&palette.on)?, off: parse_color(&palette.off)? })
+    }
+
+    // Keys a touch keypad should show, sorted. Empty when the game could use any of them
+    pub fn keypad(&self) -> Vec<u8> {
+        let mut keys = match &self.keypad {
+            Some(keys) => keys.clone(),
+            None => self.controls.iter().flat_map(|control| control.keys.iter().copied()).collect(),
+        };
+        keys.sort_unstable();
+        keys.dedup();
+        keys
+    }
+
+    // Set up emulator for the game, frontends handle speed and palette themselves
+    pub fn apply(&self, emu: &mut Emulator<impl Bus>) {
+        if let Some(quirks) = self.quirks() {
+            emu.set_quirks(quirks);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Database {
+    game: Vec<Game>,
+}
+
+// Every known game, in the order of games.toml
+pub fn games() -> &'static [Game] {
+    static GAMES: OnceLock<Vec<Game>> = OnceLock::new();
+    GAMES.get_or_init(|| {
+        let database: Database = toml::from_str(DATABASE).expect("Invalid games.toml");
+        database.game
+    })
+}
+
+// Find game by contents of its ROM
+pub fn identify(rom: &[u8]) -> Option<&'static Game> {
+    let hash = sha1(rom);
+    games().iter().find(|game| game.sha1 == hash)
+}
+
+// Lowercase hex SHA-1, the key of the database
+pub fn sha1(rom: &[u8]) -> String {
+    chip8_core::hash::sha1(rom)
+}
+
+// "#rrggbb" into opaque RGBA
+fn parse_color(text: &str) -> Option<[u8; 4]> {
+    let digits = text.strip_prefix('#')?;
+    if digits.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let [_, red, green, blue] = value.to_be_bytes();
+    Some([red, green, blue, 255])
+}
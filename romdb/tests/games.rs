@@ -0,0 +1,54 @@
+// Every bundled ROM has to be in the database, with settings frontends can use.
+use std::collections::HashSet;
+use std::fs;
+use chip8_core::Emulator;
+use chip8_core::quirks::Quirks;
+
+#[test]
+fn database_is_valid() {
+    let games = romdb::games();
+    let hashes: HashSet<&str> = games.iter().map(|game| game.sha1.as_str()).collect();
+    assert_eq!(hashes.len(), games.len(), "duplicate hashes");
+
+    for game in games {
+        assert!(game.sha1.len() == 40 && game.sha1.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()),
+                "{}: bad hash", game.title);
+        assert!(game.quirks.is_none() || game.quirks().is_some(), "{}: unknown quirks", game.title);
+        assert!(game.palette.is_none() || game.palette().is_some(), "{}: bad palette", game.title);
+        assert!(game.ticks_per_frame() > 0, "{}: zero ipf", game.title);
+        assert!(game.controls.iter().flat_map(|control| &control.keys).all(|key| *key <= 0xF),
+                "{}: key out of range", game.title);
+        assert!(game.keypad().iter().all(|key| *key <= 0xF), "{}: keypad key out of range", game.title);
+    }
+}
+
+#[test]
+fn bundled_games_are_identified() {
+    for entry in fs::read_dir("../games").unwrap() {
+        let path = entry.unwrap().path();
+        let rom = fs::read(&path).unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let game = romdb::identify(&rom).unwrap_or_else(|| panic!("{} isn't in the database", name));
+        assert_eq!(game.file.as_deref(), Some(name));
+    }
+    assert!(romdb::identify(b"not a game").is_none());
+}
+
+#[test]
+fn apply_sets_quirks() {
+    let mut game = romdb::games()[0].clone();
+    game.quirks = Some("vip".to_string());
+    let mut emu = Emulator::with_seed(0);
+    game.apply(&mut emu);
+    assert_eq!(emu.quirks(), Quirks::COSMAC_VIP);
+}
+
+#[test]
+fn keypad_shows_keys_of_controls() {
+    let tetris = romdb::games().iter().find(|game| game.title == "Tetris").unwrap();
+    assert_eq!(tetris.keypad(), [0x4, 0x5, 0x6, 0x7]);
+
+    let mut game = tetris.clone();
+    game.keypad = Some(vec![0xF, 0x5, 0x5]);
+    assert_eq!(game.keypad(), [0x5, 0xF]);
+}
@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+
+const KEYS_NUM: usize = 16;
+
+
+// Keeps track of held Chip8 keys. Most terminals only report presses, so a key counts
+// as released when it didn't repeat for a while. Terminals which report releases
+// (kitty keyboard protocol) skip the guessing.
+pub struct Keypad {
+    // When the key was last seen pressed, None if it's released
+    held: [Option<Instant>; KEYS_NUM],
+    hold_time: Duration,
+    reports_release: bool,
+}
+
+impl Keypad {
+    pub fn new(hold_time: Duration, reports_release: bool) -> Self {
+        Self { held: [None; KEYS_NUM], hold_time, reports_release }
+    }
+
+    // Handle terminal key event, returns Chip8 keys that changed state
+    pub fn handle(&mut self, event: KeyEvent, now: Instant) -> Option<(usize, bool)> {
+        let KeyCode::Char(character) = event.code else {
+            return None;
+        };
+        let btn = key_to_button(character.to_ascii_lowercase())?;
+
+        match event.kind {
+            KeyEventKind::Release => {
+                self.held[btn] = None;
+                Some((btn, false))
+            }
+            _ => {
+                let was_held = self.held[btn].is_some();
+                self.held[btn] = Some(now);
+                (!was_held).then_some((btn, true))
+            }
+        }
+    }
+
+    // Release keys which stopped repeating, returns them
+    pub fn expire(&mut self, now: Instant) -> Vec<usize> {
+        if self.reports_release {
+            return Vec::new();
+        }
+
+        let mut released = Vec::new();
+        for (btn, held) in self.held.iter_mut().enumerate() {
+            if held.is_some_and(|since| now.duration_since(since) > self.hold_time) {
+                *held = None;
+                released.push(btn);
+            }
+        }
+        released
+    }
+
+    pub fn is_held(&self, btn: usize) -> bool {
+        self.held[btn].is_some()
+    }
+}
+
+// Convert key to Chip8 button
+fn key_to_button(key: char) -> Option<usize> {
+    match key {
+        '1' => Some(0x1),
+        '2' => Some(0x2),
+        '3' => Some(0x3),
+        '4' => Some(0xC),
+        'q' => Some(0x4),
+        'w' => Some(0x5),
+        'e' => Some(0x6),
+        'r' => Some(0xD),
+        'a' => Some(0x7),
+        's' => Some(0x8),
+        'd' => Some(0x9),
+        'f' => Some(0xE),
+        'z' => Some(0xA),
+        'x' => Some(0x0),
+        'c' => Some(0xB),
+        'v' => Some(0xF),
+        _ => None,
+    }
+}
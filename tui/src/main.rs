@@ -0,0 +1,133 @@
+mod input;
+mod render;
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use chip8_core::*;
+use chip8_core::quirks::Quirks;
+use crossterm::{cursor, event, execute, terminal};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+                       PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+use input::Keypad;
+use render::{Mode, Renderer};
+
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// Terminal key repeat usually starts after about half a second
+const DEFAULT_HOLD_TIME: Duration = Duration::from_millis(550);
+
+const USAGE: &str = "Usage: cargo run [options] game/path
+
+Options:
+    --braille                     draw with braille characters, for small terminals
+    --hold <ms>                   how long key stays pressed without repeating
+    --quirks <vip|schip|modern>   interpreter to imitate
+
+Press Esc to quit, P to pause.";
+
+
+fn main() {
+    // Get env arguments
+    let mut rom = None;
+    let mut mode = Mode::HalfBlock;
+    let mut hold_time = DEFAULT_HOLD_TIME;
+    let mut quirks = Quirks::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--braille" => mode = Mode::Braille,
+            "--hold" => match args.next().and_then(|ms| ms.parse().ok()) {
+                Some(ms) => hold_time = Duration::from_millis(ms),
+                None => return println!("{}", USAGE),
+            },
+            "--quirks" => match args.next().and_then(|name| Quirks::preset(&name)) {
+                Some(preset) => quirks = preset,
+                None => return println!("{}", USAGE),
+            },
+            _ if rom.is_none() && !arg.starts_with("--") => rom = Some(arg),
+            _ => return println!("{}", USAGE),
+        }
+    }
+    let Some(rom) = rom else {
+        return println!("{}", USAGE);
+    };
+
+    let mut emu = Emulator::new();
+    emu.set_quirks(quirks);
+    // Open ROM from arguments, read it and load into emulator
+    let game_buffer = fs::read(&rom).expect("Failed to open the file");
+    emu.load_data(&game_buffer);
+
+    let mut out = io::stdout();
+    terminal::enable_raw_mode().expect("Terminal doesn't support raw mode");
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide).unwrap();
+
+    // Ask for key releases where terminal can report them
+    let reports_release = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if reports_release {
+        execute!(out, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)).unwrap();
+    }
+
+    let result = run(&mut emu, &mut out, Keypad::new(hold_time, reports_release), mode);
+
+    // Always give the terminal back in the state it was in
+    if reports_release {
+        let _ = execute!(out, PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(out, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+
+    if let Err(err) = result {
+        eprintln!("Terminal error: {}", err);
+    }
+}
+
+// The entire program loop
+fn run(emu: &mut Emulator, out: &mut impl Write, mut keypad: Keypad, mode: Mode) -> io::Result<()> {
+    let mut renderer = Renderer::new(mode);
+    renderer.invalidate(out)?;
+
+    let mut paused = false;
+    let mut next_frame = Instant::now();
+
+    loop {
+        // Handle input until it's time for the next frame
+        while event::poll(next_frame.saturating_duration_since(Instant::now()))? {
+            match event::read()? {
+                Event::Key(key) if key.code == KeyCode::Esc => return Ok(()),
+                Event::Key(key) if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                Event::Key(key) if key.code == KeyCode::Char('p') && key.kind == KeyEventKind::Press => {
+                    paused = !paused;
+                }
+                Event::Key(key) => {
+                    if let Some((btn, pressed)) = keypad.handle(key, Instant::now()) {
+                        emu.keypress(btn, pressed);
+                    }
+                }
+                Event::Resize(..) => renderer.invalidate(out)?,
+                _ => (),
+            }
+        }
+        next_frame = (next_frame + FRAME_DURATION).max(Instant::now());
+
+        for btn in keypad.expire(Instant::now()) {
+            emu.keypress(btn, false);
+        }
+
+        if !paused {
+            let was_beeping = emu.sound_active();
+            emu.run_frame(TICKS_PER_FRAME);
+
+            // Terminal bell rings once when the beeper starts
+            if emu.sound_active() && !was_beeping {
+                write!(out, "\x07")?;
+            }
+        }
+
+        renderer.draw(out, emu, &keypad, if paused { "PAUSED" } else { "" })?;
+    }
+}
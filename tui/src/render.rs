@@ -0,0 +1,129 @@
+use std::io::{self, Write};
+use chip8_core::*;
+use crossterm::{cursor::MoveTo, queue, style::Print, terminal::{Clear, ClearType}};
+use crate::input::Keypad;
+
+// Columns between display and registers pane
+const PANE_GAP: usize = 2;
+// Layout of the keypad shown in registers pane
+const KEYPAD: [[usize; 4]; 4] = [[0x1, 0x2, 0x3, 0xC], [0x4, 0x5, 0x6, 0xD], [0x7, 0x8, 0x9, 0xE], [0xA, 0x0, 0xB, 0xF]];
+
+
+// How display pixels are packed into terminal characters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // Two pixels per character, uses whole screen width
+    HalfBlock,
+    // Eight pixels per character, fits in small terminals
+    Braille,
+}
+
+// Draws emulator into terminal, only lines that changed get sent
+pub struct Renderer {
+    mode: Mode,
+    previous: Vec<String>,
+}
+
+impl Renderer {
+    pub fn new(mode: Mode) -> Self {
+        Self { mode, previous: Vec::new() }
+    }
+
+    // Forget what is on the terminal, e.g. after it was resized
+    pub fn invalidate(&mut self, out: &mut impl Write) -> io::Result<()> {
+        self.previous.clear();
+        queue!(out, Clear(ClearType::All))
+    }
+
+    pub fn draw(&mut self, out: &mut impl Write, emu: &Emulator, keypad: &Keypad, status: &str) -> io::Result<()> {
+        let display = match self.mode {
+            Mode::HalfBlock => half_blocks(emu.get_display()),
+            Mode::Braille => braille(emu.get_display()),
+        };
+        let pane = registers(emu, keypad, status);
+        let display_width = display.first().map_or(0, |line| line.chars().count());
+
+        // Put display and pane side by side
+        let lines = display.len().max(pane.len());
+        let mut frame = Vec::with_capacity(lines);
+        for number in 0..lines {
+            let left = display.get(number).cloned().unwrap_or_else(|| " ".repeat(display_width));
+            let right = pane.get(number).map_or("", String::as_str);
+            frame.push(format!("{}{}{:<24}", left, " ".repeat(PANE_GAP), right));
+        }
+
+        for (number, line) in frame.iter().enumerate() {
+            if self.previous.get(number) != Some(line) {
+                queue!(out, MoveTo(0, number as u16), Print(line))?;
+            }
+        }
+        self.previous = frame;
+        out.flush()
+    }
+}
+
+// Every character holds upper and lower pixel
+fn half_blocks(display: &[bool]) -> Vec<String> {
+    (0..SCREEN_HEIGHT).step_by(2).map(|y| {
+        (0..SCREEN_WIDTH).map(|x| {
+            let top = display[x + y * SCREEN_WIDTH];
+            let bottom = display[x + (y + 1) * SCREEN_WIDTH];
+            match (top, bottom) {
+                (false, false) => ' ',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (true, true) => '█',
+            }
+        }).collect()
+    }).collect()
+}
+
+// Every character holds 2x4 pixels as braille dots
+fn braille(display: &[bool]) -> Vec<String> {
+    // Bit of every dot, indexed by [y][x] inside of the character
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    (0..SCREEN_HEIGHT).step_by(4).map(|y| {
+        (0..SCREEN_WIDTH).step_by(2).map(|x| {
+            let mut bits = 0;
+            for (dy, row) in DOTS.iter().enumerate() {
+                for (dx, bit) in row.iter().enumerate() {
+                    if display[x + dx + (y + dy) * SCREEN_WIDTH] {
+                        bits |= bit;
+                    }
+                }
+            }
+            char::from_u32(0x2800 + bits).unwrap_or(' ')
+        }).collect()
+    }).collect()
+}
+
+// Registers, timers and keypad of the emulator
+fn registers(emu: &Emulator, keypad: &Keypad, status: &str) -> Vec<String> {
+    let mut lines = vec![
+        format!("PC {:04X}  I  {:04X}", emu.pc(), emu.i_reg()),
+        format!("SP {:02X}    DT {:02X}  ST {:02X}", emu.sp(), emu.delay_timer(), emu.sound_timer()),
+        String::new(),
+    ];
+
+    let v_regs = emu.v_regs();
+    for row in 0..4 {
+        lines.push((0..4)
+            .map(|column| row + column * 4)
+            .map(|reg| format!("V{:X} {:02X}", reg, v_regs[reg]))
+            .collect::<Vec<_>>()
+            .join("  "));
+    }
+
+    lines.push(String::new());
+    for row in KEYPAD {
+        lines.push(row.iter()
+            .map(|btn| if keypad.is_held(*btn) { format!("[{:X}]", btn) } else { format!(" {:X} ", btn) })
+            .collect());
+    }
+
+    lines.push(String::new());
+    lines.push(format!("FRAME {}", emu.frame()));
+    lines.push(status.to_string());
+    lines
+}
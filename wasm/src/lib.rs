@@ -1,9 +1,13 @@
-use js_sys::Uint8Array;
+use js_sys::{Array, Object, Reflect, Uint8Array};
 use chip8_core::*;
-use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent};
-use std::io::Read;
-use std::fs::File;
+use chip8_core::cheat::{format_cheats, parse_cheats, Cheat, CheatSearch, Compare};
+use chip8_core::filter::{Filter, Scaler, MAX_OUTPUT_SIZE};
+use chip8_core::profile::Profiler;
+use chip8_core::quirks::Quirks;
+use chip8_core::state::STATE_SIZE;
+use romdb::Game;
+use wasm_bindgen::{prelude::*, Clamped};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, KeyboardEvent};
 use console_error_panic_hook::set_once;
 
 #[wasm_bindgen(start)]
@@ -11,49 +15,108 @@ pub fn main() {
     set_once();
 }
 
+// Shapes of the objects returned by game_info and game_list
+#[wasm_bindgen(typescript_custom_section)]
+const GAME_TYPES: &'static str = r#"
+export interface GameControl {
+    keys: string[];
+    action: string;
+}
+
+export interface GameInfo {
+    title: string;
+    author?: string;
+    year?: number;
+    platform: string;
+    description?: string;
+    controls: GameControl[];
+}
+
+export interface GameEntry {
+    file: string;
+    title: string;
+}
+"#;
+
 #[wasm_bindgen]
 pub struct EmulatorWasm {
     emu: Emulator,
-    ctx: CanvasRenderingContext2d,
+    // Canvas the display is drawn on, None when running headless
+    canvas: Option<(HtmlCanvasElement, CanvasRenderingContext2d)>,
+    frame: Vec<u8>,
+    scaled: Vec<u8>,
+    palette: Palette,
+    scaler: Scaler,
+    rom_size: usize,
+    // Recognized game, None for unknown ROMs
+    game: Option<&'static Game>,
+    rom_hash: String,
+    cheat_search: Option<CheatSearch>,
 }
 
 // Wrappers and functions for frontend using wasm
 #[wasm_bindgen]
 impl EmulatorWasm {
+    // Emulator drawing on the canvas, or headless without one
     #[wasm_bindgen(constructor)]
-    pub fn new() -> Result<EmulatorWasm, JsValue> {
-        let emu = Emulator::new();
-
-        // Get document window, html canvas
-        let document = web_sys::window().unwrap().document().unwrap();
-        let canvas = document.get_element_by_id("canvas").unwrap();
-        let canvas: HtmlCanvasElement = canvas
-            .dyn_into::<HtmlCanvasElement>()
-            .map_err(|_| ()).unwrap();
-
-        // Grab canvas context
-        let ctx = canvas.get_context("2d").unwrap().unwrap()
-            .dyn_into::<CanvasRenderingContext2d>().unwrap();
+    pub fn new(canvas: Option<HtmlCanvasElement>) -> Result<EmulatorWasm, JsValue> {
+        let canvas = match canvas {
+            Some(canvas) => {
+                let ctx = canvas.get_context("2d")?
+                    .ok_or("Canvas has no 2d context")?
+                    .dyn_into::<CanvasRenderingContext2d>()?;
+                Some((canvas, ctx))
+            }
+            None => None,
+        };
 
-        Ok(EmulatorWasm{emu, ctx})
+        Ok(EmulatorWasm{
+            emu: Emulator::new(),
+            canvas,
+            frame: vec![0; RGBA_SIZE],
+            scaled: vec![0; MAX_OUTPUT_SIZE],
+            palette: Palette::default(),
+            scaler: Scaler::default(),
+            rom_size: 0,
+            game: None,
+            rom_hash: String::new(),
+            cheat_search: None,
+        })
     }
 
     #[wasm_bindgen]
-    pub fn draw_display(&mut self, scale: usize) {
-        let display = self.emu.get_display();
+    pub fn draw_display(&mut self) -> Result<(), JsValue> {
+        let (canvas, ctx) = self.canvas.as_ref().ok_or("Headless emulator has no canvas to draw on")?;
 
-        // Draw all pixels needed on canvas
-        for i in 0..(SCREEN_WIDTH * SCREEN_HEIGHT) {
-            if display[i] {
-                let x = i % SCREEN_WIDTH;
-                let y = i / SCREEN_WIDTH;
+        // Convert display into filtered pixels and put them on canvas in one call
+        self.emu.write_rgba(&mut self.frame, &self.palette);
+        let size = self.scaler.output_size(SCREEN_WIDTH, SCREEN_HEIGHT);
+        self.scaler.apply(&self.frame, SCREEN_WIDTH, SCREEN_HEIGHT, &mut self.scaled[..size]);
 
-                self.ctx.fill_rect(
-                    (x * scale) as f64, (y * scale) as f64,
-                    scale as f64, scale as f64
-                );
-            }
+        // Canvas resolution follows the filter, CSS scales it to the page
+        let factor = self.scaler.factor();
+        let (width, height) = ((SCREEN_WIDTH * factor) as u32, (SCREEN_HEIGHT * factor) as u32);
+        if canvas.width() != width || canvas.height() != height {
+            canvas.set_width(width);
+            canvas.set_height(height);
         }
+
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&self.scaled[..size]), width, height
+        )?;
+        ctx.put_image_data(&image, 0.0, 0.0)
+    }
+
+    #[wasm_bindgen]
+    pub fn set_filter(&mut self, name: &str) -> Result<(), JsValue> {
+        self.scaler.filter = Filter::from_name(name)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown filter {}", name)))?;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn set_scanlines(&mut self, enabled: bool) {
+        self.scaler.scanlines = enabled;
     }
 
     #[wasm_bindgen]
@@ -64,9 +127,271 @@ impl EmulatorWasm {
         }
     }
 
+    // Press or release Chip8 key 0-F directly, for touch screens and custom bindings
+    #[wasm_bindgen]
+    pub fn press_key(&mut self, key: usize, pressed: bool) -> Result<(), JsValue> {
+        if key >= 16 {
+            return Err(format!("No key {}", key).into());
+        }
+        self.emu.keypress(key, pressed);
+        Ok(())
+    }
+
     #[wasm_bindgen]
     pub fn load_data(&mut self, data: Uint8Array) {
-        self.emu.load_data(&data.to_vec());
+        let rom = data.to_vec();
+        self.rom_size = rom.len().min(MAX_ROM_SIZE);
+        self.emu.load_data(&rom);
+        self.rom_hash = romdb::sha1(&rom);
+
+        // Settings and cheats of the previous game don't carry over
+        self.emu.cheats_mut().clear();
+        self.cheat_search = None;
+        self.game = romdb::identify(&rom);
+        self.emu.set_quirks(Quirks::default());
+        self.palette = Palette::default();
+        if let Some(game) = self.game {
+            game.apply(&mut self.emu);
+            self.palette = game.palette().unwrap_or_default();
+        }
+    }
+
+    // Instructions to run every frame, known games may need a different speed
+    #[wasm_bindgen]
+    pub fn ticks_per_frame(&self) -> usize {
+        self.game.map_or(TICKS_PER_FRAME, Game::ticks_per_frame)
+    }
+
+    // Title, author, year, platform, description and controls of the loaded game, undefined if it's unknown
+    #[wasm_bindgen(unchecked_return_type = "GameInfo | undefined")]
+    pub fn game_info(&self) -> Option<Object> {
+        let game = self.game?;
+        let info = Object::new();
+        set(&info, "title", game.title.as_str().into());
+        set(&info, "author", game.author.as_deref().into());
+        set(&info, "year", game.year.into());
+        set(&info, "platform", game.platform.name().into());
+        set(&info, "description", game.description.as_deref().into());
+
+        let controls = Array::new();
+        for control in &game.controls {
+            let keys: Array = control.keys.iter().map(|key| JsValue::from(button_name(*key as usize))).collect();
+            let entry = Object::new();
+            set(&entry, "keys", keys.into());
+            set(&entry, "action", control.action.as_str().into());
+            controls.push(&entry);
+        }
+        set(&info, "controls", controls.into());
+        Some(info)
+    }
+
+    // Keys the touch keypad should show, every key for unknown games and games which could use any
+    #[wasm_bindgen]
+    pub fn keypad_keys(&self) -> Vec<u8> {
+        let keys = self.game.map(Game::keypad).unwrap_or_default();
+        if keys.is_empty() { (0..16).collect() } else { keys }
+    }
+
+    // Start or stop counting executed instructions
+    #[wasm_bindgen]
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.emu.set_profiler(enabled.then(Profiler::new));
+    }
+
+    // Heat map of the loaded ROM as text, undefined when not profiling
+    #[wasm_bindgen]
+    pub fn profile_report(&self) -> Option<String> {
+        let profiler = self.emu.profiler()?;
+        let mut report = Vec::new();
+        let rom = START_MEMORY_ADDR..START_MEMORY_ADDR + self.rom_size as u16;
+        profiler.write_heat_map(self.emu.ram(), rom, &mut report).ok()?;
+        String::from_utf8(report).ok()
+    }
+
+    // SHA-1 of the loaded ROM in hex, a key to save its cheats under
+    #[wasm_bindgen]
+    pub fn rom_hash(&self) -> String {
+        self.rom_hash.clone()
+    }
+
+    // Start searching memory, every address is a candidate
+    #[wasm_bindgen]
+    pub fn cheat_search_start(&mut self) {
+        self.cheat_search = Some(CheatSearch::new(self.emu.ram()));
+    }
+
+    // Keep addresses which are "equal" to the value, "changed", "unchanged", "increased" or "decreased"
+    // since the last search, returns how many are left
+    #[wasm_bindgen]
+    pub fn cheat_search(&mut self, compare: &str, value: Option<u8>) -> Result<usize, JsValue> {
+        let compare = match (compare, value) {
+            ("equal", Some(value)) => Compare::Equal(value),
+            ("equal", None) => return Err("Equal needs a value".into()),
+            ("changed", _) => Compare::Changed,
+            ("unchanged", _) => Compare::Unchanged,
+            ("increased", _) => Compare::Increased,
+            ("decreased", _) => Compare::Decreased,
+            _ => return Err(format!("Unknown comparison {}", compare).into()),
+        };
+        let search = self.cheat_search.as_mut().ok_or("Start a search first")?;
+        Ok(search.filter(self.emu.ram(), compare))
+    }
+
+    #[wasm_bindgen]
+    pub fn cheat_candidates(&self) -> Vec<u16> {
+        self.cheat_search.as_ref().map_or_else(Vec::new, |search| search.candidates().to_vec())
+    }
+
+    // Add a code like "2F0=03 lives"
+    #[wasm_bindgen]
+    pub fn add_cheat(&mut self, code: &str) -> Result<(), JsValue> {
+        let cheat = Cheat::parse(code)?;
+        self.emu.cheats_mut().push(cheat);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(cheat) = self.emu.cheats_mut().get_mut(index) {
+            cheat.enabled = enabled;
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_cheat(&mut self, index: usize) {
+        if index < self.emu.cheats().len() {
+            self.emu.cheats_mut().remove(index);
+        }
+    }
+
+    // Every code as text, one per line, for saving
+    #[wasm_bindgen]
+    pub fn cheats_text(&self) -> String {
+        format_cheats(self.emu.cheats())
+    }
+
+    // Replace codes with saved ones from cheats_text
+    #[wasm_bindgen]
+    pub fn load_cheats(&mut self, text: &str) -> Result<(), JsValue> {
+        *self.emu.cheats_mut() = parse_cheats(text)?;
+        Ok(())
+    }
+
+    // Change quirks to the ones of "vip", "schip" or "modern" interpreter
+    #[wasm_bindgen]
+    pub fn set_quirks(&mut self, name: &str) -> Result<(), JsValue> {
+        let quirks = Quirks::preset(name).ok_or_else(|| format!("Unknown quirks {}", name))?;
+        self.emu.set_quirks(quirks);
+        Ok(())
+    }
+
+    // Registers V0-VF
+    #[wasm_bindgen]
+    pub fn registers(&self) -> Vec<u8> {
+        self.emu.v_regs().to_vec()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_register(&mut self, index: usize, value: u8) -> Result<(), JsValue> {
+        let register = self.emu.v_regs_mut().get_mut(index).ok_or_else(|| format!("No register V{}", index))?;
+        *register = value;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn pc(&self) -> u16 {
+        self.emu.pc()
+    }
+
+    #[wasm_bindgen]
+    pub fn i_reg(&self) -> u16 {
+        self.emu.i_reg()
+    }
+
+    #[wasm_bindgen]
+    pub fn sp(&self) -> u16 {
+        self.emu.sp()
+    }
+
+    #[wasm_bindgen]
+    pub fn stack(&self) -> Vec<u16> {
+        self.emu.stack().to_vec()
+    }
+
+    #[wasm_bindgen]
+    pub fn delay_timer(&self) -> u8 {
+        self.emu.delay_timer()
+    }
+
+    #[wasm_bindgen]
+    pub fn sound_timer(&self) -> u8 {
+        self.emu.sound_timer()
+    }
+
+    // True while the beeper should sound
+    #[wasm_bindgen]
+    pub fn sound_active(&self) -> bool {
+        self.emu.sound_active()
+    }
+
+    // Frames run since reset, as a number since it won't get anywhere near 2^53
+    #[wasm_bindgen]
+    pub fn frame(&self) -> f64 {
+        self.emu.frame() as f64
+    }
+
+    // Copy of the whole memory
+    #[wasm_bindgen]
+    pub fn memory(&self) -> Vec<u8> {
+        self.emu.ram().to_vec()
+    }
+
+    // Bytes starting at the address, wrapping around at the end of memory
+    #[wasm_bindgen]
+    pub fn read_memory(&self, address: u16, length: usize) -> Vec<u8> {
+        let ram = self.emu.ram();
+        (0..length).map(|offset| ram[(address as usize + offset) % ram.len()]).collect()
+    }
+
+    #[wasm_bindgen]
+    pub fn write_memory(&mut self, address: u16, data: &[u8]) {
+        let ram = self.emu.ram_mut();
+        let size = ram.len();
+        for (offset, byte) in data.iter().enumerate() {
+            ram[(address as usize + offset) % size] = *byte;
+        }
+    }
+
+    // Pixels of the display row by row, 1 when lit, 64x32
+    #[wasm_bindgen]
+    pub fn display(&self) -> Vec<u8> {
+        self.emu.get_display().iter().map(|pixel| *pixel as u8).collect()
+    }
+
+    // Display as RGBA in colors of the game, unfiltered, ready for ImageData of 64x32
+    #[wasm_bindgen]
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let mut rgba = vec![0; RGBA_SIZE];
+        self.emu.write_rgba(&mut rgba, &self.palette);
+        rgba
+    }
+
+    #[wasm_bindgen]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![0; STATE_SIZE];
+        self.emu.save_state(&mut state).expect("Buffer has the size of a state");
+        state
+    }
+
+    #[wasm_bindgen]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsValue> {
+        self.emu.load_state(state).map_err(|err| JsValue::from(err.to_string()))
+    }
+
+    // One 60Hz frame: instructions of the frame, then timers
+    #[wasm_bindgen]
+    pub fn run_frame(&mut self) {
+        self.emu.run_frame(self.ticks_per_frame());
     }
 
     #[wasm_bindgen]
@@ -85,6 +410,29 @@ impl EmulatorWasm {
     }
 }
 
+// Every game in the database with a file in games/, as {file, title}
+#[wasm_bindgen(unchecked_return_type = "GameEntry[]")]
+pub fn game_list() -> Array {
+    romdb::games().iter()
+        .filter_map(|game| {
+            let entry = Object::new();
+            set(&entry, "file", game.file.as_deref()?.into());
+            set(&entry, "title", game.title.as_str().into());
+            Some(JsValue::from(entry))
+        })
+        .collect()
+}
+
+fn set(object: &Object, key: &str, value: JsValue) {
+    Reflect::set(object, &key.into(), &value).unwrap();
+}
+
+// Keyboard key of Chip8 button, the opposite of key_to_button
+fn button_name(button: usize) -> &'static str {
+    const NAMES: [&str; 16] = ["C", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "X", "4", "R", "F", "V"];
+    NAMES[button & 0xF]
+}
+
 // Change key from JS to Chip8 button
 fn key_to_button(key: &str) -> Option<usize> {
     match key {
@@ -0,0 +1,82 @@
+import init, * as wasm from "./wasm.js"
+
+const KEYS_NUM = 16;
+
+// Wasm module is shared by every emulator on the page, it's loaded only once
+let loading = null;
+export function ready() {
+    if (loading === null) {
+        loading = init();
+    }
+    return loading;
+}
+
+// One emulator on the page with its own canvas and loop. Keys reach it only while its canvas
+// has focus, so several of them can run side by side
+export class Chip8Player {
+    constructor(canvas, scale = 15) {
+        this.canvas = canvas;
+        this.emu = new wasm.EmulatorWasm(canvas);
+        this.frame = 0;
+
+        // Native resolution, CSS scales it
+        canvas.width = 64;
+        canvas.height = 32;
+        canvas.style.width = `${64 * scale}px`;
+        canvas.style.height = `${32 * scale}px`;
+
+        // Canvas can't get focus without tab index
+        canvas.tabIndex = 0;
+        canvas.addEventListener("keydown", (event) => this.emu.keypress(event, true));
+        canvas.addEventListener("keyup", (event) => this.emu.keypress(event, false));
+        // Keys released while focus was elsewhere would stay held
+        canvas.addEventListener("blur", () => this.releaseKeys());
+        canvas.addEventListener("click", () => canvas.focus());
+    }
+
+    // Restart with the ROM, given as bytes
+    load(rom) {
+        this.stop();
+        this.emu.reset();
+        this.emu.load_data(rom);
+        this.start();
+    }
+
+    // Fetch the ROM and load it
+    async loadUrl(url) {
+        const response = await fetch(url);
+        if (!response.ok) {
+            throw new Error(`Failed to load ${url}: ${response.status}`);
+        }
+        this.load(new Uint8Array(await response.arrayBuffer()));
+    }
+
+    start() {
+        if (this.frame !== 0) {
+            return;
+        }
+        const loop = () => {
+            this.emu.run_frame();
+            this.emu.draw_display();
+            this.frame = window.requestAnimationFrame(loop);
+        };
+        this.frame = window.requestAnimationFrame(loop);
+    }
+
+    stop() {
+        if (this.frame !== 0) {
+            window.cancelAnimationFrame(this.frame);
+            this.frame = 0;
+        }
+    }
+
+    focus() {
+        this.canvas.focus();
+    }
+
+    releaseKeys() {
+        for (let key = 0; key < KEYS_NUM; key++) {
+            this.emu.press_key(key, false);
+        }
+    }
+}
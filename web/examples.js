@@ -0,0 +1,8 @@
+import { ready, Chip8Player } from "./chip8.js"
+
+// Every canvas with a ROM gets its own emulator
+await ready();
+for (const canvas of document.querySelectorAll("canvas[data-rom]")) {
+    const player = new Chip8Player(canvas, 6);
+    player.loadUrl(canvas.dataset.rom).catch(console.error);
+}
@@ -1,66 +1,117 @@
-import init, * as wasm from "./wasm.js"
+import * as wasm from "./wasm.js"
+import { ready, Chip8Player } from "./chip8.js"
+import { Keypad } from "./keypad.js"
 
-const WIDTH = 64;
-const HEIGHT = 32;
-const SCREEN_SCALE = 15;
-
-const TICKS_PER_FRAME = 10;
-let current_frame = 0;
-
-// Canvas as display for emulation
+// Canvas as display for emulation, it has native resolution and gets scaled by CSS
 const canvas = document.getElementById("canvas");
-canvas.width = WIDTH * SCREEN_SCALE;
-canvas.height = HEIGHT * SCREEN_SCALE;
-
-// Refresh the context of canvas
-const ctx = canvas.getContext("2d")
-ctx.fillStyle = "black";
-ctx.fillRect(0, 0, canvas.width, canvas.height);
 
 const input = document.getElementById("fileInput");
 const fileList = document.getElementById("fileList");
 const description = document.getElementById("description");
+const filterList = document.getElementById("filterList");
+const scanlines = document.getElementById("scanlines");
+const fullscreen = document.getElementById("fullscreen");
+const screenshot = document.getElementById("screenshot");
+const record = document.getElementById("record");
+const profile = document.getElementById("profile");
+const cheats = document.getElementById("cheats");
+const applyCheats = document.getElementById("applyCheats");
+let recorder = null;
+let profiling = false;
 
 run().catch(console.error);
 
-// Emulation loop
-function programLoop(emu) {
-    // Run the emulation
-    for (let i = 0; i < TICKS_PER_FRAME; i++) {
-        emu.tick();
-    }
-    emu.time_tick();
+async function run() {
+    await ready();
+    const player = new Chip8Player(canvas);
+    const emu = player.emu;
+    const keypad = new Keypad(document.getElementById("keypad"), player);
 
-    // Clean the screen
+    // Black screen until a game is chosen
+    const ctx = canvas.getContext("2d");
     ctx.fillStyle = "black";
     ctx.fillRect(0, 0, canvas.width, canvas.height);
 
-    // Draw the rest of screen
-    ctx.fillStyle = "white";
-    emu.draw_display(SCREEN_SCALE);
-    current_frame = window.requestAnimationFrame(() => {
-        programLoop(emu);
-    });
-}
+    // Games come from the database, they're all in games/
+    for (const game of wasm.game_list()) {
+        fileList.add(new Option(game.title, game.file));
+    }
 
-async function run() {
-    await init();
-    let emu = new wasm.EmulatorWasm();
+    // Change the way display is scaled
+    filterList.onchange = (event) => {
+        emu.set_filter(event.target.value);
+        player.focus();
+    };
+    scanlines.onchange = (event) => {
+        emu.set_scanlines(event.target.checked);
+        player.focus();
+    };
+    fullscreen.onclick = () => {
+        canvas.requestFullscreen().catch(console.error);
+        player.focus();
+    };
 
-    document.addEventListener("keydown", function(event) {
-        emu.keypress(event, true);
-    })
-    document.addEventListener("keyup", function(event) {
-        emu.keypress(event, false);
-    })
+    // Save current frame the way it's shown on the page
+    screenshot.onclick = () => {
+        const scaled = document.createElement("canvas");
+        scaled.width = canvas.clientWidth;
+        scaled.height = canvas.clientHeight;
+
+        const scaledCtx = scaled.getContext("2d");
+        scaledCtx.imageSmoothingEnabled = false;
+        scaledCtx.drawImage(canvas, 0, 0, scaled.width, scaled.height);
+        scaled.toBlob((blob) => download(blob, "png"));
+        player.focus();
+    };
 
-    // Load new game into emulator
-    input.addEventListener("change", function(event) {
-        // If there was already a file running, stop the animation
-        if (current_frame !== 0) {
-            window.cancelAnimationFrame(current_frame);
+    // Record canvas into a video until pressed again
+    record.onclick = () => {
+        if (recorder !== null) {
+            recorder.stop();
+            recorder = null;
+            record.textContent = "Record";
+        }
+        else {
+            const chunks = [];
+            recorder = new MediaRecorder(canvas.captureStream(60));
+            recorder.ondataavailable = (event) => chunks.push(event.data);
+            recorder.onstop = () => download(new Blob(chunks, {type: "video/webm"}), "webm");
+            recorder.start();
+            record.textContent = "Stop recording";
+        }
+        player.focus();
+    };
+
+    // Count executed instructions until pressed again, then save the heat map
+    profile.onclick = () => {
+        if (profiling) {
+            const report = emu.profile_report();
+            emu.set_profiling(false);
+            download(new Blob([report], {type: "text/plain"}), "txt");
+            profile.textContent = "Profile";
+        }
+        else {
+            emu.set_profiling(true);
+            profile.textContent = "Save profile";
+        }
+        profiling = !profiling;
+        player.focus();
+    };
+
+    // Codes are saved per ROM, so they come back with the game
+    applyCheats.onclick = () => {
+        try {
+            emu.load_cheats(cheats.value);
+            localStorage.setItem(`cheats-${emu.rom_hash()}`, emu.cheats_text());
+        }
+        catch (error) {
+            alert(error);
         }
+        player.focus();
+    };
 
+    // Load new game into emulator
+    input.addEventListener("change", function(event) {
         // Try to get the file
         let file = event.target.files[0];
         if (!file) {
@@ -70,13 +121,12 @@ async function run() {
 
         // Read the file, load it into the emulator
         let fileReader = new FileReader();
-        fileReader.onload = function(event) {
-            let buffer = fileReader.result;
-            const rom = new Uint8Array(buffer);
-            emu.reset();
-            emu.load_data(rom);
-            // Begin emulation
-            programLoop(emu);
+        fileReader.onload = function() {
+            player.load(new Uint8Array(fileReader.result));
+            changeDescription(emu);
+            loadCheats(emu);
+            keypad.update();
+            player.focus();
         }
 
         fileReader.readAsArrayBuffer(file);
@@ -88,148 +138,57 @@ async function run() {
         if (fileName === "") {
             return;
         }
-        const filePath = `./games/${fileName}`;
 
-        changeDescription(fileName);
-
-        // If there was already a file running, stop the animation
-        if (current_frame !== 0) {
-            window.cancelAnimationFrame(current_frame);
-        }
-        emu.reset();
-
-        fetch(filePath)
-            .then(response => response.arrayBuffer())
-            .then(arrayBuffer => {
-                const uint8Array = new Uint8Array(arrayBuffer);
-                emu.load_data(uint8Array);
-                programLoop(emu);
+        player.loadUrl(`./games/${fileName}`)
+            .then(() => {
+                changeDescription(emu);
+                loadCheats(emu);
+                keypad.update();
             }).catch(error => console.error("Error loading file:", error));
-        fileList.blur();
+        player.focus();
     };
 }
 
-function changeDescription(game) {
-    let desc = "<p><h3>" + game + "</h3>";
-    if (game === "TETRIS") {
-        desc +=
-            "Just try to fill the entire line to break blocks, don't let them overflow!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>W / E</i> - move block</li>" +
-            "<li><i>Q</i> - rotate block</li>" +
-            "<li><i>A</i> - make it fall faster</li>" +
-            "</ul></p>";
-    }
-    else if (game === "INVADERS") {
-        desc +=
-            "Beat all the aliens, watch out - they're going much faster with each round!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>Q / E</i> - movement</li>" +
-            "<li><i>W</i> - shoot</li>" +
-            "</ul></p>";
-    }
-    else if (game === "PONG" || game === "PONG2") {
-        desc +=
-            "Just a pong! Try to make your opponent lose, bounce the ball with your paddle!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>1 / Q</i> - Player 1 up/down movement</li>" +
-            "<li><i>4 / R</i> - Player 2 up/down movement</li>" +
-            "</ul></p>";
-    }
-    else if (game === "WIPEOFF") {
-        desc +=
-            "Try to wipe off all blocks, make sure to bounce the ball!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>Q/E</i> - movement</li>" +
-            "</ul></p>";
-    }
-    else if (game === "TANK") {
-        desc +=
-            "Defeat enemies with your tank, don't let them come to close!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>2 / S</i> - vertical movement</li>" +
-            "<li><i>Q / E</i> - horizontal movement</li>" +
-            "<li><i>W</i> - shoot</li>" +
-            "</ul></p>";
-    }
-    else if (game === "TICTAC") {
-        desc = "<p><h3>TIC TAC TOE</h3>" +
-            "Beat your friend in a match of tic-tac-toe, try to get a full line of your shape!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li>1 / 2 / 3 - top row</li>" +
-            "<li><i>Q / W / E</i> - middle row</li>" +
-            "<li><i>A / S / D</i> - bottom row</li>" +
-            "</ul>";
-    }
-    else if (game === "UFO") {
-        desc +=
-            "Try to hit aliens as many times, as possible!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>W</i> - shoot forward</li>" +
-            "<li><i>Q</i> - shoot to the left</li>" +
-            "<li><i>E</i> - shoot to the right</li>" +
-            "</ul></p>";
-    }
-    else if (game === "BLINKY") {
-        desc = desc = "<p><h3>BLINKY</h3><b>I don't know what's going on, but</br>" +
-            "it's something like PacMan</b>";
-    }
-    else if (game === "VERS") {
-        desc = "<p><h3>VERS</h3><b>I don't know what's going on, but</br>" +
-            "Something like Snake, but two???</b>";
-    }
-    else if (game === "BRIX") {
-        desc +=
-            "Break all bricks!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>Q / E</i> - movement</li>" +
-            "</ul></p>";
-    }
-    else if (game === "VBRIX") {
-        desc +=
-            "Break all bricks, now vertically!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>1 / Q</i> - movement</li>" +
-            "<li><i>A</i> - start</li>" +
-            "</ul></p>";
-    }
-    else if (game === "MISSILE") {
-        desc +=
-            "Shoot all blocks, watch out, every shoot makes you go faster!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>S</i> - shoot</li>" +
-            "</ul></p>";
+// Let the user save a file made from the blob
+function download(blob, extension) {
+    const link = document.createElement("a");
+    link.href = URL.createObjectURL(blob);
+    link.download = `chip8-${Date.now()}.${extension}`;
+    link.click();
+    setTimeout(() => URL.revokeObjectURL(link.href), 1000);
+}
+
+// Restore codes saved for the loaded game
+function loadCheats(emu) {
+    const saved = localStorage.getItem(`cheats-${emu.rom_hash()}`) ?? "";
+    try {
+        emu.load_cheats(saved);
     }
-    else if (game === "MERLIN") {
-        desc +=
-            "Basically Simon Says, remember and guess the combination!<br /><br />" +
-            "<b>Keybinds:</b>" +
-            "<ul>" +
-            "<li><i>Q</i> - top left</li>" +
-            "<li><i>W</i> - top right</li>" +
-            "<li><i>A</i> - bottom left</li>" +
-            "<li><i>S</i> - bottom right</li>" +
-            "</ul></p>";
+    catch (error) {
+        console.error("Invalid saved cheats:", error);
     }
-    else if (game === "MAZE") {
-        desc = "<p><h3>MAZE</h3><b>Just made generator</b>";
+    cheats.value = emu.cheats_text();
+}
+
+// Show what the database knows about the loaded game
+function changeDescription(emu) {
+    const info = emu.game_info();
+    if (info === undefined) {
+        description.innerHTML = "<p>Unknown game, it runs with default settings.</p>";
+        return;
     }
-    else if (game === "KALEID") {
-        desc = "<p><h3>KALEID</h3><b>I don't understand it, but impressive, just spam buttons</b>";
+
+    const credits = [info.author, info.year, info.platform].filter((part) => part !== undefined);
+    let desc = `<p><h3>${info.title}</h3><i>${credits.join(", ")}</i><br /><br />`;
+    if (info.description !== undefined) {
+        desc += `${info.description}<br /><br />`;
     }
-    else if (game === "test_opcode.ch8") {
-        desc = "<p><h3>TEST OPCODES</h3>Just check if opcodes are working correctly</p>";
+    if (info.controls.length > 0) {
+        desc += "<b>Keybinds:</b><ul>";
+        for (const control of info.controls) {
+            desc += `<li><i>${control.keys.join(" / ")}</i> - ${control.action}</li>`;
+        }
+        desc += "</ul>";
     }
-
-    description.innerHTML = desc;
+    description.innerHTML = desc + "</p>";
 }
@@ -0,0 +1,62 @@
+// On-screen hex keypad for touch screens, in the layout of COSMAC VIP
+const LAYOUT = [
+    0x1, 0x2, 0x3, 0xC,
+    0x4, 0x5, 0x6, 0xD,
+    0x7, 0x8, 0x9, 0xE,
+    0xA, 0x0, 0xB, 0xF,
+];
+// Short buzz on press, where the device can vibrate
+const HAPTIC_MS = 10;
+
+export class Keypad {
+    constructor(container, player) {
+        this.player = player;
+        this.buttons = new Map();
+        // Every finger presses its own key, so several can be held at once
+        this.pointers = new Map();
+
+        container.classList.add("keypad");
+        for (const key of LAYOUT) {
+            const button = document.createElement("button");
+            button.textContent = key.toString(16).toUpperCase();
+            button.addEventListener("pointerdown", (event) => this.press(event, key));
+            for (const type of ["pointerup", "pointercancel", "pointerleave"]) {
+                button.addEventListener(type, (event) => this.release(event));
+            }
+            // Long press would open the context menu instead of holding the key
+            button.addEventListener("contextmenu", (event) => event.preventDefault());
+            container.appendChild(button);
+            this.buttons.set(key, button);
+        }
+    }
+
+    // Show only keys the loaded game uses, the rest keep their place in the grid
+    update() {
+        const used = new Set(this.player.emu.keypad_keys());
+        for (const [key, button] of this.buttons) {
+            button.style.visibility = used.has(key) ? "visible" : "hidden";
+        }
+    }
+
+    press(event, key) {
+        event.preventDefault();
+        this.pointers.set(event.pointerId, key);
+        this.player.emu.press_key(key, true);
+        this.buttons.get(key).classList.add("pressed");
+        navigator.vibrate?.(HAPTIC_MS);
+    }
+
+    release(event) {
+        const key = this.pointers.get(event.pointerId);
+        if (key === undefined) {
+            return;
+        }
+        this.pointers.delete(event.pointerId);
+
+        // Another finger may still hold the same key
+        if (![...this.pointers.values()].includes(key)) {
+            this.player.emu.press_key(key, false);
+            this.buttons.get(key).classList.remove("pressed");
+        }
+    }
+}
@@ -0,0 +1,142 @@
+/* tslint:disable */
+/* eslint-disable */
+export function main(): void;
+/**
+ * Every game in the database with a file in games/
+ */
+export function game_list(): GameEntry[];
+
+export interface GameControl {
+    keys: string[];
+    action: string;
+}
+
+export interface GameInfo {
+    title: string;
+    author?: string;
+    year?: number;
+    platform: string;
+    description?: string;
+    controls: GameControl[];
+}
+
+export interface GameEntry {
+    file: string;
+    title: string;
+}
+
+export class EmulatorWasm {
+  free(): void;
+  /**
+   * Emulator drawing on the canvas, or headless without one
+   */
+  constructor(canvas?: HTMLCanvasElement | null);
+  /**
+   * Throws when the emulator is headless
+   */
+  draw_display(): void;
+  /**
+   * nearest, scale2x, scale3x, epx or xbr-lite, throws for other names
+   */
+  set_filter(name: string): void;
+  set_scanlines(enabled: boolean): void;
+  keypress(event: KeyboardEvent, pressed: boolean): void;
+  /**
+   * Press or release Chip8 key 0-F directly, throws for other keys
+   */
+  press_key(key: number, pressed: boolean): void;
+  load_data(data: Uint8Array): void;
+  ticks_per_frame(): number;
+  game_info(): GameInfo | undefined;
+  /**
+   * Keys the touch keypad should show, all of them for unknown games
+   */
+  keypad_keys(): Uint8Array;
+  set_profiling(enabled: boolean): void;
+  profile_report(): string | undefined;
+  /**
+   * SHA-1 of the loaded ROM in hex
+   */
+  rom_hash(): string;
+  cheat_search_start(): void;
+  /**
+   * compare is "equal", "changed", "unchanged", "increased" or "decreased", returns candidates left
+   */
+  cheat_search(compare: string, value?: number | null): number;
+  cheat_candidates(): Uint16Array;
+  add_cheat(code: string): void;
+  set_cheat_enabled(index: number, enabled: boolean): void;
+  remove_cheat(index: number): void;
+  cheats_text(): string;
+  load_cheats(text: string): void;
+  /**
+   * vip, schip or modern, throws for other names
+   */
+  set_quirks(name: string): void;
+  /**
+   * Registers V0-VF
+   */
+  registers(): Uint8Array;
+  set_register(index: number, value: number): void;
+  pc(): number;
+  i_reg(): number;
+  sp(): number;
+  stack(): Uint16Array;
+  delay_timer(): number;
+  sound_timer(): number;
+  sound_active(): boolean;
+  frame(): number;
+  /**
+   * Copy of the whole memory
+   */
+  memory(): Uint8Array;
+  read_memory(address: number, length: number): Uint8Array;
+  write_memory(address: number, data: Uint8Array): void;
+  /**
+   * 64x32 pixels row by row, 1 when lit
+   */
+  display(): Uint8Array;
+  /**
+   * 64x32 RGBA pixels in colors of the game
+   */
+  framebuffer(): Uint8Array;
+  save_state(): Uint8Array;
+  /**
+   * Throws for states of a different size or version
+   */
+  load_state(state: Uint8Array): void;
+  /**
+   * Instructions of one 60Hz frame, then timers
+   */
+  run_frame(): void;
+  tick(): void;
+  time_tick(): void;
+  reset(): void;
+}
+
+export type InitInput = RequestInfo | URL | Response | BufferSource | WebAssembly.Module;
+
+export interface InitOutput {
+  readonly memory: WebAssembly.Memory;
+}
+
+export type SyncInitInput = BufferSource | WebAssembly.Module;
+/**
+* Instantiates the given `module`, which can either be bytes or
+* a precompiled `WebAssembly.Module`.
+*
+* @param {{ module: SyncInitInput }} module - Passing `SyncInitInput` directly is deprecated.
+*
+* @returns {InitOutput}
+*/
+export function initSync(module: { module: SyncInitInput } | SyncInitInput): InitOutput;
+
+/**
+* If `module_or_path` is {RequestInfo} or {URL}, makes a request and
+* for everything else, calls `WebAssembly.instantiate` directly.
+*
+* @param {{ module_or_path: InitInput | Promise<InitInput> }} module_or_path - Passing `InitInput` directly is deprecated.
+*
+* @returns {Promise<InitOutput>}
+*/
+export default function __wbg_init (module_or_path?: { module_or_path: InitInput | Promise<InitInput> } | InitInput | Promise<InitInput>): Promise<InitOutput>;